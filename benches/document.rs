@@ -0,0 +1,46 @@
+//! Rough timing of re-wrapping a [Document] at several widths, e.g., as a view
+//! is resized, run with `cargo bench`.
+//!
+//! A document is lexed, parsed and merged once, so re-wrapping it should beat
+//! wrapping the same input from scratch at each width.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use tortilla::{Document, Salsa, Toppings, wrap};
+
+const PARAGRAPHS: usize = 10_000;
+const WIDTHS: [usize; 5] = [40, 60, 80, 100, 120];
+const RUNS: usize = 5;
+
+fn fastest(mut run: impl FnMut(Toppings) -> usize) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            for width in WIDTHS {
+                black_box(run(Toppings::default().width(width)));
+            }
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let input = (0..PARAGRAPHS)
+        .map(|idx| {
+            format!(
+                "// Paragraph number {idx}, with a few more words to\n\
+                 // wrap across several lines of a comment block.\n\n"
+            )
+        })
+        .collect::<String>();
+
+    let document = Document::new(input.clone(), &Toppings::default());
+
+    let rewrap = fastest(|toppings| document.wrap::<Salsa>(toppings).map(str::len).sum());
+    let one_shot = fastest(|toppings| wrap::<Salsa>(&input, toppings).map(str::len).sum());
+
+    println!("document re-wraps: {rewrap:?}");
+    println!("one-shot wraps:    {one_shot:?}");
+}
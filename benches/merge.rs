@@ -0,0 +1,41 @@
+//! Rough timing of paragraph merging on large inputs, run with `cargo bench`.
+//!
+//! Blank-separated single-line paragraphs never merge, so the time spent on
+//! them is mostly the cost of peeking at the following line. Compare against
+//! the same words in a single paragraph to see whether it matters.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use tortilla::{Salsa, Toppings, wrap};
+
+const PARAGRAPHS: usize = 100_000;
+const RUNS: usize = 5;
+
+fn fastest(input: &str) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let bytes = wrap::<Salsa>(input, Toppings::default())
+                .map(str::len)
+                .sum::<usize>();
+            black_box(bytes);
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let line = |idx| format!("paragraph number {idx} with a few more words");
+
+    let separate = (0..PARAGRAPHS)
+        .map(|idx| line(idx) + "\n\n")
+        .collect::<String>();
+    let merged = (0..PARAGRAPHS)
+        .map(|idx| line(idx) + "\n")
+        .collect::<String>();
+
+    println!("single-line paragraphs: {:?}", fastest(&separate));
+    println!("one merged paragraph:   {:?}", fastest(&merged));
+}
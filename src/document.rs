@@ -0,0 +1,218 @@
+use std::ops::Range;
+
+use super::merge::paragraph_boundary;
+use super::wrap::Sauce;
+use super::{Line, Paragraphs, Toppings, Whitespace, Wrap, merged};
+
+/// Text that has been split into paragraphs once, to be wrapped any number of
+/// times, e.g., whenever the width of a view changes.
+///
+/// Lexing, parsing and merging of lines happens only once, when constructing
+/// the document. The toppings passed to [Document::new] thus determine how
+/// paragraphs are formed (e.g., [Toppings::collapse_spaces] or
+/// [Toppings::overflow_only]), while the toppings passed to [Document::wrap]
+/// determine how they are wrapped.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{Document, Salsa, Toppings};
+///
+/// let document = Document::new("foo bar\nbaz\n".to_owned(), &Toppings::default());
+///
+/// let narrow = document.wrap::<Salsa>(Toppings::default().width(7));
+/// assert_eq!(narrow.collect::<String>(), "foo bar\nbaz\n");
+///
+/// let wide = document.wrap::<Salsa>(Toppings::default().width(80));
+/// assert_eq!(wide.collect::<String>(), "foo bar baz\n");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Document {
+    input: String,
+    lines: Vec<RangeLine>,
+}
+
+/// A [Line] with byte ranges into the document's input instead of slices.
+#[derive(Debug, Clone)]
+struct RangeLine {
+    gutter: Option<Range<usize>>,
+    indent: Whitespace,
+    raw_indent: Option<Range<usize>>,
+    comment: Option<Range<usize>>,
+    padding: Whitespace,
+    bullet: Option<Range<usize>>,
+    hanging: Option<usize>,
+    words: Vec<Range<usize>>,
+    gaps: Vec<(usize, Whitespace)>,
+    verbatim: Option<Range<usize>>,
+    trailing_space: Option<Range<usize>>,
+    heading: bool,
+    continuation: Option<Range<usize>>,
+    newline: bool,
+}
+
+impl Document {
+    /// Split `input` into paragraphs the way [wrap](crate::wrap) does.
+    pub fn new(input: String, toppings: &Toppings) -> Self {
+        let range = |text: &str| {
+            let start = text.as_ptr() as usize - input.as_ptr() as usize;
+            start..start + text.len()
+        };
+
+        let (lines, _) = merged(&input, toppings.clone(), paragraph_boundary);
+        let lines = lines
+            .map(|line| RangeLine {
+                gutter: line.gutter.map(range),
+                indent: line.indent,
+                raw_indent: line.raw_indent.map(range),
+                comment: line.comment.map(range),
+                padding: line.padding,
+                bullet: line.bullet.map(range),
+                hanging: line.hanging,
+                words: line.words.into_iter().map(range).collect(),
+                gaps: line.gaps,
+                verbatim: line.verbatim.map(range),
+                trailing_space: line.trailing_space.map(range),
+                heading: line.heading,
+                continuation: line.continuation.map(range),
+                newline: line.newline,
+            })
+            .collect();
+
+        Self { input, lines }
+    }
+
+    /// The original input.
+    pub fn as_str(&self) -> &str {
+        &self.input
+    }
+
+    /// Wrap the document, see [wrap](crate::wrap).
+    pub fn wrap<S: Sauce>(&self, toppings: Toppings) -> Wrap<'_, S> {
+        let lines = Lines {
+            document: self,
+            lines: self.lines.iter(),
+        };
+
+        Wrap::new(Paragraphs::Document(lines), toppings.detected(&self.input))
+    }
+
+    fn line(&self, line: &RangeLine) -> Line<'_> {
+        let slice = |range: &Range<usize>| &self.input[range.clone()];
+
+        Line {
+            gutter: line.gutter.as_ref().map(slice),
+            indent: line.indent,
+            raw_indent: line.raw_indent.as_ref().map(slice),
+            comment: line.comment.as_ref().map(slice),
+            padding: line.padding,
+            bullet: line.bullet.as_ref().map(slice),
+            hanging: line.hanging,
+            words: line.words.iter().map(slice).collect(),
+            gaps: line.gaps.clone(),
+            verbatim: line.verbatim.as_ref().map(slice),
+            trailing_space: line.trailing_space.as_ref().map(slice),
+            heading: line.heading,
+            continuation: line.continuation.as_ref().map(slice),
+            newline: line.newline,
+        }
+    }
+}
+
+/// The paragraphs of a [Document], see [Document::wrap].
+pub(super) struct Lines<'t> {
+    document: &'t Document,
+    lines: std::slice::Iter<'t, RangeLine>,
+}
+
+impl<'t> Iterator for Lines<'t> {
+    type Item = Line<'t>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lines.next().map(|line| self.document.line(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FinalNewline, Guacamole, Salsa, Toppings, wrap};
+
+    const INPUT: &str = "\
+// Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod
+// tempor incididunt ut labore et dolore magna aliqua.
+//
+// - Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi
+//   ut aliquip ex ea commodo consequat.
+\tDuis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore
+\teu fugiat nulla pariatur.
+";
+
+    #[test]
+    fn equals_wrap() {
+        let document = super::Document::new(INPUT.to_owned(), &Toppings::default());
+
+        for width in [1, 10, 20, 40, 80, 1000] {
+            let toppings = Toppings::default().width(width);
+
+            assert_eq!(
+                document.wrap::<Salsa>(toppings.clone()).collect::<String>(),
+                wrap::<Salsa>(INPUT, toppings.clone()).collect::<String>()
+            );
+            assert_eq!(
+                document
+                    .wrap::<Guacamole>(toppings.clone())
+                    .collect::<String>(),
+                wrap::<Guacamole>(INPUT, toppings).collect::<String>()
+            );
+        }
+    }
+
+    #[test]
+    fn equals_wrap_with_gaps() {
+        let toppings = Toppings::default().width(30).collapse_spaces(false);
+        let document = super::Document::new(INPUT.replace(", ", ",  "), &toppings);
+
+        assert_eq!(
+            document.wrap::<Salsa>(toppings.clone()).collect::<String>(),
+            wrap::<Salsa>(document.as_str(), toppings).collect::<String>()
+        );
+    }
+
+    #[test]
+    fn stats() {
+        let document = super::Document::new(INPUT.to_owned(), &Toppings::default());
+        let toppings = Toppings::default().width(30);
+
+        let mut wrapped = document.wrap::<Salsa>(toppings.clone());
+        let mut expected = wrap::<Salsa>(INPUT, toppings);
+
+        assert_eq!(
+            wrapped.by_ref().collect::<String>(),
+            expected.by_ref().collect::<String>()
+        );
+        assert_eq!(wrapped.stats(), expected.stats());
+        assert_eq!(wrapped.stats().lines, 15);
+    }
+
+    #[test]
+    fn final_newline() {
+        let toppings = Toppings::default()
+            .width(7)
+            .final_newline(FinalNewline::EnsureNonEmpty);
+
+        for input in ["", "foo bar baz", "foo bar baz\n", "foo\n\n"] {
+            let document = super::Document::new(input.to_owned(), &toppings);
+
+            assert_eq!(
+                document.wrap::<Salsa>(toppings.clone()).collect::<String>(),
+                wrap::<Salsa>(input, toppings.clone()).collect::<String>()
+            );
+        }
+
+        let document = super::Document::new("foo bar baz".to_owned(), &toppings);
+        assert_eq!(
+            document.wrap::<Salsa>(toppings).collect::<String>(),
+            "foo bar\nbaz\n"
+        );
+    }
+}
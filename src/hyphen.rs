@@ -0,0 +1,97 @@
+//! Hyphenation points of words, found with the TeX patterns bundled by
+//! [hypher], see [crate::Toppings::hyphenate].
+//!
+//! Also see: <https://github.com/hyphenation/tex-hyphen>
+
+use hypher::Lang;
+
+/// The language of a tag such as `en` or `en-US`, if its patterns are built
+/// in.
+pub(crate) fn lang(tag: &str) -> Option<Lang> {
+    let code = tag.split(['-', '_']).next()?.to_ascii_lowercase();
+    Lang::from_iso(code.as_bytes().try_into().ok()?)
+}
+
+/// Break `word` apart at its hyphenation points. Words are only hyphenated if
+/// they consist of letters, apart from any punctuation in front or after,
+/// which stays with the first or last fragment.
+pub(crate) fn fragments(word: &str, lang: Lang) -> Vec<&str> {
+    let start = word.len()
+        - word
+            .trim_start_matches(|c: char| c.is_ascii_punctuation())
+            .len();
+    let core = word[start..].trim_end_matches(|c: char| c.is_ascii_punctuation());
+
+    if core.is_empty() || !core.chars().all(char::is_alphabetic) {
+        return vec![word];
+    }
+
+    // The offsets of the syllables' ends, apart from the last one.
+    let mut points = hypher::hyphenate(core, lang)
+        .scan(start, |end, syllable| {
+            *end += syllable.len();
+            Some(*end)
+        })
+        .collect::<Vec<_>>();
+    points.pop();
+
+    let starts = std::iter::once(0).chain(points.iter().copied());
+    let ends = points.iter().copied().chain(std::iter::once(word.len()));
+    starts
+        .zip(ends)
+        .map(|(start, end)| &word[start..end])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hyphenate(word: &str) -> String {
+        fragments(word, lang("en").unwrap()).join("-")
+    }
+
+    #[test]
+    fn words() {
+        for (word, expected) in [
+            ("internationalization", "in-ter-na-tion-al-iza-tion"),
+            ("hyphenation", "hy-phen-ation"),
+            ("possible", "pos-si-ble"),
+            ("present", "pre-sent"),
+            ("another", "an-other"),
+            ("everything", "every-thing"),
+        ] {
+            assert_eq!(hyphenate(word), expected);
+        }
+    }
+
+    #[test]
+    fn short_words() {
+        assert_eq!(hyphenate("tell"), "tell");
+        assert_eq!(hyphenate("bring"), "bring");
+    }
+
+    #[test]
+    fn punctuation() {
+        assert_eq!(hyphenate("(hyphenation),"), "(hy-phen-ation),");
+    }
+
+    #[test]
+    fn not_letters() {
+        assert_eq!(hyphenate("hyphenation42"), "hyphenation42");
+        assert_eq!(hyphenate("..."), "...");
+    }
+
+    #[test]
+    fn case() {
+        assert_eq!(hyphenate("Hyphenation"), "Hy-phen-ation");
+    }
+
+    #[test]
+    fn languages() {
+        assert!(lang("en-US").is_some());
+        assert!(lang("EN_gb").is_some());
+        assert!(lang("de").is_none());
+        assert!(lang("english").is_none());
+    }
+}
@@ -11,7 +11,7 @@ pub(super) struct Lex<'t> {
     input: &'t str,
     inner: GraphemeIndices<'t>,
     state: State,
-    pending: Option<Token<'static>>,
+    pending: Option<(usize, Token<'static>)>,
 }
 
 impl<'t> Lex<'t> {
@@ -25,39 +25,49 @@ impl<'t> Lex<'t> {
     }
 }
 
-fn word_break(grapheme: &str) -> Option<Token<'static>> {
+/// Token that ends a word, if any. A carriage return that is `last` in the
+/// input is taken as a CRLF (`\r\n`) newline cut short.
+fn word_break(grapheme: &str, last: bool) -> Option<Token<'static>> {
     Some(match grapheme {
         " " => Token::Space,
         "\t" => Token::Tab,
         "\n" => Token::Newline(Newline::LF),
         "\r\n" => Token::Newline(Newline::CRLF),
+        "\r" if last => Token::Newline(Newline::CRLF),
         _ => return None,
     })
 }
 
 impl<'t> Iterator for Lex<'t> {
-    type Item = Token<'t>;
+    /// A token, along with the byte offset it starts at in the input.
+    type Item = (usize, Token<'t>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(token) = self.pending.take() {
-            return Some(token);
+        if let Some(pending) = self.pending.take() {
+            return Some(pending);
         }
 
         for (byte_idx, grapheme) in self.inner.by_ref() {
+            let last = byte_idx + grapheme.len() == self.input.len();
+
             match self.state {
                 State::Clean => {
-                    if let Some(token) = word_break(grapheme) {
-                        return Some(token);
+                    if let Some(token) = word_break(grapheme, last) {
+                        return Some((byte_idx, token));
                     } else {
                         self.state = State::Word(byte_idx);
                     }
                 }
 
                 State::Word(start_idx) => {
-                    if let Some(token) = word_break(grapheme) {
+                    // Both ends of the word are grapheme cluster boundaries, so
+                    // a cluster such as a ZWJ sequence is never split, even if
+                    // it makes up the whole word.
+                    if let Some(token) = word_break(grapheme, last) {
                         self.state = State::Clean;
-                        self.pending = Some(token);
-                        return Some(Token::Word(&self.input[start_idx..byte_idx]));
+                        self.pending = Some((byte_idx, token));
+                        let word = Token::Word(&self.input[start_idx..byte_idx]);
+                        return Some((start_idx, word));
                     }
                 }
             }
@@ -65,7 +75,7 @@ impl<'t> Iterator for Lex<'t> {
 
         if let State::Word(start_idx) = self.state {
             self.state = State::Clean;
-            return Some(Token::Word(&self.input[start_idx..]));
+            return Some((start_idx, Token::Word(&self.input[start_idx..])));
         }
 
         None
@@ -77,7 +87,7 @@ mod tests {
     use crate::tokens;
 
     fn lex(input: &str) -> Vec<crate::Token<'_>> {
-        super::Lex::new(input).collect()
+        super::Lex::new(input).map(|(_, token)| token).collect()
     }
 
     #[test]
@@ -115,6 +125,17 @@ mod tests {
         assert_eq!(lex("\r\n\n\n\r\n"), tokens![crlf, lf, lf, crlf],);
     }
 
+    #[test]
+    fn mixed_newlines_between_words() {
+        assert_eq!(lex("a\n\r\n\nb"), tokens!["a", lf, crlf, lf, "b"]);
+    }
+
+    #[test]
+    fn trailing_cr() {
+        assert_eq!(lex("a\rb\r"), tokens!["a\rb", crlf]);
+        assert_eq!(lex("\r"), tokens![crlf]);
+    }
+
     #[test]
     fn one_letter_word() {
         assert_eq!(lex("a"), tokens!["a"]);
@@ -136,6 +157,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn indexed() {
+        assert_eq!(
+            super::Lex::new("é foo\tbar\r\n").collect::<Vec<_>>(),
+            [0, 2, 3, 6, 7, 10]
+                .into_iter()
+                .zip(tokens!["é", s, "foo", t, "bar", crlf])
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn zwj_sequences() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}";
+        let input = format!("{family} {family}\t{family}\r\n{family}");
+
+        assert_eq!(
+            super::Lex::new(&input).collect::<Vec<_>>(),
+            [0, 25, 26, 51, 52, 77, 79]
+                .into_iter()
+                .zip(tokens![family, s, family, t, family, crlf, family])
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn trailing_joiner() {
+        // A joiner or combining mark sticks to whatever precedes it, so
+        // whitespace followed by one doesn't end a word.
+        assert_eq!(lex("a\u{200D} b"), tokens!["a\u{200D}", s, "b"]);
+        assert_eq!(lex("a \u{301}b"), tokens!["a \u{301}b"]);
+    }
+
     #[test]
     fn mixed_paragraphs() {
         assert_eq!(
@@ -1,14 +1,29 @@
+mod document;
+#[cfg(feature = "hyphen")]
+mod hyphen;
 mod lex;
 mod merge;
 mod parse;
 mod wrap;
 
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, BufRead, Read};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::Arc;
+
 use lex::Lex;
-use merge::Merge;
+use merge::{Merge, MergeState};
 use parse::Parse;
-use wrap::{LineWrap, Sauce};
+use wrap::LineWrap;
 
-pub use wrap::{Guacamole, Salsa};
+pub use document::Document;
+pub use merge::paragraph_boundary;
+pub use wrap::{
+    Auto, Guacamole, Minimax, Mole, Penalties, Salsa, SalsaMinLines, Sauce, WordsPerLine,
+    break_indices,
+};
 
 /// Newline characters.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -36,8 +51,9 @@ impl Newline {
     }
 }
 
+/// A lexical token, see [tokenize_indexed].
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-enum Token<'t> {
+pub enum Token<'t> {
     /// A space character (' ').
     Space,
     /// A tab character (`\t`).
@@ -75,20 +91,288 @@ impl Whitespace {
 
 #[derive(Debug, Clone, PartialEq)]
 struct Line<'t> {
+    /// Line number and the whitespace preceding it, see
+    /// [Toppings::line_numbers].
+    gutter: Option<&'t str>,
     indent: Whitespace,
+    /// Original text of the indentation if it mixes tabs and spaces, see
+    /// [Toppings::exact_indent]. [Line::indent] then holds spaces of equal
+    /// width.
+    raw_indent: Option<&'t str>,
     comment: Option<&'t str>,
     padding: Whitespace,
     bullet: Option<&'t str>,
+    /// Spaces replacing the bullet on continuation lines, as found in input,
+    /// see [Toppings::preserve_continuation_indent] and
+    /// [Toppings::continuation].
+    hanging: Option<usize>,
     words: Vec<&'t str>,
+    /// Original whitespace preceding a word, keyed by the word's index. Only
+    /// recorded if [Toppings::collapse_spaces] is disabled.
+    gaps: Vec<(usize, Whitespace)>,
+    /// Original text of the line (without its newline character) if it is to
+    /// be emitted as is, rather than wrapped.
+    verbatim: Option<&'t str>,
+    /// Whitespace at the end of the line that separates it from the next,
+    /// as far as it is emitted, i.e., empty if it is dropped. See
+    /// [Toppings::trailing_space_breaks],
+    /// [Toppings::preserve_trailing_whitespace] and [Toppings::markdown].
+    trailing_space: Option<&'t str>,
+    /// Whether the line is a markdown heading, see [Toppings::markdown].
+    heading: bool,
+    /// Token that ended the line to continue it on the next one, and is to
+    /// be emitted at each break, see [Toppings::continuation].
+    continuation: Option<&'t str>,
     newline: bool,
 }
 
+/// Split text into tokens, along with the byte offset each token starts at in
+/// the input. This is the first stage of [wrap], and can be used to map wrapped
+/// output back to its source.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{tokenize_indexed, Newline, Token};
+///
+/// let tokens = tokenize_indexed("foo\tbär\n").collect::<Vec<_>>();
+///
+/// assert_eq!(tokens, vec![
+///     (0, Token::Word("foo")),
+///     (3, Token::Tab),
+///     (4, Token::Word("bär")),
+///     (8, Token::Newline(Newline::LF)),
+/// ]);
+/// ```
+pub fn tokenize_indexed(input: &str) -> impl Iterator<Item = (usize, Token<'_>)> {
+    Lex::new(input)
+}
+
+/// A read-only view of a parsed line, see [wrap_with_boundary].
+#[derive(Debug, Clone, Copy)]
+pub struct LineInfo<'a>(
+    &'a Line<'a>,
+    /// Whether characters of ambiguous width count as wide, see
+    /// [Toppings::cjk].
+    bool,
+    &'a Tabs,
+);
+
+impl<'a> LineInfo<'a> {
+    /// Width of the leading indentation, counting each tab character (`\t`)
+    /// as `tabs` columns, unless [Toppings::tab_width_by_level] is set.
+    pub fn indent_width(&self, tabs: usize) -> usize {
+        match (self.0.indent, &self.2.levels) {
+            (Whitespace::Tab(count), None) => count.saturating_mul(tabs),
+            (indent, _) => self.2.indent_width(indent),
+        }
+    }
+
+    /// The comment token, if any (e.g., `//` or `#`).
+    pub fn comment(&self) -> Option<&'a str> {
+        self.0.comment
+    }
+
+    /// The bullet token, if any (e.g., `-` or `1.`).
+    pub fn bullet(&self) -> Option<&'a str> {
+        self.0.bullet
+    }
+
+    /// The words following the indentation, comment token and bullet token.
+    pub fn words(&self) -> &'a [&'a str] {
+        &self.0.words
+    }
+
+    /// Whether the line is devoid of any words (it may still consist of
+    /// indentation, a comment token or a bullet token).
+    pub fn is_empty(&self) -> bool {
+        self.0.words.is_empty()
+    }
+}
+
+/// Which lines are padded with trailing spaces, see [Toppings::fill].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fill {
+    /// No padding, lines end right after their last word.
+    #[default]
+    Ragged,
+    /// Pad all lines but the last one of each paragraph.
+    AllButLast,
+    /// Pad all lines that contain words.
+    All,
+}
+
+/// Horizontal alignment of lines, see [Toppings::align].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Align {
+    /// Lines start right after their indentation.
+    #[default]
+    Left,
+    /// Lines end at the maximum line width.
+    Right,
+    /// Lines are centered within the maximum line width.
+    Center,
+    /// Each paragraph is aligned according to the class of its first line.
+    /// [LineClass::Heading] is centered, everything else is aligned to the
+    /// left. The mapping can be changed with [Toppings::auto_align].
+    Auto,
+}
+
+/// Whether output ends with a newline, see [Toppings::final_newline].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FinalNewline {
+    /// Output ends with a newline if and only if the input does.
+    #[default]
+    Preserve,
+    /// Output ends with a newline unless it is empty.
+    EnsureNonEmpty,
+}
+
+/// What to put in place of a line number on wrapped continuation lines, see
+/// [Toppings::line_numbers].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gutter {
+    /// Repeat the line number.
+    Repeat,
+    /// Replace the line number with spaces of equal width.
+    Blank,
+}
+
+/// Whether two adjacent words may end up on separate lines, see
+/// [Toppings::keep].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepRule {
+    /// The words may be separated by a line break.
+    Allow,
+    /// The first word must stay on the same line as the word following it.
+    KeepWithNext,
+    /// The second word must stay on the same line as the word preceding it.
+    KeepWithPrevious,
+}
+
+type KeepFn = dyn Fn(&str, &str) -> KeepRule + Send + Sync;
+
+type TabLevelFn = dyn Fn(usize) -> usize + Send + Sync;
+
+type AutoAlignFn = dyn Fn(LineClass) -> Align + Send + Sync;
+
+/// A closure, shared between clones of [Toppings], e.g., a [KeepRule]
+/// predicate.
+struct Shared<F: ?Sized>(Arc<F>);
+
+impl<F: ?Sized> Clone for Shared<F> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for Shared<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Shared(..)")
+    }
+}
+
+impl<F: ?Sized> PartialEq for Shared<F> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<F: ?Sized> Eq for Shared<F> {}
+
+/// How wide tab characters (`\t`) are, see [Toppings::tabs] and
+/// [Toppings::tab_width_by_level].
+#[derive(Debug, Clone)]
+struct Tabs {
+    width: usize,
+    levels: Option<Shared<TabLevelFn>>,
+}
+
+impl Tabs {
+    /// Width of whitespace other than indentation, e.g., padding.
+    fn width(&self, whitespace: Whitespace) -> usize {
+        match whitespace {
+            Whitespace::Space(count) => count,
+            Whitespace::Tab(count) => count.saturating_mul(self.width),
+        }
+    }
+
+    /// Width of the indentation of a line, where tabs count by their level of
+    /// indentation if [Toppings::tab_width_by_level] is set.
+    fn indent_width(&self, indent: Whitespace) -> usize {
+        match (indent, &self.levels) {
+            (Whitespace::Tab(count), Some(levels)) => (0..count)
+                .map(|level| (levels.0)(level))
+                .fold(0, usize::saturating_add),
+            (indent, _) => self.width(indent),
+        }
+    }
+}
+
 /// Parameters for line breaking algorithms & formatting.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Toppings {
     tabs: usize,
     width: usize,
     newline: Newline,
+    detect_newline: bool,
+    collapse_spaces: bool,
+    break_long_words: bool,
+    protect_urls: bool,
+    #[cfg(feature = "regex")]
+    protect: Option<Shared<regex::Regex>>,
+    enforce_width: bool,
+    intra_word_penalty: Option<usize>,
+    #[cfg(feature = "hyphen")]
+    hyphenate: Option<hypher::Lang>,
+    keep: Option<Shared<KeepFn>>,
+    overflow_only: bool,
+    reflow: bool,
+    continuation_column: Option<usize>,
+    hanging: Option<usize>,
+    preserve_continuation_indent: bool,
+    indent_by_width: bool,
+    lenient_comment_indent: bool,
+    single_blank_between_paragraphs: bool,
+    max_blank_lines: Option<usize>,
+    trailing_space_on_break: bool,
+    list_indent: Option<usize>,
+    align_list_numbers: bool,
+    first_line_indent: isize,
+    output_indent: usize,
+    fill: Fill,
+    justify: bool,
+    align: Align,
+    auto_align: Option<Shared<AutoAlignFn>>,
+    final_newline: FinalNewline,
+    horizontal_rules: bool,
+    punctuation_lines: bool,
+    line_numbers: Option<Gutter>,
+    restructured_text: bool,
+    block_comments: bool,
+    max_indent: Option<usize>,
+    exact_indent: bool,
+    expand_tabs: bool,
+    indent_unit: Option<usize>,
+    keep_brackets: bool,
+    break_before_code: Option<usize>,
+    diff: bool,
+    metadata: bool,
+    trailing_space_breaks: bool,
+    preserve_trailing_whitespace: bool,
+    markdown: bool,
+    continuation: Option<String>,
+    min_last_line_words: usize,
+    words_per_line: usize,
+    paragraph_separator: Option<String>,
+    comment_tokens: Vec<String>,
+    min_width: usize,
+    bullet_markers: Vec<String>,
+    alphabetic_bullets: bool,
+    cjk: bool,
+    penalties: Penalties,
+    drop_paragraph_separators: bool,
+    tab_levels: Option<Shared<TabLevelFn>>,
 }
 
 impl Default for Toppings {
@@ -99,6 +383,70 @@ impl Default for Toppings {
             tabs: 4,
             width: 80,
             newline: Newline::default(),
+            detect_newline: false,
+            collapse_spaces: true,
+            break_long_words: false,
+            protect_urls: false,
+            #[cfg(feature = "regex")]
+            protect: None,
+            enforce_width: false,
+            intra_word_penalty: None,
+            #[cfg(feature = "hyphen")]
+            hyphenate: None,
+            keep: None,
+            overflow_only: false,
+            reflow: true,
+            continuation_column: None,
+            hanging: None,
+            preserve_continuation_indent: false,
+            indent_by_width: false,
+            lenient_comment_indent: false,
+            single_blank_between_paragraphs: false,
+            max_blank_lines: None,
+            trailing_space_on_break: false,
+            list_indent: None,
+            align_list_numbers: false,
+            first_line_indent: 0,
+            output_indent: 0,
+            restructured_text: false,
+            block_comments: false,
+            max_indent: None,
+            exact_indent: false,
+            expand_tabs: false,
+            indent_unit: None,
+            keep_brackets: false,
+            break_before_code: None,
+            diff: false,
+            metadata: false,
+            trailing_space_breaks: false,
+            preserve_trailing_whitespace: false,
+            markdown: false,
+            continuation: None,
+            min_last_line_words: 0,
+            words_per_line: 1,
+            paragraph_separator: None,
+            cjk: true,
+            penalties: Penalties::default(),
+            comment_tokens: parse::COMMENT_TOKENS
+                .iter()
+                .map(|token| token.to_string())
+                .collect(),
+            bullet_markers: parse::BULLET_MARKERS
+                .iter()
+                .map(|marker| marker.to_string())
+                .collect(),
+            alphabetic_bullets: false,
+            min_width: 0,
+            drop_paragraph_separators: false,
+            tab_levels: None,
+            fill: Fill::Ragged,
+            justify: false,
+            align: Align::Left,
+            auto_align: None,
+            final_newline: FinalNewline::Preserve,
+            horizontal_rules: true,
+            punctuation_lines: false,
+            line_numbers: None,
         }
     }
 }
@@ -111,14 +459,43 @@ impl Toppings {
     ///
     /// 1. Any combination of indentation, comment token, bullet token and/or
     ///    indentation of the bullet token following a comment token is never
-    ///    wrapped, and may exceed maximum line width by itself.
+    ///    wrapped, and may exceed maximum line width by itself. Words following
+    ///    it may exceed maximum line width, too, if [Toppings::min_width] is
+    ///    set.
     ///
     /// 2. Words that exceed maximum line width by themselves (or in combination
-    ///    with case 1.) are not broken apart and get placed on their own line.
+    ///    with case 1.) are not broken apart and get placed on their own line,
+    ///    unless [Toppings::break_long_words] is enabled.
     pub fn width(self, width: usize) -> Self {
         Self { width, ..self }
     }
 
+    /// The minimum width left for words on each line, regardless of how much
+    /// of [Toppings::width] indentation, comment token and bullet token take
+    /// up. The default value is 0.
+    ///
+    /// Deeply indented lines otherwise end up with a single word per line, or
+    /// all words on a single line if there is no room left at all. Lines may
+    /// exceed maximum line width as a result.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "\t\t// foo bar baz\n";
+    ///
+    /// let toppings = Toppings::default().width(14);
+    /// let output = wrap::<Salsa>(input, toppings.clone()).collect::<String>();
+    /// assert_eq!(output, "\t\t// foo\n\t\t// bar\n\t\t// baz\n");
+    ///
+    /// let output = wrap::<Salsa>(input, toppings.min_width(7)).collect::<String>();
+    /// assert_eq!(output, "\t\t// foo bar\n\t\t// baz\n");
+    /// ```
+    pub fn min_width(self, min_width: usize) -> Self {
+        Self { min_width, ..self }
+    }
+
     /// How much a tab character (`\t`) contributes to line width calculation.
     /// The default value is 4.
     ///
@@ -132,105 +509,3811 @@ impl Toppings {
         Self { tabs, ..self }
     }
 
+    /// Whether characters of ambiguous width (e.g., `•` or `§`) count as wide
+    /// (2 columns), as is common in East Asian contexts, rather than narrow (1
+    /// column). Enabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "§§ §§ §§";
+    ///
+    /// let toppings = Toppings::default().width(9);
+    /// let output = wrap::<Salsa>(input, toppings.clone()).collect::<String>();
+    /// assert_eq!(output, "§§ §§\n§§");
+    ///
+    /// let output = wrap::<Salsa>(input, toppings.cjk(false)).collect::<String>();
+    /// assert_eq!(output, "§§ §§ §§");
+    /// ```
+    pub fn cjk(self, cjk: bool) -> Self {
+        Self { cjk, ..self }
+    }
+
+    /// Penalties for the raggedness of lines, see [Penalties]. Only [Mole]
+    /// takes these into account.
+    pub fn penalties(self, penalties: Penalties) -> Self {
+        Self { penalties, ..self }
+    }
+
+    /// How much each tab character (`\t`) of indentation contributes to line
+    /// width calculation, depending on its level of indentation (starting at
+    /// 0). Unset by default, in which case each tab contributes
+    /// [Toppings::tabs] columns.
+    ///
+    /// Only tabs that are part of a line's indentation are affected, e.g., for
+    /// editors that display the first level of indentation narrower than the
+    /// following ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default()
+    ///     .width(14)
+    ///     .tab_width_by_level(|level| if level == 0 { 2 } else { 4 });
+    ///
+    /// // The indentation is 6 columns wide, leaving 8 for words.
+    /// let output = wrap::<Salsa>("\t\tfoo bar baz", toppings).collect::<String>();
+    /// assert_eq!(output, "\t\tfoo bar\n\t\tbaz");
+    /// ```
+    pub fn tab_width_by_level<F>(self, width_for_level: F) -> Self
+    where
+        F: Fn(usize) -> usize + Send + Sync + 'static,
+    {
+        Self {
+            tab_levels: Some(Shared(Arc::new(width_for_level))),
+            ..self
+        }
+    }
+
     /// The newline character to use, see [Newline]. This is a line feed
     /// character (`\n`, [Newline::LF]) by default.
     ///
-    /// tortilla does not perform any heuristical newline character detection
-    /// and always outputs uniform linebreaks. You may choose to perform such
-    /// detection on the input string beforehand, and then pass the appropriate
-    /// variant to tortilla.
+    /// tortilla always outputs uniform linebreaks. Unless
+    /// [Toppings::detect_newline] is enabled, it does not perform any newline
+    /// character detection. You may choose to perform such detection on the
+    /// input string beforehand, and then pass the appropriate variant to
+    /// tortilla.
     pub fn newline(self, newline: Newline) -> Self {
         Self { newline, ..self }
     }
-}
 
-/// Wrap text. Output is lazily generated and returned in small chunks.
-///
-/// To set the line breaking algorithm, see [Guacamole] and [Salsa]. For other
-/// options, see [Toppings].
-///
-/// # Examples
-///
-/// Wrap a string and collect it into a new string:
-///
-/// ```
-/// use tortilla::{wrap, Salsa, Toppings};
-///
-/// // Leading indent of two spaces, comment token and bullet point:
-/// let input = "
-///   // - foo bar baz
-/// ";
-///
-/// // Set a low width that will force wrapping across several lines:
-/// let toppings = Toppings::default().width(8);
-/// let output = wrap::<Salsa>(input, toppings).collect::<String>();
-///
-/// // Indent and comment token are replicated onto new lines, bullet point
-/// // remains only on the first line and is replaced by spacing on the
-/// // subsequent ones:
-/// assert_eq!(output, "
-///   // - foo
-///   //   bar
-///   //   baz
-/// ");
-///
-/// // Now set a much higher width, which will get us back to the original input
-/// // (a single line):
-/// let toppings = Toppings::default().width(100);
-/// let output = wrap::<Salsa>(&output, toppings).collect::<String>();
-///
-/// assert_eq!(output, input);
-/// ```
-///
-/// Stream output to stdout:
-///
-/// ```
-/// use std::io::{self, Write};
-/// use tortilla::{wrap, Salsa, Toppings};
-///
-/// let input = "...";
-///
-/// let toppings = Toppings::default();
-/// let mut stdout = io::stdout().lock();
-///
-/// for chunk in wrap::<Salsa>(input, toppings) {
-///     stdout.write_all(chunk.as_bytes()).unwrap();
-/// }
-/// ```
-///
-pub fn wrap<S: Sauce>(input: &str, toppings: Toppings) -> Wrap<'_, S> {
-    Wrap {
-        toppings,
-        lines: Merge::new(Parse::new(Lex::new(input))),
-        current: None,
+    /// Use the newline character that ends the first line of input for all of
+    /// output. Disabled by default. If input consists of a single line without
+    /// a newline, [Toppings::newline] is used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Newline, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(7).detect_newline(true);
+    /// let wrap = |input| wrap::<Salsa>(input, toppings.clone()).collect::<String>();
+    ///
+    /// assert_eq!(wrap("foo bar baz\r\nqux\n"), "foo bar\r\nbaz qux\r\n");
+    /// assert_eq!(wrap("foo bar baz\nqux\r\n"), "foo bar\nbaz qux\n");
+    /// assert_eq!(wrap("foo bar baz"), "foo bar\nbaz");
+    /// ```
+    pub fn detect_newline(self, detect_newline: bool) -> Self {
+        Self {
+            detect_newline,
+            ..self
+        }
     }
-}
 
-/// An [Iterator] over chunks of wrapped output.
-pub struct Wrap<'t, S> {
-    toppings: Toppings,
-    lines: Merge<Parse<Lex<'t>>>,
-    current: Option<LineWrap<'t, S>>,
-}
+    /// Settle on the newline character of `input`, if it is to be detected,
+    /// see [Toppings::detect_newline].
+    fn detected(self, input: &str) -> Self {
+        if !self.detect_newline {
+            return self;
+        }
 
-impl<'t, S: Sauce> Iterator for Wrap<'t, S> {
-    type Item = &'t str;
+        let newline = Lex::new(input).find_map(|(_, token)| match token {
+            Token::Newline(newline) => Some(newline),
+            _ => None,
+        });
 
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            let inner = match &mut self.current {
-                Some(inner) => inner,
-                None => self
-                    .current
-                    .insert(LineWrap::new(self.lines.next()?, &self.toppings)),
-            };
+        match newline {
+            Some(newline) => Self {
+                newline,
+                detect_newline: false,
+                ..self
+            },
+            None => self,
+        }
+    }
 
-            match inner.next() {
-                Some(chunk) => return Some(chunk),
-                None => self.current = None,
-            }
+    /// Whether runs of whitespace between words are collapsed into a single
+    /// space. Enabled by default.
+    ///
+    /// When disabled, the original whitespace between two words is kept as
+    /// long as no line break is inserted between them, e.g., to preserve
+    /// alignment within a paragraph. Words joined from separate input lines
+    /// are still separated by a single space. Preserved whitespace counts
+    /// towards line width, with each tab character (`\t`) counting as
+    /// [Toppings::tabs] columns.
+    pub fn collapse_spaces(self, collapse_spaces: bool) -> Self {
+        Self {
+            collapse_spaces,
+            ..self
+        }
+    }
+
+    /// Whether words that exceed the maximum line width by themselves are
+    /// broken apart. Disabled by default.
+    ///
+    /// Overlong words are split into fragments that each fill an entire line.
+    /// Splits only ever happen between grapheme clusters, so a single cluster
+    /// that is wider than the line by itself (e.g., a wide character on a very
+    /// narrow line) still overflows. Words are left intact if indentation,
+    /// comment token and bullet token leave no room on the line at all.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(6).break_long_words(true);
+    /// let output = wrap::<Salsa>("# foo barbazqux", toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "# foo\n# barb\n# azqu\n# x");
+    /// ```
+    pub fn break_long_words(self, break_long_words: bool) -> Self {
+        Self {
+            break_long_words,
+            ..self
+        }
+    }
+
+    /// Whether to keep URLs intact, i.e., words that start with `http://`,
+    /// `https://` or `www.`, even if [Toppings::break_long_words] or
+    /// [Toppings::enforce_width] would break them apart otherwise. Disabled by
+    /// default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default()
+    ///     .width(12)
+    ///     .break_long_words(true)
+    ///     .protect_urls(true);
+    ///
+    /// let input = "// see https://example.com/foo";
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "// see\n// https://example.com/foo");
+    /// ```
+    pub fn protect_urls(self, protect_urls: bool) -> Self {
+        Self {
+            protect_urls,
+            ..self
+        }
+    }
+
+    /// Keep text that matches `pattern` together as a single, unbreakable
+    /// word, even if it spans several words. Not set by default.
+    ///
+    /// Matches are found within the words of each line, i.e., following any
+    /// indentation, comment token and bullet. Matched text is kept as it is,
+    /// including any whitespace within it, and counts towards line width as a
+    /// whole. Neither [Toppings::break_long_words] nor
+    /// [Toppings::enforce_width] break it apart, so it may overflow the line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use regex::Regex;
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default()
+    ///     .width(10)
+    ///     .protect(Regex::new(r"\d+ km").unwrap());
+    ///
+    /// let output = wrap::<Salsa>("foo bar 10 km", toppings).collect::<String>();
+    /// assert_eq!(output, "foo bar\n10 km");
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn protect(self, pattern: regex::Regex) -> Self {
+        Self {
+            protect: Some(Shared(Arc::new(pattern))),
+            ..self
+        }
+    }
+
+    /// Whether to never exceed the maximum line width, breaking words apart
+    /// as a last resort. Disabled by default.
+    ///
+    /// This is the strict counterpart to [Toppings::break_long_words]: words
+    /// are split into fragments that fit both the first line and continuation
+    /// lines of a paragraph, and words are no longer kept together (see
+    /// [Toppings::keep] and [Toppings::keep_brackets]) if that would exceed
+    /// the line. Lines still overflow if a single grapheme cluster is wider
+    /// than the room left by indentation, comment token and bullet token, or
+    /// if that prefix exceeds the maximum line width by itself. Sauces that
+    /// ignore the width (see [WordsPerLine]) are not held to it either.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(8).enforce_width(true);
+    /// let output = wrap::<Salsa>("- abcdefghijklmnopqrst", toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "- abcdef\n  ghijkl\n  mnopqr\n  st");
+    /// ```
+    pub fn enforce_width(self, enforce_width: bool) -> Self {
+        Self {
+            enforce_width,
+            ..self
+        }
+    }
+
+    /// Let the line breaking algorithm break any word apart between grapheme
+    /// clusters, at the given cost per break, if [Toppings::break_long_words]
+    /// or [Toppings::enforce_width] is enabled. Unset by default, in which case
+    /// only words that don't fit on a line are broken apart, into fragments
+    /// that each fill an entire line.
+    ///
+    /// The cost is weighed against the raggedness of lines, i.e., the square of
+    /// the columns each line falls short of the maximum width by, so a word is
+    /// only broken apart if no set of breaks between words comes in cheaper.
+    /// Only algorithms that support this (see [Sauce::breaks_words]) do so, and
+    /// only if words aren't kept together (see [Toppings::keep] and
+    /// [Toppings::keep_brackets]) and no breaks are forced before inline code
+    /// (see [Toppings::break_before_code]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "aaaa bbbbbbbb";
+    /// let toppings = Toppings::default().width(10).break_long_words(true);
+    ///
+    /// let output = wrap::<Salsa>(input, toppings.clone().intra_word_penalty(100))
+    ///     .collect::<String>();
+    /// assert_eq!(output, "aaaa\nbbbbbbbb");
+    ///
+    /// let output = wrap::<Salsa>(input, toppings.intra_word_penalty(10)).collect::<String>();
+    /// assert_eq!(output, "aaaa bbbbb\nbbb");
+    /// ```
+    pub fn intra_word_penalty(self, penalty: usize) -> Self {
+        Self {
+            intra_word_penalty: Some(penalty),
+            ..self
+        }
+    }
+
+    /// Break words apart at the hyphenation points of language `lang`,
+    /// inserting a hyphen at the end of each line that ends within a word. Not
+    /// set by default.
+    ///
+    /// Only the US English patterns of TeX are built in, for `en` and any of
+    /// its regional variants such as `en-GB`. Words in other languages are
+    /// left intact, as are words that contain anything but letters, apart from
+    /// punctuation in front or after. Words are only ever broken apart at
+    /// hyphenation points, regardless of [Toppings::break_long_words] and
+    /// [Toppings::enforce_width].
+    ///
+    /// Each break within a word costs [Toppings::intra_word_penalty], if set.
+    /// Words are only broken apart where that setting would break them apart
+    /// as well, e.g., only by algorithms that support it (see
+    /// [Sauce::breaks_words]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(13).hyphenate("en");
+    /// let output = wrap::<Salsa>("see internationalization", toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "see interna-\ntionalization");
+    /// ```
+    #[cfg(feature = "hyphen")]
+    pub fn hyphenate(self, lang: &str) -> Self {
+        Self {
+            hyphenate: hyphen::lang(lang),
+            ..self
+        }
+    }
+
+    /// A predicate that decides whether two adjacent words must stay on the
+    /// same line, e.g., to keep a section sign with the number following it.
+    /// By default, a line break may be placed between any two words.
+    ///
+    /// The predicate is called with each pair of adjacent words in a paragraph.
+    /// Words that must stay together are treated as a single, wider word by the
+    /// line breaking algorithm, so they may still overflow the line as a whole.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, KeepRule, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(10).keep(|word, _| match word {
+    ///     "§" => KeepRule::KeepWithNext,
+    ///     _ => KeepRule::Allow,
+    /// });
+    ///
+    /// let output = wrap::<Salsa>("see also § 5", toppings).collect::<String>();
+    /// assert_eq!(output, "see also\n§ 5");
+    /// ```
+    pub fn keep<F>(self, keep: F) -> Self
+    where
+        F: Fn(&str, &str) -> KeepRule + Send + Sync + 'static,
+    {
+        Self {
+            keep: Some(Shared(Arc::new(keep))),
+            ..self
+        }
+    }
+
+    /// Whether to keep brackets with the words they enclose. Disabled by
+    /// default.
+    ///
+    /// Lines never end with a word that ends with an opening bracket (`(`, `[`
+    /// or `{`), and never start with a word that starts with a closing bracket
+    /// (`)`, `]` or `}`). This applies on top of [Toppings::keep].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(8).keep_brackets(true);
+    /// let output = wrap::<Salsa>("foo ( bar )", toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "foo\n( bar )");
+    /// ```
+    pub fn keep_brackets(self, keep_brackets: bool) -> Self {
+        Self {
+            keep_brackets,
+            ..self
+        }
+    }
+
+    /// Start inline code spans (`` `like this` ``) on a new line if they take
+    /// up at least `percent` percent of the room for words. Not set by
+    /// default.
+    ///
+    /// Code spans are delimited by backticks and may span several words.
+    /// Spans that are narrower than the threshold stay inline.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(30).break_before_code(50);
+    /// let input = "call `frobnicate(widgets, 42)` now";
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "call\n`frobnicate(widgets, 42)` now");
+    /// ```
+    pub fn break_before_code(self, percent: usize) -> Self {
+        Self {
+            break_before_code: Some(percent),
+            ..self
+        }
+    }
+
+    /// The minimum number of words on the last line of a paragraph. The
+    /// default value is 0, i.e., no minimum.
+    ///
+    /// If the last line ends up with fewer words, words are pulled down from
+    /// the line above, as many as fit. The line above always keeps at least
+    /// one word, and paragraphs of fewer words in total are left as they are.
+    /// [Salsa] (and [Auto], for paragraphs it uses [Salsa] for) takes the
+    /// minimum into account for all of its breaks instead, such that earlier
+    /// lines are balanced as well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(12).min_last_line_words(2);
+    /// let output = wrap::<Salsa>("aaaa bbbb cc d", toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "aaaa bbbb\ncc d");
+    /// ```
+    pub fn min_last_line_words(self, min_last_line_words: usize) -> Self {
+        Self {
+            min_last_line_words,
+            ..self
         }
     }
+
+    /// The number of words on each line when wrapping with [WordsPerLine]. The
+    /// default value is 1. A value of 0 places all words on a single line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Toppings, WordsPerLine};
+    ///
+    /// let toppings = Toppings::default().words_per_line(3);
+    /// let output = wrap::<WordsPerLine>("a b c d e f g", toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "a b c\nd e f\ng");
+    /// ```
+    pub fn words_per_line(self, words_per_line: usize) -> Self {
+        Self {
+            words_per_line,
+            ..self
+        }
+    }
+
+    /// Whether to only wrap lines that exceed the maximum line width, leaving
+    /// all other lines untouched. Disabled by default.
+    ///
+    /// When enabled, lines are never joined into paragraphs. Lines that fit
+    /// are emitted exactly as they are, except for their newline character
+    /// (see [Toppings::newline]), and lines that don't fit are wrapped by
+    /// themselves.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "\
+    /// # short  and   sweet
+    /// # this one line is a little too long
+    /// # short
+    /// ";
+    ///
+    /// let toppings = Toppings::default().width(24).overflow_only(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "\
+    /// # short  and   sweet
+    /// # this one line is a
+    /// # little too long
+    /// # short
+    /// ");
+    /// ```
+    pub fn overflow_only(self, overflow_only: bool) -> Self {
+        Self {
+            overflow_only,
+            ..self
+        }
+    }
+
+    /// Whether to join adjacent lines into paragraphs before wrapping them.
+    /// Enabled by default.
+    ///
+    /// When disabled, each line is wrapped by itself, such that lines that
+    /// exceed the maximum line width are broken, but short lines are never
+    /// joined. Unlike [Toppings::overflow_only], lines that fit are still
+    /// rewritten, e.g., to collapse spaces.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "# foo\n# bar baz qux\n";
+    ///
+    /// let toppings = Toppings::default().width(12);
+    /// let output = wrap::<Salsa>(input, toppings.clone()).collect::<String>();
+    /// assert_eq!(output, "# foo bar\n# baz qux\n");
+    ///
+    /// let output = wrap::<Salsa>(input, toppings.reflow(false)).collect::<String>();
+    /// assert_eq!(output, "# foo\n# bar baz\n# qux\n");
+    /// ```
+    pub fn reflow(self, reflow: bool) -> Self {
+        Self { reflow, ..self }
+    }
+
+    /// Shorthand for enabling [Toppings::overflow_only] and disabling
+    /// [Toppings::collapse_spaces], i.e., the lightest possible touch: lines
+    /// are never joined, and only lines that exceed the maximum line width are
+    /// broken, at their existing whitespace. Disabling leaves both settings as
+    /// they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "\
+    /// let  x = 1;
+    /// // this comment  is a  little too long
+    /// // short
+    /// ";
+    ///
+    /// let toppings = Toppings::default().width(24).hard_wrap_only(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "\
+    /// let  x = 1;
+    /// // this comment  is a
+    /// // little too long
+    /// // short
+    /// ");
+    ///
+    /// let toppings = Toppings::default().collapse_spaces(false).hard_wrap_only(false);
+    /// let output = wrap::<Salsa>("let  x = 1;", toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "let  x = 1;");
+    /// ```
+    pub fn hard_wrap_only(self, hard_wrap_only: bool) -> Self {
+        match hard_wrap_only {
+            true => self.overflow_only(true).collapse_spaces(false),
+            false => self,
+        }
+    }
+
+    /// Whether to recognize Markdown-style horizontal rules, i.e., lines that
+    /// consist solely of three or more `-`, `*` or `_` characters. Enabled by
+    /// default.
+    ///
+    /// Horizontal rules are emitted exactly as they are, and are never joined
+    /// with adjacent lines. They may follow a comment token.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "foo\n---\nbar\n";
+    ///
+    /// let output = wrap::<Salsa>(input, Toppings::default()).collect::<String>();
+    /// assert_eq!(output, "foo\n---\nbar\n");
+    ///
+    /// let toppings = Toppings::default().horizontal_rules(false);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    /// assert_eq!(output, "foo --- bar\n");
+    /// ```
+    pub fn horizontal_rules(self, horizontal_rules: bool) -> Self {
+        Self {
+            horizontal_rules,
+            ..self
+        }
+    }
+
+    /// Whether to keep lines that consist solely of punctuation and symbols,
+    /// i.e., contain no letters or digits (e.g., `...`, `!!!` or `* * *`), as
+    /// they are. Disabled by default, in which case such lines are wrapped
+    /// like any other, unless they are [horizontal
+    /// rules](Toppings::horizontal_rules).
+    ///
+    /// Like horizontal rules, these lines are never joined with adjacent
+    /// lines, and may follow a comment token.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "foo\n...\nbar\n!!!\nbaz\n";
+    ///
+    /// let output = wrap::<Salsa>(input, Toppings::default()).collect::<String>();
+    /// assert_eq!(output, "foo ... bar !!! baz\n");
+    ///
+    /// let toppings = Toppings::default().punctuation_lines(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    /// assert_eq!(output, input);
+    /// ```
+    pub fn punctuation_lines(self, punctuation_lines: bool) -> Self {
+        Self {
+            punctuation_lines,
+            ..self
+        }
+    }
+
+    /// Treat a leading number on each line as a line number gutter, e.g., when
+    /// wrapping a listing that has been printed with line numbers. Unset by
+    /// default, in which case such numbers are wrapped like any other word.
+    ///
+    /// The gutter consists of the whitespace and digits at the start of a line.
+    /// Like indentation, it is never wrapped, and `continuation` determines
+    /// whether it is repeated on wrapped lines. Lines with different line
+    /// numbers are never joined into paragraphs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Gutter, Salsa, Toppings};
+    ///
+    /// let input = "  9  // this comment is a little too long\n 10  foo();\n";
+    ///
+    /// let toppings = Toppings::default().width(28).line_numbers(Gutter::Blank);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(
+    ///     output,
+    ///     "  9  // this comment is a\n     // little too long\n 10  foo();\n"
+    /// );
+    /// ```
+    pub fn line_numbers(self, continuation: Gutter) -> Self {
+        Self {
+            line_numbers: Some(continuation),
+            ..self
+        }
+    }
+
+    /// Start the words of wrapped continuation lines at a fixed column,
+    /// regardless of padding and bullets. Unset by default, in which case
+    /// continuation lines replicate the padding of the first line and align
+    /// with the words following its bullet.
+    ///
+    /// The column is counted from the start of the line, including indentation
+    /// and comment token, which are still replicated. If those already reach
+    /// the column by themselves, words are preceded by a single space instead.
+    pub fn continuation_column(self, column: usize) -> Self {
+        Self {
+            continuation_column: Some(column),
+            ..self
+        }
+    }
+
+    /// Indent wrapped continuation lines of a bulleted line by a number of
+    /// spaces after the bullet's padding, instead of aligning them with the
+    /// words following the bullet. Unset by default. Indentation and comment
+    /// token are still replicated in front of the hanging indent, and
+    /// [Toppings::continuation_column] takes precedence if set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "  // - foo bar baz qux\n";
+    ///
+    /// let toppings = Toppings::default().width(16).hanging(4);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "  // - foo bar\n  //     baz qux\n");
+    /// ```
+    pub fn hanging(self, spaces: usize) -> Self {
+        Self {
+            hanging: Some(spaces),
+            ..self
+        }
+    }
+
+    /// Join lines that continue a bulleted line even if they are indented by
+    /// a different amount than the bullet's width, as long as all of them are
+    /// indented the same, and keep that indentation on wrapped continuation
+    /// lines. Disabled by default, in which case such lines start a paragraph
+    /// of their own. Takes precedence over [Toppings::hanging].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "- foo bar\n    baz qux\n    quux\n";
+    ///
+    /// let toppings = Toppings::default().width(12).preserve_continuation_indent(true);
+    /// let output = wrap::<Salsa>(input, toppings.clone()).collect::<String>();
+    ///
+    /// assert_eq!(output, "- foo bar\n    baz qux\n    quux\n");
+    ///
+    /// let wider = toppings.width(16);
+    /// let output = wrap::<Salsa>(input, wider).collect::<String>();
+    ///
+    /// assert_eq!(output, "- foo bar baz\n    qux quux\n");
+    /// ```
+    pub fn preserve_continuation_indent(self, preserve_continuation_indent: bool) -> Self {
+        Self {
+            preserve_continuation_indent,
+            ..self
+        }
+    }
+
+    /// Shift the first line of each paragraph by `offset` columns relative to
+    /// the indentation of the following lines. The default value is 0.
+    ///
+    /// A positive offset adds spaces after the original indentation, while a
+    /// negative one removes columns from it (turning tabs into spaces). Lines
+    /// that consist of indentation only are not affected. Also see
+    /// [Toppings::continuation_column] for controlling where the words of the
+    /// following lines start.
+    ///
+    /// Note that subsequent runs will parse the first line as a paragraph of
+    /// its own, as its indentation differs from the lines below.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(11).first_line_indent(-2);
+    /// let output = wrap::<Salsa>("    foo bar baz", toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "  foo bar\n    baz");
+    /// ```
+    pub fn first_line_indent(self, offset: isize) -> Self {
+        Self {
+            first_line_indent: offset,
+            ..self
+        }
+    }
+
+    /// Prepend `columns` spaces to every line of output, on top of the
+    /// indentation of the input, e.g., to embed wrapped text into an already
+    /// indented context. The default value is 0.
+    ///
+    /// The extra indentation counts towards [Toppings::width]. Empty lines
+    /// stay empty rather than consisting of trailing whitespace.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "Lorem ipsum dolor sit amet.\n\nConsectetur adipiscing elit.\n";
+    ///
+    /// let toppings = Toppings::default().width(24).output_indent(4);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// let yaml = format!("description: |\n{output}");
+    /// assert_eq!(
+    ///     yaml,
+    ///     "description: |\n    Lorem ipsum dolor\n    sit amet.\n\n    Consectetur\n    adipiscing elit.\n"
+    /// );
+    /// ```
+    pub fn output_indent(self, columns: usize) -> Self {
+        Self {
+            output_indent: columns,
+            ..self
+        }
+    }
+
+    /// Count at most `columns` columns of indentation towards the maximum line
+    /// width. Unset by default, in which case all indentation counts.
+    ///
+    /// Indentation beyond the maximum is still emitted as it is, but words are
+    /// wrapped as if it wasn't there. This keeps pathologically indented lines
+    /// from being wrapped into a single word per line, at the cost of
+    /// exceeding [Toppings::width].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = format!("{}foo bar baz", " ".repeat(40));
+    ///
+    /// let toppings = Toppings::default().width(16).max_indent(8);
+    /// let output = wrap::<Salsa>(&input, toppings).collect::<String>();
+    ///
+    /// let indent = " ".repeat(40);
+    /// assert_eq!(output, format!("{indent}foo bar\n{indent}baz"));
+    /// ```
+    pub fn max_indent(self, columns: usize) -> Self {
+        Self {
+            max_indent: Some(columns),
+            ..self
+        }
+    }
+
+    /// Whether to preserve indentation that mixes tabs and spaces exactly as
+    /// it is. Disabled by default, in which case only the leading run of
+    /// either is treated as indentation.
+    ///
+    /// The indentation of the first line of a paragraph is repeated on each
+    /// wrapped line. For the purpose of joining lines into paragraphs, it is
+    /// compared by width (see [Toppings::tabs]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(16).exact_indent(true);
+    /// let output = wrap::<Salsa>("\t  \tfoo bar baz\n", toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "\t  \tfoo\n\t  \tbar\n\t  \tbaz\n");
+    /// ```
+    pub fn exact_indent(self, exact_indent: bool) -> Self {
+        Self {
+            exact_indent,
+            ..self
+        }
+    }
+
+    /// Replace tabs in indentation and in padding following the comment token
+    /// with spaces, [Toppings::tabs] per tab. Disabled by default, in which
+    /// case tabs are kept as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(12).tabs(2).expand_tabs(true);
+    /// let output = wrap::<Salsa>("\t#\t- foo bar\n", toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "  #  - foo\n  #    bar\n");
+    /// ```
+    pub fn expand_tabs(self, expand_tabs: bool) -> Self {
+        Self {
+            expand_tabs,
+            ..self
+        }
+    }
+
+    /// Round indentation that consists of spaces to the nearest multiple of
+    /// `columns`, rounding half way up. Unset by default, in which case
+    /// indentation is kept as it is.
+    ///
+    /// Indentation that consists of tabs is left alone, unless
+    /// [Toppings::expand_tabs] is enabled as well. Lines are joined into
+    /// paragraphs by their rounded indentation, while the padding following a
+    /// comment token is not touched. Lines continuing a list item keep lining
+    /// up with the words following its bullet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "   foo\n    bar\n      baz\n\tqux\n";
+    ///
+    /// let toppings = Toppings::default().indent_unit(4);
+    /// let output = wrap::<Salsa>(input, toppings.clone()).collect::<String>();
+    /// assert_eq!(output, "    foo bar\n        baz\n\tqux\n");
+    ///
+    /// let output = wrap::<Salsa>(input, toppings.clone().expand_tabs(true)).collect::<String>();
+    /// assert_eq!(output, "    foo bar\n        baz\n    qux\n");
+    ///
+    /// let output = wrap::<Salsa>("- foo\n  bar\n", toppings).collect::<String>();
+    /// assert_eq!(output, "- foo bar\n");
+    /// ```
+    pub fn indent_unit(self, columns: usize) -> Self {
+        Self {
+            indent_unit: Some(columns),
+            ..self
+        }
+    }
+
+    /// Whether to recognize reStructuredText field lists and directives, e.g.,
+    /// within Python docstrings. Disabled by default.
+    ///
+    /// Field markers such as `:param foo:` are treated like bullets: Each field
+    /// starts a new paragraph, and its body is wrapped with a hanging indent.
+    /// Lines starting with `..`, such as `.. note::`, are emitted exactly as
+    /// they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "\
+    /// .. note:: Keep this as it is.
+    /// :param foo: The foo to frobnicate.
+    /// :returns: Nothing.
+    /// ";
+    ///
+    /// let toppings = Toppings::default().width(24).restructured_text(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "\
+    /// .. note:: Keep this as it is.
+    /// :param foo: The foo to
+    ///             frobnicate.
+    /// :returns: Nothing.
+    /// ");
+    /// ```
+    pub fn restructured_text(self, restructured_text: bool) -> Self {
+        Self {
+            restructured_text,
+            ..self
+        }
+    }
+
+    /// Whether to recognize C-style block comments. Disabled by default.
+    ///
+    /// Block comments start with a line whose first word starts with `/*`, and
+    /// end with a line whose last word ends with `*/`. In between, a leading
+    /// `*` is treated like a comment token rather than a bullet, and is thus
+    /// repeated on each wrapped line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "\
+    /// /**
+    ///  * Frobnicates the foo, unless
+    ///  * it has been frobnicated already.
+    ///  */
+    /// ";
+    ///
+    /// let toppings = Toppings::default().width(24).block_comments(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "\
+    /// /**
+    ///  * Frobnicates the foo,
+    ///  * unless it has been
+    ///  * frobnicated already.
+    ///  */
+    /// ");
+    /// ```
+    pub fn block_comments(self, block_comments: bool) -> Self {
+        Self {
+            block_comments,
+            ..self
+        }
+    }
+
+    /// Whether to leave the lines of diffs and patches untouched. Disabled by
+    /// default.
+    ///
+    /// Lines that start with `+`, `-`, `@@` or a single space are emitted
+    /// exactly as they are, and are never joined with adjacent lines. This
+    /// allows for wrapping the prose around a diff, e.g., in a commit message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "\
+    /// Rename foo to bar, as it is
+    /// really more of a bar.
+    ///
+    /// @@ -1,3 +1,3 @@
+    ///  fn main() {
+    /// -    foo();
+    /// +    bar();
+    ///  }
+    /// ";
+    ///
+    /// let toppings = Toppings::default().diff(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "\
+    /// Rename foo to bar, as it is really more of a bar.
+    ///
+    /// @@ -1,3 +1,3 @@
+    ///  fn main() {
+    /// -    foo();
+    /// +    bar();
+    ///  }
+    /// ");
+    /// ```
+    pub fn diff(self, diff: bool) -> Self {
+        Self { diff, ..self }
+    }
+
+    /// The comment tokens that are recognized at the start of a line. The
+    /// default tokens are `#`, `>`, `;`, `//`, `--`, `;;`, `///` and `//!`.
+    ///
+    /// Tokens are only recognized as whole words, i.e., when followed by
+    /// whitespace or the end of the line. Passing no tokens at all disables
+    /// comment detection entirely.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "% foo\n% bar\n// baz\n";
+    ///
+    /// let toppings = Toppings::default().comment_tokens(&["%"]);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    /// assert_eq!(output, "% foo bar\n// baz\n");
+    ///
+    /// let toppings = Toppings::default().comment_tokens(&[]);
+    /// let output = wrap::<Salsa>("// foo\n// bar\n", toppings).collect::<String>();
+    /// assert_eq!(output, "// foo // bar\n");
+    /// ```
+    pub fn comment_tokens(self, tokens: &[&str]) -> Self {
+        Self {
+            comment_tokens: tokens.iter().map(|token| token.to_string()).collect(),
+            ..self
+        }
+    }
+
+    /// The literal bullet tokens that are recognized at the start of a line,
+    /// following indentation and comment token. The default markers are `-`,
+    /// `*` and `•`. Numbered markers (e.g., `1.` or `2)`) are recognized
+    /// regardless.
+    ///
+    /// Like comment tokens, markers are only recognized as whole words.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(10).bullet_markers(&["+", "-"]);
+    /// let output = wrap::<Salsa>("+ foo bar baz\n", toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "+ foo bar\n  baz\n");
+    /// ```
+    pub fn bullet_markers(self, markers: &[&str]) -> Self {
+        Self {
+            bullet_markers: markers.iter().map(|marker| marker.to_string()).collect(),
+            ..self
+        }
+    }
+
+    /// Whether to recognize a single ASCII letter followed by `.` or `)` as a
+    /// bullet token of an ordered list, e.g., `a.` or `B)`, in addition to
+    /// numbered ones. Disabled by default, as such markers are easily confused
+    /// with initials at the start of a sentence.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(10).alphabetic_bullets(true);
+    /// let output = wrap::<Salsa>("a. foo bar\n   baz\nb) qux\n", toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "a. foo bar\n   baz\nb) qux\n");
+    /// ```
+    pub fn alphabetic_bullets(self, alphabetic_bullets: bool) -> Self {
+        Self {
+            alphabetic_bullets,
+            ..self
+        }
+    }
+
+    /// Whether to leave a leading block of metadata lines (`Key: value`)
+    /// untouched. Disabled by default.
+    ///
+    /// The block consists of all lines at the very start of the input whose
+    /// first word is a key (letters, digits, `-` and `_`) directly followed by
+    /// a colon, without any indentation, comment token or bullet token. It
+    /// ends with the first line that doesn't match, e.g., an empty line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "\
+    /// Title: Tortilla, a love letter
+    /// Author: Someone Hungry
+    ///
+    /// Wraps are
+    /// a delicacy.
+    /// ";
+    ///
+    /// let toppings = Toppings::default().width(20).metadata(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "\
+    /// Title: Tortilla, a love letter
+    /// Author: Someone Hungry
+    ///
+    /// Wraps are a
+    /// delicacy.
+    /// ");
+    /// ```
+    pub fn metadata(self, metadata: bool) -> Self {
+        Self { metadata, ..self }
+    }
+
+    /// Whether a line that ends in a space is kept apart from the line below,
+    /// rather than joined into the same paragraph. Disabled by default.
+    ///
+    /// This suits formats where trailing whitespace marks a line break, such
+    /// as markdown. The trailing whitespace itself is dropped, unless
+    /// [Toppings::preserve_trailing_whitespace] is enabled or it makes up a
+    /// hard line break (see [Toppings::markdown]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Guacamole, Salsa, Toppings};
+    ///
+    /// let input = "foo \nbar\nbaz";
+    ///
+    /// let output = wrap::<Salsa>(input, Toppings::default()).collect::<String>();
+    /// assert_eq!(output, "foo bar baz");
+    ///
+    /// let toppings = Toppings::default().trailing_space_breaks(true);
+    ///
+    /// let output = wrap::<Salsa>(input, toppings.clone()).collect::<String>();
+    /// assert_eq!(output, "foo\nbar baz");
+    ///
+    /// let output = wrap::<Guacamole>(input, toppings.clone()).collect::<String>();
+    /// assert_eq!(output, "foo\nbar baz");
+    ///
+    /// let toppings = toppings.preserve_trailing_whitespace(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    /// assert_eq!(output, "foo \nbar baz");
+    /// ```
+    pub fn trailing_space_breaks(self, trailing_space_breaks: bool) -> Self {
+        Self {
+            trailing_space_breaks,
+            ..self
+        }
+    }
+
+    /// Keep whitespace at the end of lines as it is, e.g., two spaces that mark
+    /// a hard line break in markdown. Disabled by default, in which case
+    /// trailing whitespace is dropped.
+    ///
+    /// A line that ends in whitespace is never joined with the line below, and
+    /// its trailing whitespace ends up at the end of the last line it is
+    /// wrapped into.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "foo  \nbar\nbaz qux \n";
+    ///
+    /// let output = wrap::<Salsa>(input, Toppings::default()).collect::<String>();
+    /// assert_eq!(output, "foo bar baz qux\n");
+    ///
+    /// let toppings = Toppings::default().width(8).preserve_trailing_whitespace(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    /// assert_eq!(output, "foo  \nbar baz\nqux \n");
+    /// ```
+    pub fn preserve_trailing_whitespace(self, preserve_trailing_whitespace: bool) -> Self {
+        Self {
+            preserve_trailing_whitespace,
+            ..self
+        }
+    }
+
+    /// Whether to recognize markdown hard line breaks, i.e., lines that end in
+    /// two or more spaces or a backslash. Disabled by default.
+    ///
+    /// A line that ends in a hard line break is never joined with the line
+    /// below, and the spaces that make up the break are kept. Headings, i.e.,
+    /// lines starting with one to six `#`, are never joined with the lines
+    /// around them either (see [LineClass::Heading]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "Roses are red,  \nviolets are blue,\\\nsugar is sweet\nand so are you.\n";
+    ///
+    /// let output = wrap::<Salsa>(input, Toppings::default()).collect::<String>();
+    /// assert_eq!(output, "Roses are red, violets are blue,\\ sugar is sweet and so are you.\n");
+    ///
+    /// let toppings = Toppings::default().markdown(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    /// assert_eq!(output, "Roses are red,  \nviolets are blue,\\\nsugar is sweet and so are you.\n");
+    /// ```
+    pub fn markdown(self, markdown: bool) -> Self {
+        Self { markdown, ..self }
+    }
+
+    /// A token that continues a line on the next one when it ends the line,
+    /// such as `\` in shell scripts and Makefiles. Unset by default.
+    ///
+    /// A line that ends in the token is joined with the line below, whatever
+    /// its indentation, comment token or bullet, and the token is emitted at
+    /// the end of each line but the last when wrapping. Room for it is left
+    /// on every line. Wrapped lines are indented like the first line that was
+    /// continued, relative to the line that continues it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "foo bar \\\n  baz qux\nquux\n";
+    ///
+    /// let toppings = Toppings::default().width(12).continuation("\\");
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "foo bar \\\n  baz qux\nquux\n");
+    /// ```
+    pub fn continuation(self, token: &str) -> Self {
+        Self {
+            continuation: Some(token.to_string()),
+            ..self
+        }
+    }
+
+    /// A line that separates paragraphs, in addition to empty lines. Not set
+    /// by default.
+    ///
+    /// Lines that match the separator exactly (excluding their newline
+    /// character) end the paragraph above and are emitted as they are, unless
+    /// [Toppings::drop_paragraph_separators] is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "foo\nbar\n%%\nbaz\nqux\n";
+    ///
+    /// let toppings = Toppings::default().paragraph_separator(Some("%%".to_owned()));
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "foo bar\n%%\nbaz qux\n");
+    /// ```
+    pub fn paragraph_separator(self, paragraph_separator: Option<String>) -> Self {
+        Self {
+            paragraph_separator,
+            ..self
+        }
+    }
+
+    /// Whether to remove lines that match [Toppings::paragraph_separator] from
+    /// the output. Disabled by default.
+    ///
+    /// The paragraphs around them are still kept apart.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "foo\nbar\n%%\nbaz\nqux\n";
+    ///
+    /// let toppings = Toppings::default()
+    ///     .paragraph_separator(Some("%%".to_owned()))
+    ///     .drop_paragraph_separators(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "foo bar\nbaz qux\n");
+    /// ```
+    pub fn drop_paragraph_separators(self, drop_paragraph_separators: bool) -> Self {
+        Self {
+            drop_paragraph_separators,
+            ..self
+        }
+    }
+
+    /// Compare the indentation of adjacent lines by rendered width (see
+    /// [Toppings::tabs]) when deciding whether they belong to the same
+    /// paragraph, such that a line indented by a tab continues one indented by
+    /// an equivalent number of spaces. Disabled by default, in which case
+    /// indentation must match character by character.
+    pub fn indent_by_width(self, indent_by_width: bool) -> Self {
+        Self {
+            indent_by_width,
+            ..self
+        }
+    }
+
+    /// Let adjacent comment lines with the same comment token continue each
+    /// other even if their indentation differs by a column or two, e.g., when
+    /// a comment block was indented by hand. The paragraph keeps the
+    /// indentation of its first line. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "  // foo bar\n   // baz\n";
+    ///
+    /// let toppings = Toppings::default().lenient_comment_indent(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "  // foo bar baz\n");
+    /// ```
+    pub fn lenient_comment_indent(self, lenient_comment_indent: bool) -> Self {
+        Self {
+            lenient_comment_indent,
+            ..self
+        }
+    }
+
+    /// Collapse any run of blank lines between paragraphs into exactly one, and
+    /// drop blank lines at the start and end of input altogether. Disabled by
+    /// default, in which case blank lines are kept as they are.
+    ///
+    /// A blank line may still consist of indentation and/or a comment token,
+    /// but not a bullet token.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().single_blank_between_paragraphs(true);
+    ///
+    /// let output = wrap::<Salsa>("\na\n\n\n\nb\nc\n\n", toppings).collect::<String>();
+    /// assert_eq!(output, "a\n\nb c\n");
+    /// ```
+    pub fn single_blank_between_paragraphs(self, single_blank_between_paragraphs: bool) -> Self {
+        Self {
+            single_blank_between_paragraphs,
+            ..self
+        }
+    }
+
+    /// Collapse any run of more than `max` consecutive blank lines into `max`
+    /// blank lines. Unlimited by default, in which case blank lines are kept
+    /// as they are. See [Toppings::single_blank_between_paragraphs] for what
+    /// counts as a blank line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().max_blank_lines(2);
+    ///
+    /// let output = wrap::<Salsa>("a\n\nb\n\n\n\nc\n", toppings).collect::<String>();
+    /// assert_eq!(output, "a\n\nb\n\n\nc\n");
+    /// ```
+    pub fn max_blank_lines(self, max: usize) -> Self {
+        Self {
+            max_blank_lines: Some(max),
+            ..self
+        }
+    }
+
+    /// Keep a single space at the end of each line that is followed by a
+    /// break within a paragraph, such that every word is followed by exactly
+    /// one space, e.g., for rendering onto a fixed grid. Disabled by default.
+    ///
+    /// The trailing space does not count towards [Toppings::width].
+    pub fn trailing_space_on_break(self, trailing_space_on_break: bool) -> Self {
+        Self {
+            trailing_space_on_break,
+            ..self
+        }
+    }
+
+    /// Pad lines with trailing spaces up to the maximum line width, such that
+    /// paragraphs form a rectangular block, e.g., for rendering onto a fixed
+    /// grid. The default value is [Fill::Ragged].
+    ///
+    /// Lines that exceed the maximum line width by themselves (see
+    /// [Toppings::width]) are left as they are, as are lines without any words
+    /// and lines left untouched by [Toppings::overflow_only].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Fill, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().width(9).fill(Fill::All);
+    /// let output = wrap::<Salsa>("foo bar baz qux", toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "foo bar  \nbaz qux  ");
+    /// ```
+    pub fn fill(self, fill: Fill) -> Self {
+        Self { fill, ..self }
+    }
+
+    /// Widen lines to the maximum line width by adding spaces between words,
+    /// distributed as evenly as possible with earlier gaps getting more.
+    /// Disabled by default.
+    ///
+    /// The last line of each paragraph is left ragged, as are lines with a
+    /// single word and verbatim lines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "// foo bar baz qux quux corge\n";
+    ///
+    /// let toppings = Toppings::default().width(16).justify(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "// foo  bar  baz\n// qux      quux\n// corge\n");
+    /// ```
+    pub fn justify(self, justify: bool) -> Self {
+        Self { justify, ..self }
+    }
+
+    /// Align lines shorter than the maximum line width to the right, or
+    /// center them, by adding spaces between indentation and comment token.
+    /// The comment token and bullet move along with the words. When centered,
+    /// an odd space left over goes to the right. The default value is
+    /// [Align::Left].
+    ///
+    /// Verbatim lines are left as they are. Lines widened by
+    /// [Toppings::justify] count as such, so that only the ragged ones move.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Align, Salsa, Toppings};
+    ///
+    /// let input = "  # foo bar baz\n";
+    ///
+    /// let toppings = Toppings::default().width(12).align(Align::Right);
+    /// let output = wrap::<Salsa>(input, toppings.clone()).collect::<String>();
+    /// assert_eq!(output, "   # foo bar\n       # baz\n");
+    ///
+    /// let output = wrap::<Salsa>(input, toppings.align(Align::Center)).collect::<String>();
+    /// assert_eq!(output, "  # foo bar\n    # baz\n");
+    /// ```
+    pub fn align(self, align: Align) -> Self {
+        Self { align, ..self }
+    }
+
+    /// How to align a paragraph given the class of its first line, if
+    /// [Toppings::align] is [Align::Auto]. By default, [LineClass::Heading] is
+    /// centered and everything else is aligned to the left. Returning
+    /// [Align::Auto] aligns to the left.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Align, LineClass, Salsa, Toppings};
+    ///
+    /// let input = "# Title\n\nSome words.\n\n- an item\n";
+    /// let toppings = Toppings::default().width(20).markdown(true).align(Align::Auto);
+    ///
+    /// let output = wrap::<Salsa>(input, toppings.clone()).collect::<String>();
+    /// assert_eq!(output, "      # Title\n\nSome words.\n\n- an item\n");
+    ///
+    /// let toppings = toppings.auto_align(|class| match class {
+    ///     LineClass::Heading | LineClass::Bullet => Align::Right,
+    ///     _ => Align::Left,
+    /// });
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    /// assert_eq!(output, "             # Title\n\nSome words.\n\n           - an item\n");
+    /// ```
+    pub fn auto_align<F>(self, auto_align: F) -> Self
+    where
+        F: Fn(LineClass) -> Align + Send + Sync + 'static,
+    {
+        Self {
+            auto_align: Some(Shared(Arc::new(auto_align))),
+            ..self
+        }
+    }
+
+    /// Width of the hyphen at the end of a line that ends within a word, see
+    /// [Toppings::hyphenate].
+    fn hyphen_width(&self) -> usize {
+        #[cfg(feature = "hyphen")]
+        if self.hyphenate.is_some() {
+            return 1;
+        }
+
+        0
+    }
+
+    /// How wide tab characters are, see [Toppings::tabs] and
+    /// [Toppings::tab_width_by_level].
+    fn tab_widths(&self) -> Tabs {
+        Tabs {
+            width: self.tabs,
+            levels: self.tab_levels.clone(),
+        }
+    }
+
+    /// The alignment of a paragraph starting with `line`, resolving
+    /// [Align::Auto].
+    fn align_for(&self, line: &Line) -> Align {
+        match self.align {
+            Align::Auto => {
+                let class = line.class();
+                match &self.auto_align {
+                    Some(auto_align) => (auto_align.0)(class),
+                    None if class == LineClass::Heading => Align::Center,
+                    None => Align::Left,
+                }
+            }
+            align => align,
+        }
+    }
+
+    /// Whether to end output with a newline. The default value is
+    /// [FinalNewline::Preserve].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, FinalNewline, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().final_newline(FinalNewline::EnsureNonEmpty);
+    /// let wrap = |input| wrap::<Salsa>(input, toppings.clone()).collect::<String>();
+    ///
+    /// assert_eq!(wrap(""), "");
+    /// assert_eq!(wrap("foo"), "foo\n");
+    /// assert_eq!(wrap("foo\n"), "foo\n");
+    /// ```
+    ///
+    /// Whichever newline ends the input, a newline in output is always
+    /// [Toppings::newline]. A carriage return (`\r`) at the very end of input
+    /// counts as a newline, too:
+    ///
+    /// ```
+    /// use tortilla::{wrap, FinalNewline, Newline, Salsa, Toppings};
+    ///
+    /// let toppings = Toppings::default().newline(Newline::CRLF);
+    /// let wrap = |input, final_newline| {
+    ///     let toppings = toppings.clone().final_newline(final_newline);
+    ///     wrap::<Salsa>(input, toppings).collect::<String>()
+    /// };
+    ///
+    /// for (input, preserve, ensure) in [
+    ///     ("", "", ""),
+    ///     ("foo", "foo", "foo\r\n"),
+    ///     ("foo\n", "foo\r\n", "foo\r\n"),
+    ///     ("foo\r\n", "foo\r\n", "foo\r\n"),
+    ///     ("foo\r", "foo\r\n", "foo\r\n"),
+    ///     ("foo \r", "foo\r\n", "foo\r\n"),
+    ///     ("foo\r\n\r", "foo\r\n\r\n", "foo\r\n\r\n"),
+    ///     ("\r", "\r\n", "\r\n"),
+    /// ] {
+    ///     assert_eq!(wrap(input, FinalNewline::Preserve), preserve);
+    ///     assert_eq!(wrap(input, FinalNewline::EnsureNonEmpty), ensure);
+    /// }
+    /// ```
+    pub fn final_newline(self, final_newline: FinalNewline) -> Self {
+        Self {
+            final_newline,
+            ..self
+        }
+    }
+
+    /// Normalize the indentation of nested list items to `step` spaces per
+    /// level of nesting. Unset by default, in which case list items keep their
+    /// original indentation.
+    ///
+    /// Nesting is detected by comparing the indentation of each bullet with
+    /// the ones above it. The outermost list item stays where it is, and
+    /// indentation that consists of tabs is replaced with spaces. Within
+    /// comments, the whitespace following the comment token is normalized
+    /// instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "\
+    /// - foo
+    ///      - bar
+    ///         - baz
+    ///    - qux
+    /// ";
+    ///
+    /// let toppings = Toppings::default().list_indent(2);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, "\
+    /// - foo
+    ///   - bar
+    ///     - baz
+    ///   - qux
+    /// ");
+    /// ```
+    pub fn list_indent(self, step: usize) -> Self {
+        Self {
+            list_indent: Some(step),
+            ..self
+        }
+    }
+
+    /// Right-align the numbers of consecutive numbered list items (e.g., `9.`
+    /// and `10.`), such that the text of all items starts at the same column.
+    /// Disabled by default.
+    ///
+    /// Shorter numbers are preceded by additional spaces. A blank line, or any
+    /// other paragraph that is not a numbered list item, ends the list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Toppings};
+    ///
+    /// let input = "\
+    /// 9. foo bar
+    /// 10. baz qux
+    /// ";
+    ///
+    /// let toppings = Toppings::default().width(8).align_list_numbers(true);
+    /// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+    ///
+    /// assert_eq!(output, " 9. foo\n    bar\n10. baz\n    qux\n");
+    /// ```
+    pub fn align_list_numbers(self, align_list_numbers: bool) -> Self {
+        Self {
+            align_list_numbers,
+            ..self
+        }
+    }
+}
+
+/// Wrap text. Output is lazily generated and returned in small chunks.
+///
+/// To set the line breaking algorithm, see [Guacamole] and [Salsa]. For other
+/// options, see [Toppings].
+///
+/// Input that consists of whitespace and newlines only (including the empty
+/// string) is returned unchanged with default [Toppings], with the exception
+/// of CRLF (`\r\n`) newlines, which are replaced with [Toppings::newline].
+///
+/// # Examples
+///
+/// Wrap a string and collect it into a new string:
+///
+/// ```
+/// use tortilla::{wrap, Salsa, Toppings};
+///
+/// // Leading indent of two spaces, comment token and bullet point:
+/// let input = "
+///   // - foo bar baz
+/// ";
+///
+/// // Set a low width that will force wrapping across several lines:
+/// let toppings = Toppings::default().width(8);
+/// let output = wrap::<Salsa>(input, toppings).collect::<String>();
+///
+/// // Indent and comment token are replicated onto new lines, bullet point
+/// // remains only on the first line and is replaced by spacing on the
+/// // subsequent ones:
+/// assert_eq!(output, "
+///   // - foo
+///   //   bar
+///   //   baz
+/// ");
+///
+/// // Now set a much higher width, which will get us back to the original input
+/// // (a single line):
+/// let toppings = Toppings::default().width(100);
+/// let output = wrap::<Salsa>(&output, toppings).collect::<String>();
+///
+/// assert_eq!(output, input);
+/// ```
+///
+/// Stream output to stdout:
+///
+/// ```
+/// use std::io::{self, Write};
+/// use tortilla::{wrap, Salsa, Toppings};
+///
+/// let input = "...";
+///
+/// let toppings = Toppings::default();
+/// let mut stdout = io::stdout().lock();
+///
+/// for chunk in wrap::<Salsa>(input, toppings) {
+///     stdout.write_all(chunk.as_bytes()).unwrap();
+/// }
+/// ```
+///
+pub fn wrap<S: Sauce>(input: &str, toppings: Toppings) -> Wrap<'_, S> {
+    wrap_with_boundary(input, toppings, paragraph_boundary)
+}
+
+/// Wrap text, deciding where paragraphs start with a custom predicate.
+///
+/// By default, consecutive lines are joined into a single paragraph unless
+/// separated by an empty line, a change of indentation or comment token, or a
+/// new bullet (see [paragraph_boundary]). `boundary` replaces that decision: it
+/// is called with each pair of adjacent lines and returns `true` if the lower
+/// line starts a new paragraph. The indentation, comment token and bullet of
+/// the first line in a paragraph are used for all of its output lines.
+///
+/// # Examples
+///
+/// Treat every sentence as its own paragraph:
+///
+/// ```
+/// use tortilla::{paragraph_boundary, wrap_with_boundary, LineInfo, Salsa, Toppings};
+///
+/// let sentences = |upper: &LineInfo, lower: &LineInfo| {
+///     paragraph_boundary(upper, lower)
+///         || upper.words().last().is_some_and(|word| word.ends_with('.'))
+/// };
+///
+/// let input = "Foo bar.\nBaz\nqux.\n";
+/// let output = wrap_with_boundary::<Salsa, _>(input, Toppings::default(), sentences)
+///     .collect::<String>();
+///
+/// assert_eq!(output, "Foo bar.\nBaz qux.\n");
+/// ```
+pub fn wrap_with_boundary<S, B>(input: &str, toppings: Toppings, boundary: B) -> Wrap<'_, S, B>
+where
+    S: Sauce,
+    B: FnMut(&LineInfo<'_>, &LineInfo<'_>) -> bool,
+{
+    let (lines, toppings) = merged(input, toppings, boundary);
+    Wrap::new(Paragraphs::Input(Box::new(lines)), toppings)
+}
+
+/// Wrap text to a maximum width that varies from line to line, e.g., to flow
+/// text around a floating element.
+///
+/// `width_for_line` is called with the index of each output line (starting at
+/// 0), and returns the targeted maximum width for that line, replacing
+/// [Toppings::width].
+///
+/// Line breaking algorithms are notified of each change of width within a
+/// paragraph, see [Sauce::resize]. [Guacamole] decides on breaks as it goes,
+/// while [Salsa] decides on the remaining breaks again as the width changes.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{wrap_variable, Guacamole, Toppings};
+///
+/// let input = "foo bar baz qux quux corge grault";
+///
+/// // Two narrow lines, then wide ones:
+/// let output = wrap_variable::<Guacamole, _>(input, Toppings::default(), |line| {
+///     if line < 2 { 8 } else { 20 }
+/// });
+///
+/// assert_eq!(output, "foo bar\nbaz qux\nquux corge grault");
+/// ```
+///
+/// Output ends like that of [wrap], see [Toppings::final_newline]:
+///
+/// ```
+/// use tortilla::{wrap, wrap_variable, FinalNewline, Salsa, Toppings};
+///
+/// let toppings = Toppings::default().width(7).final_newline(FinalNewline::EnsureNonEmpty);
+/// let output = wrap_variable::<Salsa, _>("foo bar baz", toppings.clone(), |_| 7);
+///
+/// assert_eq!(output, "foo bar\nbaz\n");
+/// assert_eq!(output, wrap::<Salsa>("foo bar baz", toppings).collect::<String>());
+/// ```
+pub fn wrap_variable<S, F>(input: &str, toppings: Toppings, mut width_for_line: F) -> String
+where
+    S: Sauce,
+    F: FnMut(usize) -> usize,
+{
+    let (lines, toppings) = merged(input, toppings, paragraph_boundary);
+    let mut output = String::with_capacity(input.len());
+    let mut line_idx = 0;
+
+    for line in lines {
+        let first_line_idx = line_idx;
+        let inner = LineWrap::with_widths::<S>(line, &toppings, &mut |idx| {
+            width_for_line(first_line_idx + idx)
+        });
+
+        for chunk in inner {
+            output.push_str(chunk);
+
+            if chunk == toppings.newline.as_str() {
+                line_idx += 1;
+            }
+        }
+    }
+
+    output.extend(final_newline(
+        &toppings,
+        !output.is_empty(),
+        output.ends_with('\n'),
+    ));
+    output
+}
+
+/// Wrap text into a [String], calling `on_paragraph` after each paragraph has
+/// been wrapped, e.g., to report progress on large inputs.
+///
+/// `on_paragraph` is called with the number of paragraphs wrapped so far
+/// (starting at 1). Empty lines count as paragraphs of their own.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{wrap_with_progress, Salsa, Toppings};
+///
+/// let mut progress = Vec::new();
+/// let output = wrap_with_progress::<Salsa, _>("foo\nbar\n\nbaz", Toppings::default(), |n| {
+///     progress.push(n)
+/// });
+///
+/// assert_eq!(output, "foo bar\n\nbaz");
+/// assert_eq!(progress, vec![1, 2, 3]);
+/// ```
+///
+/// Output ends like that of [wrap], see [Toppings::final_newline]:
+///
+/// ```
+/// use tortilla::{wrap, wrap_with_progress, FinalNewline, Salsa, Toppings};
+///
+/// let toppings = Toppings::default().width(7).final_newline(FinalNewline::EnsureNonEmpty);
+/// let output = wrap_with_progress::<Salsa, _>("foo bar baz", toppings.clone(), |_| ());
+///
+/// assert_eq!(output, "foo bar\nbaz\n");
+/// assert_eq!(output, wrap::<Salsa>("foo bar baz", toppings).collect::<String>());
+/// ```
+///
+/// So does its newline, see [Toppings::detect_newline]:
+///
+/// ```
+/// use tortilla::{wrap, wrap_with_progress, Salsa, Toppings};
+///
+/// let input = "foo bar baz qux\r\n";
+/// let toppings = Toppings::default().width(7).detect_newline(true);
+/// let output = wrap_with_progress::<Salsa, _>(input, toppings.clone(), |_| ());
+///
+/// assert_eq!(output, "foo bar\r\nbaz qux\r\n");
+/// assert_eq!(output, wrap::<Salsa>(input, toppings).collect::<String>());
+/// ```
+pub fn wrap_with_progress<S, F>(input: &str, toppings: Toppings, mut on_paragraph: F) -> String
+where
+    S: Sauce,
+    F: FnMut(usize),
+{
+    let (lines, toppings) = merged(input, toppings, paragraph_boundary);
+    let mut output = String::with_capacity(input.len());
+
+    for (idx, line) in lines.enumerate() {
+        output.extend(LineWrap::new::<S>(line, &toppings));
+        on_paragraph(idx + 1);
+    }
+
+    output.extend(final_newline(
+        &toppings,
+        !output.is_empty(),
+        output.ends_with('\n'),
+    ));
+    output
+}
+
+/// Wrap text one paragraph at a time, yielding each wrapped paragraph as a
+/// [String] that includes its line breaks and trailing newline, if any.
+///
+/// Concatenating all paragraphs yields the same output as [wrap]. Blank lines
+/// belong to the paragraph before them, or to the first paragraph if they
+/// start the input.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{wrap, wrap_paragraphs, Salsa, Toppings};
+///
+/// let input = "foo\nbar\n- baz\n  qux\n# quux\n";
+///
+/// let toppings = Toppings::default();
+/// let paragraphs = wrap_paragraphs::<Salsa>(input, toppings.clone()).collect::<Vec<_>>();
+///
+/// assert_eq!(paragraphs, ["foo bar\n", "- baz qux\n", "# quux\n"]);
+/// assert_eq!(paragraphs.concat(), wrap::<Salsa>(input, toppings).collect::<String>());
+/// ```
+///
+/// Paragraphs separated by blank lines:
+///
+/// ```
+/// use tortilla::{wrap, wrap_paragraphs, FinalNewline, Salsa, Toppings};
+///
+/// let input = "\nfoo bar\nbaz\n\n\nqux quux\n\ncorge grault";
+///
+/// let toppings = Toppings::default().width(8);
+/// let paragraphs = wrap_paragraphs::<Salsa>(input, toppings.clone()).collect::<Vec<_>>();
+///
+/// assert_eq!(paragraphs, ["\nfoo bar\nbaz\n\n\n", "qux quux\n\n", "corge\ngrault"]);
+/// assert_eq!(paragraphs.concat(), wrap::<Salsa>(input, toppings.clone()).collect::<String>());
+///
+/// let toppings = toppings.final_newline(FinalNewline::EnsureNonEmpty);
+/// let paragraphs = wrap_paragraphs::<Salsa>(input, toppings.clone()).collect::<Vec<_>>();
+///
+/// assert_eq!(paragraphs[2], "corge\ngrault\n");
+/// assert_eq!(paragraphs.concat(), wrap::<Salsa>(input, toppings).collect::<String>());
+/// ```
+pub fn wrap_paragraphs<S: Sauce>(input: &str, toppings: Toppings) -> impl Iterator<Item = String> {
+    let (lines, toppings) = merged(input, toppings, paragraph_boundary);
+    let mut lines = lines.peekable();
+    let blank =
+        |line: &Line| line.words.is_empty() && line.bullet.is_none() && line.verbatim.is_none();
+    let mut emitted = false;
+
+    std::iter::from_fn(move || {
+        let mut paragraph = String::new();
+        let mut started = false;
+
+        while let Some(line) = lines.next_if(|line| !started || blank(line)) {
+            started |= !blank(&line);
+            paragraph.extend(LineWrap::new::<S>(line, &toppings));
+        }
+
+        if paragraph.is_empty() && !started {
+            return None;
+        }
+
+        emitted |= !paragraph.is_empty();
+        if lines.peek().is_none() {
+            let newline = final_newline(&toppings, emitted, paragraph.ends_with('\n'));
+            paragraph.extend(newline);
+        }
+
+        Some(paragraph)
+    })
+}
+
+/// Wrap a block of comment lines that each start with `marker` (e.g., `//`),
+/// regardless of whether tortilla would detect `marker` as a comment token by
+/// itself.
+///
+/// Indentation is taken from the first line. Markers are stripped from all
+/// lines along with a single space following them, the remaining text is
+/// wrapped to fit the remaining width, and each output line is prefixed with
+/// indentation, `marker` and a single space again (or just indentation and
+/// `marker`, for empty lines). Lines that do not start with `marker` are
+/// wrapped as if they did.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{rewrap_comment, Salsa, Toppings};
+///
+/// let toppings = Toppings::default().width(20);
+///
+/// let input = "  // foo bar\n  // baz qux\n  // quux corge\n";
+/// let output = rewrap_comment::<Salsa>(input, "//", &toppings);
+/// assert_eq!(output, "  // foo bar baz qux\n  // quux corge\n");
+///
+/// let toppings = Toppings::default().width(12);
+/// let output = rewrap_comment::<Salsa>(&output, "//", &toppings);
+/// assert_eq!(output, "  // foo bar\n  // baz qux\n  // quux\n  // corge\n");
+/// ```
+pub fn rewrap_comment<S: Sauce>(text: &str, marker: &str, toppings: &Toppings) -> String {
+    let toppings = &toppings.clone().detected(text);
+    let indent_len = text.len() - text.trim_start_matches([' ', '\t']).len();
+    let indent = &text[..indent_len];
+
+    let mut prose = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let (line, newline) = match line.strip_suffix('\n') {
+            Some(line) => (line.strip_suffix('\r').unwrap_or(line), true),
+            None => (line, false),
+        };
+
+        let line = line.trim_start_matches([' ', '\t']);
+        let line = match line.strip_prefix(marker) {
+            Some(line) => line.strip_prefix(' ').unwrap_or(line),
+            None => line,
+        };
+
+        prose.push_str(line);
+        if newline {
+            prose.push('\n');
+        }
+    }
+
+    let prefix_width = parse::text_width(indent, toppings.tabs, toppings.cjk)
+        + parse::str_width(marker, toppings.cjk)
+        + 1;
+    let inner = Toppings {
+        width: toppings.width.saturating_sub(prefix_width),
+        newline: Newline::LF,
+        ..toppings.clone()
+    };
+
+    let wrapped = wrap::<S>(&prose, inner).collect::<String>();
+
+    let mut output = String::with_capacity(text.len());
+    for line in wrapped.split_inclusive('\n') {
+        let (line, newline) = match line.strip_suffix('\n') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+
+        output.push_str(indent);
+        output.push_str(marker);
+        if !line.is_empty() {
+            output.push(' ');
+            output.push_str(line);
+        }
+        if newline {
+            output.push_str(toppings.newline.as_str());
+        }
+    }
+
+    output
+}
+
+/// Width of the part of a line that precedes its first word and is never
+/// wrapped: indentation, comment token, padding and bullet token (including the
+/// space following it). Only the first line of `input_line` is considered.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{prefix_width, Toppings};
+///
+/// let toppings = Toppings::default();
+///
+/// assert_eq!(prefix_width("foo bar", &toppings), 0);
+/// assert_eq!(prefix_width("  // foo", &toppings), 5);
+/// assert_eq!(prefix_width("\t# - foo", &toppings), 8);
+/// assert_eq!(prefix_width("  10. foo\nbar", &toppings), 6);
+/// assert_eq!(prefix_width("", &toppings), 0);
+/// ```
+pub fn prefix_width(input_line: &str, toppings: &Toppings) -> usize {
+    Parse::new(input_line, Lex::new(input_line), toppings)
+        .next()
+        .map(|line| wrap::unbreakable_width(&line, toppings))
+        .unwrap_or(0)
+}
+
+/// Render whitespace as visible glyphs: spaces as `·`, tabs as `→`, carriage
+/// returns as `␍` and newlines as `⏎`, followed by the newline itself. All
+/// other characters are left as they are.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{visible_whitespace, wrap, Salsa, Toppings};
+///
+/// let toppings = Toppings::default().width(10);
+/// let output = wrap::<Salsa>("\t- foo bar", toppings).collect::<String>();
+///
+/// assert_eq!(visible_whitespace(&output), "→-·foo⏎\n→··bar");
+/// ```
+pub fn visible_whitespace(text: &str) -> String {
+    let mut visible = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            ' ' => visible.push('·'),
+            '\t' => visible.push('→'),
+            '\r' => visible.push('␍'),
+            '\n' => visible.push_str("⏎\n"),
+            c => visible.push(c),
+        }
+    }
+
+    visible
+}
+
+/// Predominant indentation of a document, see [detect_indent].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indentation {
+    /// Lines are indented with tab characters (`\t`).
+    Tabs,
+    /// Lines are indented with space characters (' '), nesting in steps of the
+    /// given number of spaces.
+    Spaces(usize),
+}
+
+/// Detect whether `input` is predominantly indented with tabs or spaces, and
+/// for spaces, the most common nesting step out of 2, 4 and 8.
+///
+/// Only lines with words count, and lines indented with a mix of tabs and
+/// spaces are ignored. Ties between tabs and spaces go to spaces, ties between
+/// steps go to the smaller step. Documents without any indentation count as
+/// `Indentation::Spaces(4)`, in line with [Toppings::default].
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{detect_indent, Indentation};
+///
+/// let input = "fn main() {\n  if yes {\n    go();\n  }\n}\n";
+/// assert_eq!(detect_indent(input), Indentation::Spaces(2));
+///
+/// let input = "fn main() {\n\tif yes {\n\t\tgo();\n\t}\n}\n";
+/// assert_eq!(detect_indent(input), Indentation::Tabs);
+///
+/// // One line each, so spaces win the tie.
+/// let input = "foo\n\tbar\n        baz\n";
+/// assert_eq!(detect_indent(input), Indentation::Spaces(8));
+///
+/// assert_eq!(detect_indent("foo\nbar\n"), Indentation::Spaces(4));
+/// ```
+pub fn detect_indent(input: &str) -> Indentation {
+    let toppings = Toppings::default();
+
+    let mut tabs = 0;
+    let mut spaces = 0;
+    // How often indentation changes by 2, 4 or 8 spaces, counting each change
+    // towards the largest step that divides it.
+    let mut steps = [0; 3];
+    let mut previous = 0;
+
+    for line in Parse::new(input, Lex::new(input), &toppings) {
+        if line.words.is_empty() || line.raw_indent.is_some() {
+            continue;
+        }
+
+        match line.indent {
+            Whitespace::Tab(_) => tabs += 1,
+            Whitespace::Space(count) => {
+                spaces += usize::from(count > 0);
+
+                let delta = count.abs_diff(previous);
+                previous = count;
+
+                if let Some(idx) = [2, 4, 8]
+                    .iter()
+                    .rposition(|step| delta > 0 && delta % step == 0)
+                {
+                    steps[idx] += 1;
+                }
+            }
+        }
+    }
+
+    if tabs > spaces {
+        return Indentation::Tabs;
+    }
+
+    // On equal counts, the first (smallest) step wins.
+    let step = [2, 4, 8]
+        .into_iter()
+        .zip(steps)
+        .filter(|(_, count)| *count > 0)
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map_or(4, |(step, _)| step);
+
+    Indentation::Spaces(step)
+}
+
+/// Size in bytes after which [wrap_reader] wraps what it has read so far once a
+/// new paragraph starts, even if no blank line has been found yet.
+const READER_GROUP_BYTES: usize = 1 << 20;
+
+/// Wrap text read from `reader` incrementally, e.g., a large file streamed
+/// from a pipe, without reading all of it into memory first.
+///
+/// Input is read up to the next blank line that follows some text, or, once
+/// more than 1 MiB has been read, up to the next line that starts a new
+/// paragraph (see [paragraph_boundary]), and wrapped as a group. Each item is
+/// the wrapped output of one such group of lines. Paragraphs are never split
+/// between groups, and each group picks up where the one before left off, such
+/// that toppings which look beyond a single paragraph, e.g.,
+/// [Toppings::max_blank_lines] or [Toppings::metadata], apply across groups.
+///
+/// Memory use is therefore bounded by the largest paragraph rather than by the
+/// input as a whole: A log of lines that all merge into a single paragraph,
+/// e.g., without any blank lines, bullets or indentation changes, is read
+/// entirely before any of it is wrapped. Disable merging (see
+/// [Toppings::overflow_only] or [Toppings::reflow]) to wrap such input one line
+/// at a time.
+///
+/// Iteration stops after the first error, e.g., when input is not valid UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{wrap_reader, Salsa, Toppings};
+///
+/// let input = "foo bar baz\n\nqux\nquux\n".as_bytes();
+/// let output = wrap_reader::<Salsa, _>(input, Toppings::default().width(8))
+///     .collect::<std::io::Result<Vec<String>>>()
+///     .unwrap();
+///
+/// assert_eq!(output, vec!["foo bar\nbaz\n", "\nqux quux\n"]);
+/// ```
+///
+/// Output is the same as that of [wrap], also for paragraphs beyond 1 MiB:
+///
+/// ```
+/// use tortilla::{wrap, wrap_reader, Guacamole, Toppings};
+///
+/// let input = format!("{}{}", "foo bar baz qux\n".repeat(70_000), "- quux corge\n".repeat(3));
+/// let toppings = Toppings::default();
+///
+/// let groups = wrap_reader::<Guacamole, _>(input.as_bytes(), toppings.clone())
+///     .collect::<std::io::Result<Vec<String>>>()
+///     .unwrap();
+///
+/// // The long paragraph ends the first group, the list items form the second.
+/// assert_eq!(groups.len(), 2);
+/// assert_eq!(groups.concat(), wrap::<Guacamole>(&input, toppings).collect::<String>());
+/// ```
+///
+/// Without merging, any line starts a new paragraph, so groups stay small even
+/// if there are no blank lines at all:
+///
+/// ```
+/// use tortilla::{wrap, wrap_reader, Guacamole, Toppings};
+///
+/// let input = "foo bar baz qux\n".repeat(70_000);
+/// let toppings = Toppings::default().width(8).reflow(false);
+///
+/// let groups = wrap_reader::<Guacamole, _>(input.as_bytes(), toppings.clone())
+///     .collect::<std::io::Result<Vec<String>>>()
+///     .unwrap();
+///
+/// assert_eq!(groups.len(), 2);
+/// assert_eq!(groups.concat(), wrap::<Guacamole>(&input, toppings).collect::<String>());
+/// ```
+///
+/// So is output with toppings that look across blank lines:
+///
+/// ```
+/// use tortilla::{wrap, wrap_reader, Salsa, Toppings};
+///
+/// let cases = [
+///     ("foo\n\n\n\nbar\n", Toppings::default().max_blank_lines(1)),
+///     ("foo\n\n\n\nbar\n", Toppings::default().single_blank_between_paragraphs(true)),
+///     (
+///         "foo bar baz qux quux\n\nTitle: a b c d e f g h\n",
+///         Toppings::default().width(10).metadata(true),
+///     ),
+///     ("/*\nfoo bar\n\nbaz qux */\n", Toppings::default().width(5).block_comments(true)),
+///     ("\r  \n\\", Toppings::default()),
+/// ];
+///
+/// for (input, toppings) in cases {
+///     let groups = wrap_reader::<Salsa, _>(input.as_bytes(), toppings.clone())
+///         .collect::<std::io::Result<Vec<String>>>()
+///         .unwrap();
+///
+///     assert_eq!(groups.concat(), wrap::<Salsa>(input, toppings).collect::<String>());
+/// }
+/// ```
+pub fn wrap_reader<S: Sauce, R: Read>(reader: R, toppings: Toppings) -> WrapReader<S, R> {
+    WrapReader {
+        reader: io::BufReader::new(reader),
+        groups: Groups::new(toppings),
+        done: false,
+    }
+}
+
+/// An [Iterator] over groups of wrapped output, see [wrap_reader].
+pub struct WrapReader<S, R> {
+    reader: io::BufReader<R>,
+    groups: Groups<S>,
+    done: bool,
+}
+
+impl<S: Sauce, R: Read> Iterator for WrapReader<S, R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.groups.begin();
+
+        while !self.done {
+            let start = self.groups.group.len();
+
+            match self.reader.read_line(&mut self.groups.group) {
+                Ok(0) => self.done = true,
+                Ok(_) if self.groups.ends_group(start) => break,
+                Ok(_) => continue,
+                Err(err) => {
+                    self.done = true;
+                    self.groups.carry.clear();
+                    return Some(Err(err));
+                }
+            }
+        }
+
+        self.groups.wrap().map(Ok)
+    }
+}
+
+impl<S, R> WrapReader<S, R> {
+    /// Statistics about all output so far, see [Wrap::stats]. Groups are
+    /// wrapped independently, so the last line of each group counts as the
+    /// last line of a paragraph.
+    pub fn stats(&self) -> Stats {
+        self.groups.stats
+    }
+}
+
+/// Wrap text read from an asynchronous `reader`, e.g., a large upload to a web
+/// service, without reading all of it into memory first or blocking on IO.
+///
+/// This is the asynchronous counterpart to [wrap_reader]: input is grouped and
+/// wrapped the same way, and each item of the stream is the wrapped output of
+/// one group of lines, as soon as it has been read. Output is the same as that
+/// of [wrap]. The stream ends after the first error, e.g., when input is not
+/// valid UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// use futures::{executor, io::Cursor, TryStreamExt};
+/// use tortilla::{wrap, wrap_async, Salsa, Toppings};
+///
+/// let input = "foo bar baz\n\nqux\nquux\n\n- corge grault\n";
+/// let toppings = Toppings::default().width(8);
+///
+/// let groups = executor::block_on(
+///     wrap_async::<Salsa, _>(Cursor::new(input), toppings.clone()).try_collect::<Vec<_>>(),
+/// )
+/// .unwrap();
+///
+/// assert_eq!(groups, vec!["foo bar\nbaz\n", "\nqux quux\n", "\n- corge\n  grault\n"]);
+/// assert_eq!(groups.concat(), wrap::<Salsa>(input, toppings).collect::<String>());
+/// ```
+#[cfg(feature = "futures")]
+pub fn wrap_async<S, R>(
+    reader: R,
+    toppings: Toppings,
+) -> impl futures::Stream<Item = io::Result<String>>
+where
+    S: Sauce,
+    R: futures::AsyncBufRead + Unpin,
+{
+    use futures::AsyncBufReadExt;
+
+    let groups = Groups::<S>::new(toppings);
+
+    futures::stream::unfold(
+        (reader, groups, false),
+        |(mut reader, mut groups, mut done)| async move {
+            groups.begin();
+
+            while !done {
+                let start = groups.group.len();
+
+                match reader.read_line(&mut groups.group).await {
+                    Ok(0) => done = true,
+                    Ok(_) if groups.ends_group(start) => break,
+                    Ok(_) => continue,
+                    Err(err) => {
+                        groups.carry.clear();
+                        return Some((Err(err), (reader, groups, true)));
+                    }
+                }
+            }
+
+            let output = groups.wrap()?;
+            Some((Ok(output), (reader, groups, done)))
+        },
+    )
+}
+
+/// Input read so far, split into groups of lines that are wrapped one at a
+/// time, see [wrap_reader].
+struct Groups<S> {
+    toppings: Toppings,
+    group: String,
+    /// The line that starts the next group, if the current one was cut short.
+    carry: String,
+    /// Whether the group has any line that isn't blank, i.e., whether it may
+    /// end before the next blank line.
+    filled: bool,
+    /// Where parsing and merging of the previous group left off.
+    state: Option<MergeState>,
+    stats: Stats,
+    sauce: PhantomData<S>,
+}
+
+impl<S> Groups<S> {
+    fn new(toppings: Toppings) -> Self {
+        Self {
+            toppings,
+            group: String::new(),
+            carry: String::new(),
+            filled: false,
+            state: None,
+            stats: Stats::default(),
+            sauce: PhantomData,
+        }
+    }
+
+    /// Start the next group, with the line that the previous one was cut
+    /// short before, if any.
+    fn begin(&mut self) {
+        self.group.clear();
+        std::mem::swap(&mut self.group, &mut self.carry);
+        self.filled = !is_blank_line(&self.group);
+    }
+
+    /// Whether the line that was read into the group at byte `start` belongs
+    /// to the next group instead, in which case it is moved there.
+    fn ends_group(&mut self, start: usize) -> bool {
+        let blank = is_blank_line(&self.group[start..]);
+
+        if self.filled && (blank || start >= READER_GROUP_BYTES && self.starts_paragraph(start)) {
+            self.carry.push_str(&self.group[start..]);
+            self.group.truncate(start);
+            return true;
+        }
+
+        self.filled |= !blank;
+        false
+    }
+
+    /// Whether the line of the group starting at byte `start` starts a new
+    /// paragraph, such that the group may end right before it.
+    fn starts_paragraph(&self, start: usize) -> bool {
+        let upper_start = self.group[..start - 1].rfind('\n').map_or(0, |idx| idx + 1);
+        let parse = |text| Parse::new(text, Lex::new(text), &self.toppings).next();
+        let tabs = self.toppings.tab_widths();
+
+        match (
+            parse(&self.group[upper_start..start]),
+            parse(&self.group[start..]),
+        ) {
+            // Continued lines are merged even if nothing else is, see
+            // [Toppings::continuation].
+            (Some(upper), Some(_)) if upper.continuation.is_some() => false,
+            (Some(_), Some(_)) if !self.toppings.reflow || self.toppings.overflow_only => true,
+            (Some(upper), Some(lower)) => paragraph_boundary(
+                &LineInfo(&upper, self.toppings.cjk, &tabs),
+                &LineInfo(&lower, self.toppings.cjk, &tabs),
+            ),
+            _ => true,
+        }
+    }
+
+    /// The wrapped output of the group, unless there is no input left.
+    fn wrap(&mut self) -> Option<String>
+    where
+        S: Sauce,
+    {
+        let mut output = String::new();
+        self.wrap_with(|_, chunk| output.push_str(chunk))
+            .then_some(output)
+    }
+
+    /// Wrap the group, passing each chunk of output to `emit` along with the
+    /// group, which the chunk may be a slice of. False if there is no input
+    /// left.
+    fn wrap_with(&mut self, mut emit: impl FnMut(&str, &str)) -> bool
+    where
+        S: Sauce,
+    {
+        // The first newline found decides for all groups that follow.
+        self.toppings = std::mem::take(&mut self.toppings).detected(&self.group);
+
+        if self.group.is_empty() {
+            return false;
+        }
+
+        let (lines, toppings) = merged(&self.group, self.toppings.clone(), paragraph_boundary);
+        let lines = match self.state.take() {
+            Some(state) => lines.resume(state),
+            None => lines,
+        };
+
+        let mut wrap = Wrap::<S, _>::new(Paragraphs::Input(Box::new(lines)), toppings);
+        for chunk in wrap.by_ref() {
+            emit(&self.group, chunk);
+        }
+        self.stats.add(wrap.stats());
+
+        if let Paragraphs::Input(lines) = &wrap.lines {
+            self.state = Some(lines.state());
+        }
+
+        true
+    }
+}
+
+/// Whether a line of input has no words, i.e., is empty or consists of spaces
+/// and tabs only, see [Lex].
+fn is_blank_line(line: &str) -> bool {
+    Lex::new(line).all(|(_, token)| !matches!(token, Token::Word(_)))
+}
+
+/// Wrap text that is mostly, but not necessarily valid UTF-8, e.g., logs with
+/// the occasional stray byte.
+///
+/// Invalid byte sequences are passed through untouched as part of the word they
+/// occur in, each byte counting as one column. Output chunks borrow from
+/// `input` where possible. Input that isn't valid UTF-8 in its entirety is
+/// wrapped one group of lines at a time, as with [wrap_reader], rather than
+/// copied as a whole.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{wrap_bytes, Salsa, Toppings};
+///
+/// let input = b"foo \xff bar baz";
+/// let output = wrap_bytes::<Salsa>(input, Toppings::default().width(6))
+///     .flat_map(|chunk| chunk.into_owned())
+///     .collect::<Vec<u8>>();
+///
+/// assert_eq!(output, b"foo\n\xff bar\nbaz");
+/// ```
+pub fn wrap_bytes<S: Sauce>(
+    input: &[u8],
+    toppings: Toppings,
+) -> impl Iterator<Item = Cow<'_, [u8]>> {
+    let (valid, invalid) = match std::str::from_utf8(input) {
+        Ok(input) => (Some(wrap::<S>(input, toppings)), None),
+        Err(_) => (None, Some(wrap_invalid::<S>(input, toppings))),
+    };
+
+    valid
+        .into_iter()
+        .flatten()
+        .map(|chunk| Cow::Borrowed(chunk.as_bytes()))
+        .chain(invalid.into_iter().flatten())
+}
+
+/// See [wrap_bytes]. Each line is read into a group with each invalid byte
+/// substituted with a placeholder of the same length, such that chunks of the
+/// group can be mapped back to the same range of `input`.
+fn wrap_invalid<S: Sauce>(input: &[u8], toppings: Toppings) -> impl Iterator<Item = Cow<'_, [u8]>> {
+    const PLACEHOLDER: char = '?';
+
+    let mut lines = input.split_inclusive(|byte| *byte == b'\n');
+    let mut groups = Groups::<S>::new(toppings);
+    let mut read = 0;
+
+    std::iter::from_fn(move || {
+        groups.begin();
+
+        for line in lines.by_ref() {
+            let start = groups.group.len();
+            for chunk in line.utf8_chunks() {
+                groups.group.push_str(chunk.valid());
+                groups
+                    .group
+                    .extend(chunk.invalid().iter().map(|_| PLACEHOLDER));
+            }
+
+            read += line.len();
+            if groups.ends_group(start) {
+                break;
+            }
+        }
+
+        let offset = read - groups.carry.len() - groups.group.len();
+        let mut chunks = Vec::new();
+        let wrapped = groups.wrap_with(|group, chunk| {
+            let range = group.as_bytes().as_ptr_range();
+            let bytes = chunk.as_bytes();
+
+            chunks.push(match range.contains(&bytes.as_ptr()) {
+                true => {
+                    let start = offset + (bytes.as_ptr() as usize - range.start as usize);
+                    Cow::Borrowed(&input[start..start + bytes.len()])
+                }
+                // Not a slice of the input, e.g., a newline.
+                false => Cow::Owned(bytes.to_vec()),
+            });
+        });
+
+        wrapped.then_some(chunks)
+    })
+    .flatten()
+}
+
+/// Wrap text into a [String]. Same as collecting [wrap] into a [String], but
+/// with room for the output reserved up front, i.e., the size of the input and
+/// then some.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{wrap_to_string, Salsa, Toppings};
+///
+/// let output = wrap_to_string::<Salsa>("foo bar baz", Toppings::default().width(7));
+/// assert_eq!(output, "foo bar\nbaz");
+/// ```
+///
+/// Large inputs take fewer reallocations than collecting does:
+///
+/// ```standalone_crate
+/// # use std::alloc::{GlobalAlloc, Layout, System};
+/// # use std::sync::atomic::{AtomicUsize, Ordering};
+/// #
+/// # struct Counting;
+/// # static REALLOCS: AtomicUsize = AtomicUsize::new(0);
+/// #
+/// # unsafe impl GlobalAlloc for Counting {
+/// #     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+/// #         unsafe { System.alloc(layout) }
+/// #     }
+/// #     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+/// #         unsafe { System.dealloc(ptr, layout) }
+/// #     }
+/// #     unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, size: usize) -> *mut u8 {
+/// #         REALLOCS.fetch_add(1, Ordering::Relaxed);
+/// #         unsafe { System.realloc(ptr, layout, size) }
+/// #     }
+/// # }
+/// #
+/// # #[global_allocator]
+/// # static ALLOCATOR: Counting = Counting;
+/// #
+/// # fn reallocs<T>(f: impl FnOnce() -> T) -> (T, usize) {
+/// #     let before = REALLOCS.load(Ordering::Relaxed);
+/// #     let result = f();
+/// #     (result, REALLOCS.load(Ordering::Relaxed) - before)
+/// # }
+/// #
+/// use tortilla::{wrap, wrap_to_string, Salsa, Toppings};
+///
+/// let input = "  // foo bar baz qux\n".repeat(10_000);
+/// let toppings = Toppings::default().width(16);
+///
+/// let (collected, naive) = reallocs(|| wrap::<Salsa>(&input, toppings.clone()).collect::<String>());
+/// let (output, presized) = reallocs(|| wrap_to_string::<Salsa>(&input, toppings));
+///
+/// assert_eq!(output, collected);
+/// assert!(presized < naive);
+/// ```
+pub fn wrap_to_string<S: Sauce>(input: &str, toppings: Toppings) -> String {
+    let mut output = String::with_capacity(input.len().saturating_add(input.len() / 8));
+    output.extend(wrap::<S>(input, toppings));
+    output
+}
+
+/// Wrap text into a [String], along with the total number of characters
+/// (Unicode scalar values) it consists of, including newlines. A CRLF (`\r\n`)
+/// newline counts as two characters.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{wrap_measured, Newline, Salsa, Toppings};
+///
+/// let toppings = Toppings::default().width(7).newline(Newline::CRLF);
+/// let (output, count) = wrap_measured::<Salsa>("foo bar baz", toppings);
+///
+/// assert_eq!(output, "foo bar\r\nbaz");
+/// assert_eq!(count, 12);
+/// ```
+pub fn wrap_measured<S: Sauce>(input: &str, toppings: Toppings) -> (String, usize) {
+    let mut output = String::with_capacity(input.len());
+    let mut count = 0;
+
+    for chunk in wrap::<S>(input, toppings) {
+        output.push_str(chunk);
+        count += chunk.chars().count();
+    }
+
+    (output, count)
+}
+
+/// Whether wrapping text a second time leaves it unchanged, i.e., whether
+/// wrapping `input` is idempotent with the given toppings.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{is_stable, Salsa, Toppings};
+///
+/// let toppings = Toppings::default().width(12);
+///
+/// assert!(is_stable::<Salsa>("// foo bar baz qux\n\n- quux corge grault\n", &toppings));
+/// assert!(is_stable::<Salsa>("foo\r\nbar\n", &toppings));
+///
+/// let input = "- foo bar\n  baz\n\n\t// qux quux corge\n\n1. grault garply\n";
+/// assert!((1..30).all(|width| is_stable::<Salsa>(input, &toppings.clone().width(width))));
+/// ```
+pub fn is_stable<S: Sauce>(input: &str, toppings: &Toppings) -> bool {
+    let once = wrap::<S>(input, toppings.clone()).collect::<String>();
+
+    // Compare as we go, to stop at the first difference.
+    let mut rest = once.as_str();
+    let twice = wrap::<S>(&once, toppings.clone()).all(|chunk| match rest.strip_prefix(chunk) {
+        Some(tail) => {
+            rest = tail;
+            true
+        }
+        None => false,
+    });
+
+    twice && rest.is_empty()
+}
+
+/// Whether wrapping `input` would change it, e.g., to check that a file is
+/// already formatted. Output is compared against the input as it is wrapped,
+/// without collecting it, see [first_changed_line].
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{needs_wrap, Salsa, Toppings};
+///
+/// let toppings = Toppings::default().width(7);
+///
+/// assert!(!needs_wrap::<Salsa>("foo bar\nbaz\n", &toppings));
+/// assert!(needs_wrap::<Salsa>("foo bar baz\n", &toppings));
+/// assert!(needs_wrap::<Salsa>("foo\nbar baz\n", &toppings));
+/// ```
+pub fn needs_wrap<S: Sauce>(input: &str, toppings: &Toppings) -> bool {
+    first_changed_line::<S>(input, toppings).is_some()
+}
+
+/// The number of the first line (counting from 1) of `input` that wrapping
+/// would change, or [None] if wrapping leaves it as it is. Stops wrapping at
+/// the first difference.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{first_changed_line, Salsa, Toppings};
+///
+/// let toppings = Toppings::default().width(7);
+///
+/// assert_eq!(first_changed_line::<Salsa>("foo bar\nbaz\n", &toppings), None);
+/// assert_eq!(first_changed_line::<Salsa>("foo\n\nbar baz qux\n", &toppings), Some(3));
+///
+/// // Missing output, such as a dropped trailing space, counts too.
+/// assert_eq!(first_changed_line::<Salsa>("foo bar\nbaz \n", &toppings), Some(2));
+/// ```
+pub fn first_changed_line<S: Sauce>(input: &str, toppings: &Toppings) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let mut offset = 0;
+    let mut diverged = false;
+
+    for chunk in wrap::<S>(input, toppings.clone()) {
+        let common = bytes[offset..]
+            .iter()
+            .zip(chunk.as_bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        offset += common;
+        if common < chunk.len() {
+            diverged = true;
+            break;
+        }
+    }
+
+    // Output either differs from the input, or it ends before the input does.
+    (diverged || offset < bytes.len()).then(|| {
+        bytes[..offset]
+            .iter()
+            .filter(|&&byte| byte == b'\n')
+            .count()
+            + 1
+    })
+}
+
+/// Whether wrapped text consists of at most `budget` characters in total,
+/// including newlines, see [wrap_measured]. Stops wrapping as soon as the
+/// budget is exceeded.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{fits_within, Newline, Salsa, Toppings};
+///
+/// let toppings = Toppings::default().width(7);
+/// assert!(fits_within::<Salsa>("foo bar baz", toppings.clone(), 11));
+///
+/// let toppings = toppings.newline(Newline::CRLF);
+/// assert!(!fits_within::<Salsa>("foo bar baz", toppings, 11));
+/// ```
+pub fn fits_within<S: Sauce>(input: &str, toppings: Toppings, budget: usize) -> bool {
+    let mut count = 0;
+
+    wrap::<S>(input, toppings).all(|chunk| {
+        count += chunk.chars().count();
+        count <= budget
+    })
+}
+
+/// Wrap text into a [String] of at most `max_lines` lines, e.g., for a preview
+/// of a document. If there is more, the output is cut off after the newline
+/// that ends the last line, and `more` is called with the number of lines left
+/// out to produce a marker that is appended to it.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{wrap_capped, Salsa, Toppings};
+///
+/// let input = "foo bar baz\n\nqux quux\n\ncorge grault garply\n";
+///
+/// let toppings = Toppings::default().width(8);
+/// let more = |n| format!("… ({n} more lines)\n");
+///
+/// let output = wrap_capped::<Salsa>(input, toppings.clone(), 5, more);
+/// assert_eq!(output, "foo bar\nbaz\n\nqux quux\n\n… (3 more lines)\n");
+///
+/// // Nothing is left out, so there is no marker.
+/// let output = wrap_capped::<Salsa>(input, toppings, 8, more);
+/// assert_eq!(output, "foo bar\nbaz\n\nqux quux\n\ncorge\ngrault\ngarply\n");
+/// ```
+pub fn wrap_capped<S: Sauce>(
+    input: &str,
+    toppings: Toppings,
+    max_lines: usize,
+    more: impl FnOnce(usize) -> String,
+) -> String {
+    let toppings = toppings.detected(input);
+    let newline = toppings.newline.as_str();
+    let mut chunks = wrap::<S>(input, toppings);
+
+    let mut output = String::with_capacity(input.len());
+    let mut lines = 0;
+
+    while lines < max_lines {
+        let Some(chunk) = chunks.next() else {
+            return output;
+        };
+
+        output.push_str(chunk);
+        lines += usize::from(chunk == newline);
+    }
+
+    // Count the lines left out, including a last one without a newline.
+    let mut remaining = 0;
+    let mut open = false;
+
+    for chunk in chunks {
+        match chunk == newline {
+            true => {
+                remaining += 1;
+                open = false;
+            }
+            false => open |= !chunk.is_empty(),
+        }
+    }
+
+    remaining += usize::from(open);
+
+    if remaining > 0 {
+        output.push_str(&more(remaining));
+    }
+
+    output
+}
+
+/// Replacement of a byte range of input, see [format_edits].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    /// Byte range of input to replace.
+    pub range: Range<usize>,
+    /// Text to replace the range with.
+    pub new_text: String,
+}
+
+/// Wrap text and return the edits that turn input into wrapped output, e.g.,
+/// for an editor to apply on save without replacing the whole document.
+///
+/// Edits are computed by a line-level diff, so lines that are unchanged by
+/// wrapping are not touched by any edit. Edits are ordered by their position
+/// in the input and do not overlap.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{format_edits, Salsa, TextEdit, Toppings};
+///
+/// let input = "foo bar baz\n\nqux\nquux\n\ncorge grault\n";
+/// let edits = format_edits::<Salsa>(input, Toppings::default().width(12));
+///
+/// assert_eq!(
+///     edits,
+///     vec![TextEdit {
+///         range: 13..22,
+///         new_text: "qux quux\n".to_owned()
+///     }]
+/// );
+///
+/// // Applying edits back to front keeps the ranges of earlier ones valid.
+/// let mut output = input.to_owned();
+/// for edit in edits.iter().rev() {
+///     output.replace_range(edit.range.clone(), &edit.new_text);
+/// }
+/// assert_eq!(output, "foo bar baz\n\nqux quux\n\ncorge grault\n");
+///
+/// // Wrapped text needs no further edits.
+/// assert!(format_edits::<Salsa>(&output, Toppings::default().width(12)).is_empty());
+/// ```
+///
+/// Lines are diffed one paragraph at a time, see [diff], and edits far apart
+/// from each other in large inputs are found quickly and with little memory:
+///
+/// ```
+/// use tortilla::{format_edits, Salsa, TextEdit, Toppings};
+///
+/// let input = format!("foo bar baz\n\n{}qux\nquux\n", "lorem\n\n".repeat(20_000));
+/// let edits = format_edits::<Salsa>(&input, Toppings::default().width(8));
+///
+/// assert_eq!(
+///     edits,
+///     vec![
+///         TextEdit { range: 0..12, new_text: "foo bar\nbaz\n".to_owned() },
+///         TextEdit { range: 140_013..140_022, new_text: "qux quux\n".to_owned() },
+///     ]
+/// );
+/// ```
+pub fn format_edits<S: Sauce>(input: &str, toppings: Toppings) -> Vec<TextEdit> {
+    let (output, changes) = paragraph_diff::<S>(input, toppings);
+
+    let old = input.split_inclusive('\n').collect::<Vec<_>>();
+    let new = output.split_inclusive('\n').collect::<Vec<_>>();
+
+    let mut edits = Vec::new();
+    let mut pending: Option<TextEdit> = None;
+    let mut offset = 0;
+    let (mut i, mut j) = (0, 0);
+
+    for change in changes {
+        if change == LineChange::Keep {
+            edits.extend(pending.take());
+            offset += old[i].len();
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        let edit = pending.get_or_insert_with(|| TextEdit {
+            range: offset..offset,
+            new_text: String::new(),
+        });
+
+        if change == LineChange::Insert {
+            edit.new_text.push_str(new[j]);
+            j += 1;
+        } else {
+            offset += old[i].len();
+            edit.range.end = offset;
+            i += 1;
+        }
+    }
+
+    edits.extend(pending);
+    edits
+}
+
+/// Lines of context around changes in [diff].
+const DIFF_CONTEXT: usize = 3;
+
+/// Wrap text and return a unified diff from input to wrapped output, e.g., to
+/// show what wrapping would change, or an empty [String] if it wouldn't change
+/// anything.
+///
+/// Only hunks are included, with three lines of context each, but no file
+/// headers (`---` and `+++`). Like for [format_edits], lines are diffed as a
+/// whole, but one paragraph at a time: the lines of each paragraph of input
+/// are only compared to the lines it was wrapped into, such that large inputs
+/// are diffed quickly even if all of their paragraphs change.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{diff, Salsa, Toppings};
+///
+/// let input = "foo bar baz\n\nqux\nquux\n";
+///
+/// assert_eq!(
+///     diff::<Salsa>(input, Toppings::default().width(8)),
+///     "@@ -1,4 +1,4 @@\n-foo bar baz\n+foo bar\n+baz\n \n-qux\n-quux\n+qux quux\n"
+/// );
+///
+/// assert_eq!(diff::<Salsa>("foo bar\nbaz\n", Toppings::default().width(8)), "");
+/// ```
+///
+/// Diffing a large input where every paragraph changes:
+///
+/// ```
+/// use tortilla::{diff, Salsa, Toppings};
+///
+/// let input = "foo bar\n\n".repeat(20_000);
+/// let hunks = diff::<Salsa>(&input, Toppings::default().width(4));
+///
+/// // All changes are close enough to share one hunk.
+/// assert!(hunks.starts_with("@@ -1,40000 +1,60000 @@\n-foo bar\n+foo\n+bar\n \n-foo bar\n"));
+/// assert_eq!(hunks.lines().count(), 1 + 40_000 + 40_000);
+/// ```
+pub fn diff<S: Sauce>(input: &str, toppings: Toppings) -> String {
+    let (output, changes) = paragraph_diff::<S>(input, toppings);
+
+    let old = input.split_inclusive('\n').collect::<Vec<_>>();
+    let new = output.split_inclusive('\n').collect::<Vec<_>>();
+
+    // Line numbers in old and new text before each change.
+    let mut positions = Vec::with_capacity(changes.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for change in &changes {
+        positions.push((i, j));
+        i += usize::from(*change != LineChange::Insert);
+        j += usize::from(*change != LineChange::Delete);
+    }
+    positions.push((i, j));
+
+    let mut hunks = String::new();
+    let mut idx = 0;
+
+    while let Some(first) = changes[idx..].iter().position(|c| *c != LineChange::Keep) {
+        let first = idx + first;
+
+        // Changes that are close enough to share context go into one hunk.
+        let mut last = first;
+        while let Some(next) = changes[last + 1..]
+            .iter()
+            .take(2 * DIFF_CONTEXT + 1)
+            .position(|c| *c != LineChange::Keep)
+        {
+            last += next + 1;
+        }
+
+        let start = first.saturating_sub(DIFF_CONTEXT);
+        let end = changes.len().min(last + 1 + DIFF_CONTEXT);
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+
+        // An empty range starts after the line it follows.
+        let line = |start, end| start + usize::from(end > start);
+        hunks.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            line(old_start, old_end),
+            old_end - old_start,
+            line(new_start, new_end),
+            new_end - new_start,
+        ));
+
+        for (change, &(i, j)) in changes[start..end].iter().zip(&positions[start..end]) {
+            let (sign, text) = match change {
+                LineChange::Keep => (' ', old[i]),
+                LineChange::Delete => ('-', old[i]),
+                LineChange::Insert => ('+', new[j]),
+            };
+            hunks.push(sign);
+            hunks.push_str(text);
+            if !text.ends_with('\n') {
+                hunks.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+
+        idx = end;
+    }
+
+    hunks
+}
+
+/// Wrap text and diff the lines of input and output one paragraph at a time,
+/// see [line_diff], such that each diff is only as large as a paragraph.
+/// Returns the output along with the changes for all of its lines.
+fn paragraph_diff<S: Sauce>(input: &str, toppings: Toppings) -> (String, Vec<LineChange>) {
+    let (lines, toppings) = merged(input, toppings, paragraph_boundary);
+    let mut output = String::with_capacity(input.len());
+
+    // Byte offsets into input and output where a paragraph's lines start.
+    let mut cuts = vec![(0, 0)];
+
+    for line in lines {
+        // Paragraphs that aren't blank tell where their lines of input start.
+        let first = [
+            line.gutter,
+            line.raw_indent,
+            line.comment,
+            line.bullet,
+            line.verbatim,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(line.words.first().copied())
+        .map(str::as_ptr)
+        .filter(|ptr| input.as_bytes().as_ptr_range().contains(ptr))
+        .min();
+
+        if let Some(ptr) = first {
+            let offset = ptr as usize - input.as_ptr() as usize;
+            let input_start = input[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+            let (last_input, last_output) = cuts[cuts.len() - 1];
+
+            if input_start > last_input && output.len() > last_output && output.ends_with('\n') {
+                cuts.push((input_start, output.len()));
+            }
+        }
+
+        output.extend(LineWrap::new::<S>(line, &toppings));
+    }
+
+    output.extend(final_newline(
+        &toppings,
+        !output.is_empty(),
+        output.ends_with('\n'),
+    ));
+    cuts.push((input.len(), output.len()));
+
+    let mut changes = Vec::new();
+    for pair in cuts.windows(2) {
+        let [(input_start, output_start), (input_end, output_end)] = pair else {
+            unreachable!();
+        };
+        let old = input[*input_start..*input_end]
+            .split_inclusive('\n')
+            .collect::<Vec<_>>();
+        let new = output[*output_start..*output_end]
+            .split_inclusive('\n')
+            .collect::<Vec<_>>();
+        changes.extend(line_diff(&old, &new));
+    }
+
+    (output, changes)
+}
+
+/// How a line changes from old to new text, see [line_diff].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum LineChange {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// The changes that turn `old` lines into `new` lines, such that as many lines
+/// as possible are kept (the longest common subsequence).
+///
+/// Uses Myers' algorithm, which takes time proportional to the number of lines
+/// times the number of changes, and space linear in the number of lines.
+///
+/// Also see: <https://doi.org/10.1007/BF01840446>
+fn line_diff(old: &[&str], new: &[&str]) -> Vec<LineChange> {
+    let mut changes = Vec::with_capacity(old.len().max(new.len()));
+    bisect_lines(old, new, &mut changes);
+
+    // Deletions go before insertions within each run of changes.
+    for run in changes.split_mut(|change| *change == LineChange::Keep) {
+        run.sort_unstable_by_key(|change| *change == LineChange::Insert);
+    }
+
+    changes
+}
+
+/// Push the changes that turn `old` lines into `new` lines, by splitting both
+/// at a point that a shortest edit script passes through and recursing on
+/// either half, see [line_diff].
+fn bisect_lines(old: &[&str], new: &[&str], changes: &mut Vec<LineChange>) {
+    // Lines that are the same at the start and end need no diffing.
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_lines = &old[prefix..old.len() - suffix];
+    let new_lines = &new[prefix..new.len() - suffix];
+
+    changes.extend(std::iter::repeat_n(LineChange::Keep, prefix));
+
+    if old_lines.is_empty() || new_lines.is_empty() {
+        changes.extend(std::iter::repeat_n(LineChange::Delete, old_lines.len()));
+        changes.extend(std::iter::repeat_n(LineChange::Insert, new_lines.len()));
+    } else {
+        let (x, y) = middle(old_lines, new_lines);
+        bisect_lines(&old_lines[..x], &new_lines[..y], changes);
+        bisect_lines(&old_lines[x..], &new_lines[y..], changes);
+    }
+
+    changes.extend(std::iter::repeat_n(LineChange::Keep, suffix));
+}
+
+/// A point halfway along a shortest edit script from `old` to `new` lines, as
+/// indices into either, found by searching from both ends at once. Neither
+/// side may be empty, nor may they start or end with the same line.
+fn middle(old: &[&str], new: &[&str]) -> (usize, usize) {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m + 1) / 2;
+    let delta = n - m;
+    let front = delta % 2 != 0;
+
+    // Furthest position in `old` reached on each diagonal `k` (where `x - y`
+    // equals `k`), offset by `max`, from the start and from the end.
+    let mut forward = vec![-1; 2 * max as usize + 2];
+    let mut backward = forward.clone();
+    forward[max as usize + 1] = 0;
+    backward[max as usize + 1] = 0;
+
+    // Diagonals that have left the grid on either side are skipped.
+    let (mut forward_start, mut forward_end) = (0, 0);
+    let (mut backward_start, mut backward_end) = (0, 0);
+
+    let furthest = |v: &[isize], d: isize, k: isize| {
+        let idx = (max + k) as usize;
+        match k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+            true => v[idx + 1],
+            false => v[idx - 1] + 1,
+        }
+    };
+
+    for d in 0..max {
+        for k in (-d + forward_start..=d - forward_end).step_by(2) {
+            let mut x = furthest(&forward, d, k);
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[(max + k) as usize] = x;
+
+            if x > n {
+                forward_end += 2;
+            } else if y > m {
+                forward_start += 2;
+            } else if front {
+                let other = max + delta - k;
+                if (0..forward.len() as isize).contains(&other)
+                    && backward[other as usize] != -1
+                    && x >= n - backward[other as usize]
+                {
+                    return (x as usize, y as usize);
+                }
+            }
+        }
+
+        for k in (-d + backward_start..=d - backward_end).step_by(2) {
+            let mut x = furthest(&backward, d, k);
+            let mut y = x - k;
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[(max + k) as usize] = x;
+
+            if x > n {
+                backward_end += 2;
+            } else if y > m {
+                backward_start += 2;
+            } else if !front {
+                let other = max + delta - k;
+                if (0..forward.len() as isize).contains(&other) && forward[other as usize] != -1 {
+                    let forward_x = forward[other as usize];
+                    if forward_x >= n - x {
+                        return (forward_x as usize, (forward_x - (delta - k)) as usize);
+                    }
+                }
+            }
+        }
+    }
+
+    // Nothing in common, which the search above always finds first.
+    (old.len(), 0)
+}
+
+/// A paragraph of input, see [paragraphs].
+#[derive(Debug, Clone)]
+pub struct Paragraph<'t> {
+    line: Line<'t>,
+    merged: bool,
+    cjk: bool,
+    tabs: Tabs,
+}
+
+impl<'t> Paragraph<'t> {
+    /// A view of the paragraph as if it were a single line, with the words of
+    /// all lines it consists of.
+    pub fn info(&self) -> LineInfo<'_> {
+        LineInfo(&self.line, self.cjk, &self.tabs)
+    }
+
+    /// Whether the paragraph was joined from several lines of input, rather
+    /// than consisting of a single line.
+    pub fn was_merged(&self) -> bool {
+        self.merged
+    }
+}
+
+/// Split text into paragraphs the way [wrap] does, without wrapping them.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{paragraphs, Toppings};
+///
+/// let input = "foo\nbar\n\nbaz\n";
+/// let merged = paragraphs(input, Toppings::default())
+///     .map(|paragraph| paragraph.was_merged())
+///     .collect::<Vec<_>>();
+///
+/// // The empty line is a paragraph of its own.
+/// assert_eq!(merged, vec![true, false, false]);
+/// ```
+pub fn paragraphs(input: &str, toppings: Toppings) -> impl Iterator<Item = Paragraph<'_>> {
+    let (mut lines, toppings) = merged(input, toppings, paragraph_boundary);
+
+    std::iter::from_fn(move || {
+        let line = lines.next()?;
+        Some(Paragraph {
+            line,
+            merged: lines.was_merged(),
+            cjk: toppings.cjk,
+            tabs: toppings.tab_widths(),
+        })
+    })
+}
+
+/// A line of input as parsed by [wrap], see [parse_lines].
+#[derive(Debug, Clone)]
+pub struct ParsedLine<'t> {
+    line: Line<'t>,
+    tabs: Tabs,
+}
+
+impl<'t> ParsedLine<'t> {
+    /// Width of the leading indentation, counting each tab character (`\t`)
+    /// as [Toppings::tabs] columns, unless [Toppings::tab_width_by_level] is
+    /// set.
+    pub fn indent_width(&self) -> usize {
+        self.tabs.indent_width(self.line.indent)
+    }
+
+    /// The comment token, if any (e.g., `//` or `#`).
+    pub fn comment(&self) -> Option<&'t str> {
+        self.line.comment
+    }
+
+    /// The bullet token, if any (e.g., `-` or `1.`).
+    pub fn bullet(&self) -> Option<&'t str> {
+        self.line.bullet
+    }
+
+    /// The words following the indentation, comment token and bullet token.
+    pub fn words(&self) -> &[&'t str] {
+        &self.line.words
+    }
+
+    /// Whether the line is kept as is rather than wrapped, e.g., because it is
+    /// part of a code block.
+    pub fn is_verbatim(&self) -> bool {
+        self.line.verbatim.is_some()
+    }
+}
+
+/// Split text into lines and parse their structure the way [wrap] does, without
+/// merging them into paragraphs (see [paragraphs]) or wrapping them.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{parse_lines, Toppings};
+///
+/// let input = "// - foo\n//   bar\n\tbaz\n";
+/// let lines = parse_lines(input, &Toppings::default()).collect::<Vec<_>>();
+///
+/// assert_eq!(lines.len(), 3);
+///
+/// assert_eq!(lines[0].comment(), Some("//"));
+/// assert_eq!(lines[0].bullet(), Some("-"));
+/// assert_eq!(lines[0].words(), ["foo"]);
+///
+/// assert_eq!(lines[1].bullet(), None);
+/// assert_eq!(lines[2].indent_width(), 4);
+/// ```
+pub fn parse_lines<'t>(
+    input: &'t str,
+    toppings: &Toppings,
+) -> impl Iterator<Item = ParsedLine<'t>> + use<'t> {
+    let tabs = toppings.tab_widths();
+    Parse::new(input, Lex::new(input), toppings).map(move |line| ParsedLine {
+        line,
+        tabs: tabs.clone(),
+    })
+}
+
+/// How a single line of input is parsed, see [classify_line].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineClass {
+    /// No words and no bullet token, but possibly indentation and a comment
+    /// token.
+    Blank,
+    /// Words without a comment token or bullet token.
+    Prose,
+    /// Words following a comment token.
+    Comment,
+    /// Words following a markdown heading marker (`#` up to `######`), if
+    /// [Toppings::markdown] is enabled.
+    Heading,
+    /// Words following a bullet token.
+    Bullet,
+    /// Words following a comment token and a bullet token.
+    CommentBullet,
+    /// Kept as is rather than wrapped, e.g., a horizontal rule (see
+    /// [Toppings::horizontal_rules]).
+    Verbatim,
+}
+
+/// Classify a single line of input the way [wrap] parses it. Only the first
+/// line of `line` is looked at.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{classify_line, LineClass, Toppings};
+///
+/// let classify = |line| classify_line(line, &Toppings::default());
+///
+/// assert_eq!(classify(""), LineClass::Blank);
+/// assert_eq!(classify("  #"), LineClass::Blank);
+/// assert_eq!(classify("  code"), LineClass::Prose);
+/// assert_eq!(classify("# heading"), LineClass::Comment);
+/// assert_eq!(classify("- item"), LineClass::Bullet);
+/// assert_eq!(classify("-"), LineClass::Bullet);
+/// assert_eq!(classify("// 1. item"), LineClass::CommentBullet);
+/// assert_eq!(classify("---"), LineClass::Verbatim);
+///
+/// let toppings = Toppings::default().markdown(true);
+/// assert_eq!(classify_line("# heading", &toppings), LineClass::Heading);
+/// assert_eq!(classify_line("### heading", &toppings), LineClass::Heading);
+/// ```
+pub fn classify_line(line: &str, toppings: &Toppings) -> LineClass {
+    match Parse::new(line, Lex::new(line), toppings).next() {
+        Some(line) => line.class(),
+        None => LineClass::Blank,
+    }
+}
+
+impl Line<'_> {
+    /// How the line is classified, see [classify_line].
+    fn class(&self) -> LineClass {
+        if self.verbatim.is_some() {
+            return LineClass::Verbatim;
+        }
+
+        match (self.comment, self.bullet) {
+            (Some(_), Some(_)) => LineClass::CommentBullet,
+            (None, Some(_)) => LineClass::Bullet,
+            _ if self.words.is_empty() => LineClass::Blank,
+            _ if self.heading => LineClass::Heading,
+            (Some(_), None) => LineClass::Comment,
+            (None, None) => LineClass::Prose,
+        }
+    }
+}
+
+/// Where a word of the input ended up in wrapped output, see [layout].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordPlacement {
+    /// Byte range of the word in the input.
+    pub source_range: Range<usize>,
+    /// Index of the output line containing the word (starting at 0).
+    pub line: usize,
+    /// Column the word starts at (starting at 0).
+    pub start_col: usize,
+    /// Column right after the word.
+    pub end_col: usize,
+}
+
+/// Wrap text, reporting where each word ends up rather than the output itself,
+/// e.g., to map positions in rendered output back to the input.
+///
+/// Columns count each tab character (`\t`) as [Toppings::tabs]. Words that are
+/// broken apart (see [Toppings::break_long_words] and
+/// [Toppings::intra_word_penalty]) are reported once for each line they span.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{layout, Salsa, Toppings, WordPlacement};
+///
+/// let placements = layout::<Salsa>("- foo bar baz", Toppings::default().width(9));
+///
+/// assert_eq!(
+///     placements,
+///     vec![
+///         WordPlacement { source_range: 2..5, line: 0, start_col: 2, end_col: 5 },
+///         WordPlacement { source_range: 6..9, line: 0, start_col: 6, end_col: 9 },
+///         WordPlacement { source_range: 10..13, line: 1, start_col: 2, end_col: 5 },
+///     ]
+/// );
+/// ```
+///
+/// Columns include [Toppings::output_indent], also for lines that are emitted
+/// as they are, such as horizontal rules:
+///
+/// ```
+/// use tortilla::{layout, wrap, Salsa, Toppings, WordPlacement};
+///
+/// let input = "foo bar\n---\nbaz\n";
+/// let toppings = Toppings::default().output_indent(4);
+///
+/// let output = wrap::<Salsa>(input, toppings.clone()).collect::<String>();
+/// assert_eq!(output, "    foo bar\n    ---\n    baz\n");
+///
+/// assert_eq!(
+///     layout::<Salsa>(input, toppings),
+///     vec![
+///         WordPlacement { source_range: 0..3, line: 0, start_col: 4, end_col: 7 },
+///         WordPlacement { source_range: 4..7, line: 0, start_col: 8, end_col: 11 },
+///         WordPlacement { source_range: 8..11, line: 1, start_col: 4, end_col: 7 },
+///         WordPlacement { source_range: 12..15, line: 2, start_col: 4, end_col: 7 },
+///     ]
+/// );
+/// ```
+pub fn layout<S: Sauce>(input: &str, toppings: Toppings) -> Vec<WordPlacement> {
+    let (lines, toppings) = merged(input, toppings, paragraph_boundary);
+    let source_range = |text: &str| {
+        let start = text.as_ptr() as usize - input.as_ptr() as usize;
+        start..start + text.len()
+    };
+
+    let mut placements = Vec::new();
+    let (mut line_idx, mut col) = (0, 0);
+
+    for line in lines {
+        // Verbatim lines are emitted in one piece, see [Toppings::overflow_only],
+        // after output indentation (see [Toppings::output_indent]).
+        if let Some(raw) = line.verbatim {
+            let raw_start = source_range(raw).start;
+
+            for word in &line.words {
+                let range = source_range(word);
+                let start_col = col
+                    + toppings.output_indent
+                    + parse::text_width(
+                        &raw[..range.start - raw_start],
+                        toppings.tabs,
+                        toppings.cjk,
+                    );
+                let end_col = start_col + parse::text_width(word, toppings.tabs, toppings.cjk);
+
+                placements.push(WordPlacement {
+                    source_range: range,
+                    line: line_idx,
+                    start_col,
+                    end_col,
+                });
+            }
+        }
+
+        let mut inner = LineWrap::new::<S>(line, &toppings);
+
+        while let Some(chunk) = inner.next() {
+            if chunk == toppings.newline.as_str() {
+                line_idx += 1;
+                col = 0;
+                continue;
+            }
+
+            let width = parse::text_width(chunk, toppings.tabs, toppings.cjk);
+
+            if inner.emitted_word() {
+                let range = source_range(chunk);
+
+                // Pieces of a word that share a line are reported as one.
+                match placements.last_mut() {
+                    Some(last)
+                        if last.line == line_idx
+                            && last.end_col == col
+                            && last.source_range.end == range.start =>
+                    {
+                        last.source_range.end = range.end;
+                        last.end_col = col + width;
+                    }
+                    _ => placements.push(WordPlacement {
+                        source_range: range,
+                        line: line_idx,
+                        start_col: col,
+                        end_col: col + width,
+                    }),
+                }
+            }
+
+            col += width;
+        }
+    }
+
+    placements
+}
+
+/// The default paragraph boundary predicate type, see [paragraph_boundary].
+pub type Boundary = fn(&LineInfo<'_>, &LineInfo<'_>) -> bool;
+
+/// Statistics about wrapped output, e.g., to tune [Toppings::width], see
+/// [Wrap::stats].
+///
+/// Widths count tabs as [Toppings::tabs] columns.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of lines, including blank ones.
+    pub lines: usize,
+    /// Width of the widest line.
+    pub max_width: usize,
+    /// Sum of the squares of the number of columns each line falls short of
+    /// [Toppings::width] by, except for the last line of each paragraph. With
+    /// default toppings, this is the raggedness that [Salsa] minimizes.
+    pub slack: usize,
+    /// Number of lines that exceed [Toppings::width].
+    pub overfull: usize,
+}
+
+impl Stats {
+    fn add(&mut self, other: Stats) {
+        self.lines += other.lines;
+        self.max_width = self.max_width.max(other.max_width);
+        self.slack = self.slack.saturating_add(other.slack);
+        self.overfull += other.overfull;
+    }
+}
+
+/// Lex, parse and merge `input` into paragraphs, the way every entry point
+/// does. Returns them along with `toppings` as completed from `input`, see
+/// [Toppings::detected], which they are to be wrapped with.
+fn merged<B>(
+    input: &str,
+    toppings: Toppings,
+    boundary: B,
+) -> (Merge<Parse<'_, Lex<'_>>, B>, Toppings) {
+    let toppings = toppings.detected(input);
+    let lines = Merge::new(
+        Parse::new(input, Lex::new(input), &toppings),
+        boundary,
+        &toppings,
+    );
+    (lines, toppings)
+}
+
+/// Where a [Wrap] gets its paragraphs from.
+enum Paragraphs<'t, B> {
+    /// Parsed and merged from input as it is wrapped.
+    Input(Box<Merge<Parse<'t, Lex<'t>>, B>>),
+    /// Parsed and merged ahead of time, see [Document].
+    Document(document::Lines<'t>),
+}
+
+impl<'t, B> Iterator for Paragraphs<'t, B>
+where
+    B: FnMut(&LineInfo<'_>, &LineInfo<'_>) -> bool,
+{
+    type Item = Line<'t>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Paragraphs::Input(lines) => lines.next(),
+            Paragraphs::Document(lines) => lines.next(),
+        }
+    }
+}
+
+/// An [Iterator] over chunks of wrapped output.
+pub struct Wrap<'t, S, B = Boundary> {
+    toppings: Toppings,
+    lines: Paragraphs<'t, B>,
+    current: Option<LineWrap<'t>>,
+    /// Whether any non-empty chunk has been emitted yet.
+    emitted: bool,
+    /// Whether the last non-empty chunk ended with a newline.
+    terminated: bool,
+    stats: Stats,
+    /// Width of the current line, if any chunk of it has been emitted yet.
+    line_width: Option<usize>,
+    /// Slack of the most recent line, counted once it turns out not to be
+    /// the last line of its paragraph, see [Stats::slack].
+    slack: Option<usize>,
+    sauce: PhantomData<S>,
+}
+
+impl<'t, S, B> Iterator for Wrap<'t, S, B>
+where
+    S: Sauce,
+    B: FnMut(&LineInfo<'_>, &LineInfo<'_>) -> bool,
+{
+    type Item = &'t str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let inner = match &mut self.current {
+                Some(inner) => inner,
+                None => match self.lines.next() {
+                    Some(line) => self
+                        .current
+                        .insert(LineWrap::new::<S>(line, &self.toppings)),
+                    None => {
+                        let chunk = self.final_newline();
+                        match chunk {
+                            Some(chunk) => {
+                                self.tally(chunk);
+                                self.slack = None;
+                            }
+                            None => self.end_line(),
+                        }
+                        return chunk;
+                    }
+                },
+            };
+
+            match inner.next() {
+                Some(chunk) => {
+                    if !chunk.is_empty() {
+                        self.emitted = true;
+                        self.terminated = chunk.ends_with('\n');
+                    }
+                    self.tally(chunk);
+                    return Some(chunk);
+                }
+                None => {
+                    self.current = None;
+
+                    // The most recent line was the last one of its paragraph,
+                    // unless the paragraph ends without a newline.
+                    if self.line_width.is_none() {
+                        self.slack = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'t, S, B> Wrap<'t, S, B> {
+    fn new(lines: Paragraphs<'t, B>, toppings: Toppings) -> Self {
+        Self {
+            lines,
+            toppings,
+            current: None,
+            emitted: false,
+            terminated: false,
+            stats: Stats::default(),
+            line_width: None,
+            slack: None,
+            sauce: PhantomData,
+        }
+    }
+
+    /// Statistics about the output so far, i.e., all of it once the iterator
+    /// is exhausted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tortilla::{wrap, Salsa, Stats, Toppings};
+    ///
+    /// let mut output = wrap::<Salsa>("foo bar baz qux\n", Toppings::default().width(9));
+    /// assert_eq!(output.by_ref().collect::<String>(), "foo bar\nbaz qux\n");
+    ///
+    /// let stats = Stats {
+    ///     lines: 2,
+    ///     max_width: 7,
+    ///     slack: 4,
+    ///     overfull: 0,
+    /// };
+    /// assert_eq!(output.stats(), stats);
+    /// ```
+    pub fn stats(&self) -> Stats {
+        let mut stats = self.stats;
+        if let Some(width) = self.line_width {
+            stats.add(self.line_stats(width));
+        }
+        stats
+    }
+
+    /// Keep track of the width of the current line, see [Wrap::stats].
+    fn tally(&mut self, chunk: &str) {
+        if chunk.ends_with('\n') {
+            let width = self.line_width.take().unwrap_or(0);
+            self.stats.add(self.line_stats(width));
+
+            let slack = self.toppings.width.saturating_sub(width).saturating_pow(2);
+            if let Some(slack) = self.slack.replace(slack) {
+                self.stats.slack = self.stats.slack.saturating_add(slack);
+            }
+        } else if !chunk.is_empty() {
+            let width = parse::text_width(chunk, self.toppings.tabs, self.toppings.cjk);
+            let line_width = self.line_width.get_or_insert(0);
+            *line_width = line_width.saturating_add(width);
+        }
+    }
+
+    /// Count the last line of output, if it doesn't end with a newline.
+    fn end_line(&mut self) {
+        if let Some(width) = self.line_width.take() {
+            self.stats.add(self.line_stats(width));
+        }
+        if let Some(slack) = self.slack.take() {
+            self.stats.slack = self.stats.slack.saturating_add(slack);
+        }
+    }
+
+    /// Statistics about a single line of the given width, not counting its
+    /// slack.
+    fn line_stats(&self, width: usize) -> Stats {
+        Stats {
+            lines: 1,
+            max_width: width,
+            slack: 0,
+            overfull: usize::from(width > self.toppings.width),
+        }
+    }
+
+    /// The newline appended once all lines have been wrapped, if any, see
+    /// [Toppings::final_newline].
+    fn final_newline(&mut self) -> Option<&'static str> {
+        let newline = final_newline(&self.toppings, self.emitted, self.terminated);
+        self.terminated |= newline.is_some();
+        newline
+    }
+}
+
+/// The newline to append to output, if any, see [Toppings::final_newline].
+/// `emitted` is whether output is non-empty, and `terminated` whether it ends
+/// with a newline already.
+fn final_newline(toppings: &Toppings, emitted: bool, terminated: bool) -> Option<&'static str> {
+    match toppings.final_newline {
+        FinalNewline::Preserve => None,
+        FinalNewline::EnsureNonEmpty if !emitted || terminated => None,
+        FinalNewline::EnsureNonEmpty => Some(toppings.newline.as_str()),
+    }
 }
 
 /// Utility macro to construct a [Token].
@@ -261,9 +4344,10 @@ macro_rules! line {
         $(, $($word:expr),*)?
     ) => {
         $crate::Line {
-            indent: $indent, comment: $comment,
-            padding: $padding, bullet: $bullet,
-            words: vec![$($($word),*)?], newline: false,
+            gutter: None, indent: $indent, raw_indent: None, comment: $comment,
+            padding: $padding, bullet: $bullet, hanging: None,
+            words: vec![$($($word),*)?], gaps: vec![], verbatim: None, trailing_space: None,
+            heading: false, continuation: None, newline: false,
         }
     };
 
@@ -273,9 +4357,10 @@ macro_rules! line {
         $(, $($word:expr),*)? ;
     ) => {
         $crate::Line {
-            indent: $indent, comment: $comment,
-            padding: $padding, bullet: $bullet,
-            words: vec![$($($word),*)?], newline: true,
+            gutter: None, indent: $indent, raw_indent: None, comment: $comment,
+            padding: $padding, bullet: $bullet, hanging: None,
+            words: vec![$($($word),*)?], gaps: vec![], verbatim: None, trailing_space: None,
+            heading: false, continuation: None, newline: true,
         }
     };
 }
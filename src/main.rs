@@ -1,18 +1,48 @@
 use std::io::{self, Read, Write};
-use tortilla::{Guacamole, Salsa, Toppings};
+use tortilla::{
+    Auto, Guacamole, Salsa, SalsaMinLines, Stats, Toppings, WordsPerLine, diff, first_changed_line,
+};
+use unicode_width::UnicodeWidthStr;
 
-const HELP: &str = "Usage: tortilla [-h, --help] [--width <WIDTH>] [--tabs <TABS>] [--crlf] [--salsa] [--guacamole]\n";
+const HELP: &str = "Usage: tortilla [-h, --help] [--width <WIDTH>] [--tabs <TABS>] [--expand-tabs] [--crlf] [--auto-newline] [--break-long-words] [--auto] [--salsa] [--salsa-min-lines] [--guacamole] [--words-per-line <N>] [--jsonl] [--show-whitespace] [--stats] [--check] [--diff]\n";
 
 enum Sauce {
+    Auto,
     Salsa,
+    SalsaMinLines,
     Guacamole,
+    WordsPerLine,
 }
 
-fn order() -> io::Result<(Sauce, Toppings)> {
+enum Output {
+    Text,
+    /// Whitespace rendered as visible glyphs, see [tortilla::visible_whitespace].
+    Visible,
+    /// One JSON object per output line, with the line's width counting tabs as
+    /// `tabs` columns.
+    JsonLines {
+        tabs: usize,
+    },
+    /// No output, only whether wrapping would change the input, see
+    /// [tortilla::first_changed_line].
+    Check,
+    /// A unified diff from input to output, see [tortilla::diff].
+    Diff,
+}
+
+/// Sauce, toppings and output format, and whether to report statistics
+/// ('--stats').
+fn order() -> io::Result<(Sauce, Toppings, Output, bool)> {
     let mut args = std::env::args().skip(1);
 
-    let mut sauce = Sauce::Salsa;
+    let mut sauce = Sauce::Auto;
     let mut toppings = tortilla::Toppings::default();
+    let mut jsonl = false;
+    let mut show_whitespace = false;
+    let mut check = false;
+    let mut diff = false;
+    let mut stats = false;
+    let mut tabs = 4; // Same as Toppings::default().
 
     macro_rules! exit {
         ($($arg:tt)*) => {{
@@ -36,16 +66,38 @@ fn order() -> io::Result<(Sauce, Toppings)> {
                 let Some(value) = args.next() else {
                     exit!("Missing value for flag '--tabs'");
                 };
-                toppings = toppings.tabs(value.parse().unwrap_or_else(|err| {
+                tabs = value.parse().unwrap_or_else(|err| {
                     exit!("Bad value '{value}' for option '--tabs': {err}");
-                }));
+                });
+                toppings = toppings.tabs(tabs);
             }
 
+            "--expand-tabs" => toppings = toppings.expand_tabs(true),
+
             "--crlf" => toppings = toppings.newline(tortilla::Newline::CRLF),
+            "--auto-newline" => toppings = toppings.detect_newline(true),
+            "--break-long-words" => toppings = toppings.break_long_words(true),
+            "--jsonl" => jsonl = true,
+            "--show-whitespace" => show_whitespace = true,
+            "--stats" => stats = true,
+            "--check" => check = true,
+            "--diff" => diff = true,
 
+            "--auto" => sauce = Sauce::Auto,
             "--salsa" => sauce = Sauce::Salsa,
+            "--salsa-min-lines" => sauce = Sauce::SalsaMinLines,
             "--guacamole" => sauce = Sauce::Guacamole,
 
+            "--words-per-line" => {
+                let Some(value) = args.next() else {
+                    exit!("Missing value for flag '--words-per-line'");
+                };
+                toppings = toppings.words_per_line(value.parse().unwrap_or_else(|err| {
+                    exit!("Bad value '{value}' for option '--words-per-line': {err}");
+                }));
+                sauce = Sauce::WordsPerLine;
+            }
+
             "-h" | "--help" => {
                 io::stderr().lock().write_all(HELP.as_bytes())?;
                 std::process::exit(0);
@@ -55,14 +107,150 @@ fn order() -> io::Result<(Sauce, Toppings)> {
         }
     }
 
-    Ok((sauce, toppings))
+    let mut formats = [
+        ("--jsonl", jsonl),
+        ("--show-whitespace", show_whitespace),
+        ("--check", check),
+        ("--diff", diff),
+    ]
+    .into_iter()
+    .filter_map(|(flag, set)| set.then_some(flag));
+
+    let output = match (formats.next(), formats.next()) {
+        (Some(a), Some(b)) => exit!("Flags '{a}' and '{b}' are mutually exclusive"),
+        (Some("--jsonl"), None) => Output::JsonLines { tabs },
+        (Some("--show-whitespace"), None) => Output::Visible,
+        (Some("--check"), None) => Output::Check,
+        (Some("--diff"), None) => Output::Diff,
+        _ => Output::Text,
+    };
+
+    // Neither produces wrapped output to report statistics about.
+    match (&output, stats) {
+        (Output::Check, true) => exit!("Flags '--check' and '--stats' are mutually exclusive"),
+        (Output::Diff, true) => exit!("Flags '--diff' and '--stats' are mutually exclusive"),
+        _ => {}
+    }
+
+    Ok((sauce, toppings, output, stats))
+}
+
+fn eat<S: tortilla::Sauce>(
+    input: impl Read,
+    toppings: Toppings,
+    output: &Output,
+    mouth: &mut impl Write,
+) -> io::Result<Stats> {
+    let mut bites = tortilla::wrap_reader::<S, _>(input, toppings);
+
+    let tabs = match output {
+        Output::Text => {
+            for bite in bites.by_ref() {
+                mouth.write_all(bite?.as_bytes())?;
+            }
+            return Ok(bites.stats());
+        }
+        Output::Visible => {
+            for bite in bites.by_ref() {
+                mouth.write_all(tortilla::visible_whitespace(&bite?).as_bytes())?;
+            }
+            return Ok(bites.stats());
+        }
+        Output::JsonLines { tabs } => *tabs,
+        Output::Check | Output::Diff => unreachable!("Input is checked as a whole"),
+    };
+
+    let mut line = String::new();
+
+    for bite in bites.by_ref() {
+        for piece in bite?.split_inclusive('\n') {
+            let Some(text) = piece.strip_suffix('\n') else {
+                line.push_str(piece);
+                continue;
+            };
+
+            line.push_str(text.strip_suffix('\r').unwrap_or(text));
+            record(&line, tabs, mouth)?;
+            line.clear();
+        }
+    }
+
+    if !line.is_empty() {
+        record(&line, tabs, mouth)?;
+    }
+
+    Ok(bites.stats())
+}
+
+/// Write statistics about the output, see '--stats'.
+fn report(stats: Stats, mouth: &mut impl Write) -> io::Result<()> {
+    writeln!(mouth, "lines: {}", stats.lines)?;
+    writeln!(mouth, "max width: {}", stats.max_width)?;
+    writeln!(mouth, "slack: {}", stats.slack)?;
+    writeln!(mouth, "overfull: {}", stats.overfull)
+}
+
+/// Write a line of output as a JSON object, see '--jsonl'.
+fn record(line: &str, tabs: usize, mouth: &mut impl Write) -> io::Result<()> {
+    let width = line
+        .split('\t')
+        .map(UnicodeWidthStr::width_cjk)
+        .fold(0, usize::saturating_add)
+        .saturating_add(line.matches('\t').count().saturating_mul(tabs));
+
+    mouth.write_all(b"{\"line\": \"")?;
+
+    for c in line.chars() {
+        match c {
+            '"' => mouth.write_all(b"\\\"")?,
+            '\\' => mouth.write_all(b"\\\\")?,
+            '\n' => mouth.write_all(b"\\n")?,
+            '\r' => mouth.write_all(b"\\r")?,
+            '\t' => mouth.write_all(b"\\t")?,
+            c if c.is_control() => write!(mouth, "\\u{:04x}", c as u32)?,
+            c => write!(mouth, "{c}")?,
+        }
+    }
+
+    writeln!(mouth, "\", \"width\": {width}}}")
 }
 
 fn main() -> io::Result<()> {
-    let (sauce, toppings) = order()?;
+    let (sauce, toppings, output, stats) = order()?;
+
+    // Calls a function generic over the chosen sauce, e.g., `serve!(eat(...))`.
+    macro_rules! serve {
+        ($f:ident $args:tt) => {
+            match sauce {
+                Sauce::Auto => $f::<Auto> $args,
+                Sauce::Salsa => $f::<Salsa> $args,
+                Sauce::SalsaMinLines => $f::<SalsaMinLines> $args,
+                Sauce::Guacamole => $f::<Guacamole> $args,
+                Sauce::WordsPerLine => $f::<WordsPerLine> $args,
+            }
+        };
+    }
 
-    let mut input = String::new();
-    io::stdin().read_to_string(&mut input)?;
+    // Input is streamed, unless it has to be looked at as a whole first.
+    let mut text = String::new();
+    let whole = matches!(output, Output::Check | Output::Diff);
+
+    if whole {
+        io::stdin().read_to_string(&mut text)?;
+    }
+
+    if let Output::Check = output {
+        if let Some(line) = serve!(first_changed_line(&text, &toppings)) {
+            eprintln!("Line {line} would change");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let input: Box<dyn Read> = match whole {
+        false => Box::new(io::stdin().lock()),
+        true => Box::new(text.as_bytes()),
+    };
 
     #[cfg(unix)]
     let mut mouth = {
@@ -75,18 +263,101 @@ fn main() -> io::Result<()> {
     #[cfg(not(unix))]
     let mut mouth = io::stdout().lock();
 
-    match sauce {
-        Sauce::Salsa => {
-            for bite in tortilla::wrap::<Salsa>(&input, toppings) {
-                mouth.write_all(bite.as_bytes())?;
-            }
-        }
-        Sauce::Guacamole => {
-            for bite in tortilla::wrap::<Guacamole>(&input, toppings) {
-                mouth.write_all(bite.as_bytes())?;
-            }
+    if let Output::Diff = output {
+        let hunks = serve!(diff(&text, toppings));
+        if !hunks.is_empty() {
+            mouth.write_all(b"--- input\n+++ output\n")?;
+            mouth.write_all(hunks.as_bytes())?;
+            mouth.flush()?;
+            std::process::exit(1);
         }
+        return Ok(());
     }
 
-    mouth.flush() // Stay hydrated.
+    let digested = serve!(eat(input, toppings, &output, &mut mouth))?;
+
+    mouth.flush()?; // Stay hydrated.
+
+    if stats {
+        report(digested, &mut io::stderr().lock())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Output, eat, report};
+    use tortilla::{Salsa, Toppings};
+
+    fn jsonl(input: &str, toppings: Toppings) -> String {
+        let mut mouth = Vec::new();
+        eat::<Salsa>(
+            input.as_bytes(),
+            toppings,
+            &Output::JsonLines { tabs: 4 },
+            &mut mouth,
+        )
+        .unwrap();
+        String::from_utf8(mouth).unwrap()
+    }
+
+    #[test]
+    fn two_lines() {
+        assert_eq!(
+            jsonl("foo \"bar\" baz\n", Toppings::default().width(9)),
+            "{\"line\": \"foo \\\"bar\\\"\", \"width\": 9}\n{\"line\": \"baz\", \"width\": 3}\n"
+        );
+    }
+
+    #[test]
+    fn visible() {
+        let mut mouth = Vec::new();
+        let toppings = Toppings::default().width(12);
+        eat::<Salsa>(
+            "  - foo bar baz\r\n".as_bytes(),
+            toppings,
+            &Output::Visible,
+            &mut mouth,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(mouth).unwrap(),
+            "··-·foo·bar⏎\n····baz⏎\n"
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            jsonl("\tC:\\foo\u{1b}\r\n\r\n", Toppings::default()),
+            "{\"line\": \"\\tC:\\\\foo\\u001b\", \"width\": 11}\n{\"line\": \"\", \"width\": 0}\n"
+        );
+    }
+
+    #[test]
+    fn stats() {
+        let mut mouth = Vec::new();
+        let stats = eat::<Salsa>(
+            "foo bar baz qux\n\nquux corge\n".as_bytes(),
+            Toppings::default().width(9),
+            &Output::Text,
+            &mut mouth,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(mouth).unwrap(),
+            "foo bar\nbaz qux\n\nquux\ncorge\n"
+        );
+
+        let mut mouth = Vec::new();
+        report(stats, &mut mouth).unwrap();
+
+        assert_eq!(
+            String::from_utf8(mouth).unwrap(),
+            "lines: 5\nmax width: 7\nslack: 29\noverfull: 0\n"
+        );
+    }
 }
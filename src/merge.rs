@@ -1,29 +1,232 @@
-use std::iter::Peekable;
+use std::collections::VecDeque;
 
-use unicode_width::UnicodeWidthStr;
+use super::parse::{Parse, ParseState, str_width};
+use super::{Line, LineInfo, Tabs, Token, Toppings, Whitespace};
 
-use super::{Line, Whitespace};
-
-pub(super) struct Merge<L: Iterator> {
-    lines: Peekable<L>,
+pub(super) struct Merge<L: Iterator, B> {
+    lines: Ahead<L>,
+    boundary: B,
+    reflow: bool,
+    indent_by_width: bool,
+    lenient_comments: bool,
+    preserve_hanging: bool,
+    single_blank: bool,
+    max_blank: Option<usize>,
+    /// Number of blank lines returned in a row, see [Toppings::max_blank_lines].
+    blank_run: usize,
+    started: bool,
+    nesting: Option<Nesting>,
+    align_numbers: bool,
+    tabs: Tabs,
+    cjk: bool,
+    /// Paragraphs that have been read ahead, along with whether they were
+    /// merged.
+    queue: VecDeque<(L::Item, bool)>,
+    /// Number of paragraphs at the front of [Merge::queue] whose numbers
+    /// have been aligned already.
+    aligned: usize,
+    merged: bool,
 }
 
-impl<L: Iterator> Merge<L> {
-    pub fn new(lines: L) -> Self {
+impl<L: Iterator, B> Merge<L, B> {
+    pub fn new(lines: L, boundary: B, toppings: &Toppings) -> Self {
         Self {
-            lines: lines.peekable(),
+            lines: Ahead {
+                lines,
+                peeked: None,
+            },
+            boundary,
+            reflow: toppings.reflow && !toppings.overflow_only,
+            indent_by_width: toppings.indent_by_width,
+            lenient_comments: toppings.lenient_comment_indent,
+            preserve_hanging: toppings.preserve_continuation_indent,
+            single_blank: toppings.single_blank_between_paragraphs,
+            max_blank: toppings.max_blank_lines,
+            blank_run: 0,
+            started: false,
+            nesting: toppings.list_indent.map(|step| Nesting {
+                step,
+                tabs: toppings.tab_widths(),
+                levels: Vec::new(),
+                comment: None,
+            }),
+            align_numbers: toppings.align_list_numbers,
+            tabs: toppings.tab_widths(),
+            cjk: toppings.cjk,
+            queue: VecDeque::new(),
+            aligned: 0,
+            merged: false,
+        }
+    }
+
+    /// Whether the paragraph returned last was merged from several lines.
+    pub fn was_merged(&self) -> bool {
+        self.merged
+    }
+}
+
+impl<'t, I, B> Merge<Parse<'t, I>, B>
+where
+    I: Iterator<Item = (usize, Token<'t>)>,
+{
+    /// What the lines merged so far tell about the lines that follow, e.g.,
+    /// to continue on the next piece of input, see [Merge::resume].
+    pub fn state(&self) -> MergeState {
+        MergeState {
+            parse: self.lines.lines.state(),
+            blank_run: self.blank_run,
+            started: self.started,
+            nesting: self
+                .nesting
+                .as_ref()
+                .map(|nesting| (nesting.levels.clone(), nesting.comment.clone())),
+        }
+    }
+
+    /// Merge as if the lines that `state` was taken from preceded the input.
+    pub fn resume(mut self, state: MergeState) -> Self {
+        self.lines.lines = self.lines.lines.resume(state.parse);
+        self.blank_run = state.blank_run;
+        self.started = state.started;
+
+        if let (Some(nesting), Some((levels, comment))) = (&mut self.nesting, state.nesting) {
+            nesting.levels = levels;
+            nesting.comment = comment;
+        }
+
+        self
+    }
+}
+
+/// State of a [Merge] that carries over from one line to the next, see
+/// [Merge::state].
+#[derive(Debug, Clone)]
+pub(super) struct MergeState {
+    parse: ParseState,
+    blank_run: usize,
+    started: bool,
+    /// Levels and comment token of the list being nested, see [Nesting].
+    nesting: Option<(Vec<usize>, Option<String>)>,
+}
+
+/// Like [std::iter::Peekable], but with access to the underlying iterator.
+struct Ahead<L: Iterator> {
+    lines: L,
+    peeked: Option<Option<L::Item>>,
+}
+
+impl<L: Iterator> Ahead<L> {
+    fn next(&mut self) -> Option<L::Item> {
+        match self.peeked.take() {
+            Some(line) => line,
+            None => self.lines.next(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<&L::Item> {
+        let lines = &mut self.lines;
+        self.peeked.get_or_insert_with(|| lines.next()).as_ref()
+    }
+
+    fn next_if(&mut self, func: impl FnOnce(&L::Item) -> bool) -> Option<L::Item> {
+        match self.next() {
+            Some(line) if func(&line) => Some(line),
+            line => {
+                self.peeked = Some(line);
+                None
+            }
+        }
+    }
+}
+
+/// Tracks the nesting of list items to normalize their indentation, see
+/// [Toppings::list_indent].
+struct Nesting {
+    step: usize,
+    tabs: Tabs,
+    /// Original widths of the list items' nesting whitespace, one per level.
+    levels: Vec<usize>,
+    /// Comment token of the list items.
+    comment: Option<String>,
+}
+
+impl Nesting {
+    fn normalize(&mut self, line: &mut Line<'_>) {
+        if is_blank(line) {
+            return;
+        }
+
+        if self.comment.as_deref() != line.comment {
+            self.levels.clear();
+            self.comment = line.comment.map(str::to_owned);
+        }
+
+        // Within comments, lists are nested by the padding after the token.
+        let width = match line.comment {
+            Some(_) => self.tabs.width(line.padding),
+            None => self
+                .tabs
+                .indent_width(line.indent)
+                .saturating_add(self.tabs.width(line.padding)),
+        };
+
+        if line.bullet.is_none() {
+            // Text at or left of the outermost list item ends the list.
+            if self.levels.first().is_none_or(|root| width <= *root) {
+                self.levels.clear();
+            }
+            return;
+        }
+
+        while self.levels.last().is_some_and(|level| *level > width) {
+            self.levels.pop();
+        }
+
+        if self.levels.last() != Some(&width) {
+            self.levels.push(width);
+        }
+
+        if line.verbatim.is_some() {
+            return;
+        }
+
+        let width = self.levels[0] + (self.levels.len() - 1) * self.step;
+
+        match line.comment {
+            Some(_) => line.padding = Whitespace::Space(width),
+            None => {
+                line.indent = Whitespace::Space(width);
+                line.raw_indent = None;
+                line.padding = Whitespace::Space(0);
+            }
         }
     }
 }
 
-fn should_merge(upper: &Line<'_>, lower: &Line<'_>) -> bool {
+/// The default paragraph boundary predicate, see [wrap_with_boundary].
+///
+/// Returns `true` if `lower` starts a new paragraph rather than continuing
+/// `upper`. This is the case for empty lines, lines that start their own
+/// bullet, markdown headings (see [Toppings::markdown]), and lines with a
+/// different comment token or indentation than the one above.
+///
+/// [wrap_with_boundary]: crate::wrap_with_boundary
+pub fn paragraph_boundary(upper: &LineInfo<'_>, lower: &LineInfo<'_>) -> bool {
+    !should_merge(upper.0, lower.0, upper.1)
+}
+
+fn should_merge(upper: &Line<'_>, lower: &Line<'_>, cjk: bool) -> bool {
     !upper.words.is_empty() && !lower.words.is_empty() // Don't touch "empty" lines
+        && upper.verbatim.is_none() && lower.verbatim.is_none() // Nor verbatim ones
+        && upper.trailing_space.is_none() // Nor ones that end in a break
+        && !upper.heading && !lower.heading // Markdown headings stand alone
         && lower.bullet.is_none() // Don't touch lines that start their own bullet
+        && upper.gutter == lower.gutter // Line number must match
         && upper.comment == lower.comment // Comment token must match
-        && bullet_continuation(upper, lower)
+        && bullet_continuation(upper, lower, cjk)
 }
 
-fn bullet_continuation(upper: &Line<'_>, lower: &Line<'_>) -> bool {
+fn bullet_continuation(upper: &Line<'_>, lower: &Line<'_>, cjk: bool) -> bool {
     let bullet = match upper.bullet {
         // No bullet, padding and indent must match 1 to 1:
         None => return upper.padding == lower.padding && upper.indent == lower.indent,
@@ -37,7 +240,7 @@ fn bullet_continuation(upper: &Line<'_>, lower: &Line<'_>) -> bool {
     };
 
     // +1 for space between bullet and word.
-    let bullet_width = bullet.width_cjk() + 1;
+    let bullet_width = str_width(bullet, cjk) + 1;
 
     // Bullets only work with space padding.
     matches!(upper_whitespace, Whitespace::Space(_))
@@ -45,22 +248,311 @@ fn bullet_continuation(upper: &Line<'_>, lower: &Line<'_>) -> bool {
         && upper_whitespace.count() + bullet_width == lower_whitespace.count()
 }
 
+/// Largest difference in indentation, in columns, between comment lines that
+/// continue each other, see [Toppings::lenient_comment_indent].
+const COMMENT_INDENT_TOLERANCE: usize = 2;
+
+/// Whether two lines with the same comment token and no bullet are indented
+/// within [COMMENT_INDENT_TOLERANCE] of each other.
+fn lenient_comment(upper: &Line<'_>, lower: &Line<'_>, tabs: &Tabs) -> bool {
+    upper.comment.is_some()
+        && upper.comment == lower.comment
+        && upper.bullet.is_none()
+        && tabs
+            .indent_width(upper.indent)
+            .abs_diff(tabs.indent_width(lower.indent))
+            <= COMMENT_INDENT_TOLERANCE
+}
+
+/// A line continuing a bulleted line, presented as if it was aligned with the
+/// words following the bullet, along with the number of spaces it is actually
+/// indented by relative to the bullet, see
+/// [Toppings::preserve_continuation_indent]. Lines must agree with any
+/// continuation line joined before.
+fn realigned<'t>(upper: &Line<'_>, lower: &Line<'t>, cjk: bool) -> Option<(Line<'t>, usize)> {
+    let bullet_width = str_width(upper.bullet?, cjk) + 1;
+
+    // Same as in [bullet_continuation].
+    let same_indent = upper.indent == lower.indent;
+    let (upper_whitespace, lower_whitespace) = match same_indent {
+        true => (upper.padding, lower.padding),
+        false => (upper.indent, lower.indent),
+    };
+
+    let (Whitespace::Space(upper_count), Whitespace::Space(lower_count)) =
+        (upper_whitespace, lower_whitespace)
+    else {
+        return None;
+    };
+
+    let width = lower_count
+        .checked_sub(upper_count)
+        .filter(|width| *width > 0 && upper.hanging.is_none_or(|hanging| hanging == *width))?;
+
+    let aligned = Whitespace::Space(upper_count + bullet_width);
+    let line = match same_indent {
+        true => Line {
+            padding: aligned,
+            ..lower.clone()
+        },
+        false => Line {
+            indent: aligned,
+            ..lower.clone()
+        },
+    };
+
+    Some((line, width))
+}
+
+fn is_numbered(line: &Line<'_>) -> bool {
+    line.bullet
+        .is_some_and(|bullet| bullet.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// Column where the bullet of a line starts.
+fn bullet_start(line: &Line<'_>, tabs: &Tabs) -> usize {
+    tabs.indent_width(line.indent)
+        .saturating_add(tabs.width(line.padding))
+}
+
+/// Column right after the bullet of a line.
+fn bullet_end(line: &Line<'_>, tabs: &Tabs, cjk: bool) -> usize {
+    bullet_start(line, tabs).saturating_add(line.bullet.map_or(0, |bullet| str_width(bullet, cjk)))
+}
+
+/// Spaces that the words of a continued line are indented by, relative to the
+/// padding of the line that it continues, see [Toppings::continuation].
+fn continued_hanging(upper: &Line<'_>, lower: &Line<'_>, tabs: &Tabs) -> usize {
+    bullet_start(lower, tabs).saturating_sub(bullet_start(upper, tabs))
+}
+
+/// Whether two adjacent numbered list items belong to the same list, i.e.,
+/// their numbers either start or end at the same column.
+fn same_list(upper: &Line<'_>, lower: &Line<'_>, tabs: &Tabs, cjk: bool) -> bool {
+    upper.comment == lower.comment
+        && (bullet_start(upper, tabs) == bullet_start(lower, tabs)
+            || bullet_end(upper, tabs, cjk) == bullet_end(lower, tabs, cjk))
+}
+
+/// Pad the numbers of list items such that they are right-aligned, see
+/// [Toppings::align_list_numbers].
+fn align_numbers(lines: &mut [(Line<'_>, bool)], tabs: &Tabs, cjk: bool) {
+    let max = lines
+        .iter()
+        .map(|(line, _)| bullet_end(line, tabs, cjk))
+        .max()
+        .unwrap_or(0);
+
+    for (line, _) in lines {
+        let padding = max - bullet_end(line, tabs, cjk);
+
+        if let Whitespace::Space(count) = line.padding {
+            line.padding = Whitespace::Space(count + padding);
+        } else if line.padding.count() == 0 {
+            line.padding = Whitespace::Space(padding);
+        }
+    }
+}
+
+fn is_blank(line: &Line<'_>) -> bool {
+    line.words.is_empty() && line.bullet.is_none()
+}
+
 fn merge<'t>(upper: &mut Line<'t>, mut lower: Line<'t>) {
+    let offset = upper.words.len();
+    upper
+        .gaps
+        .extend(lower.gaps.iter().map(|(idx, gap)| (idx + offset, *gap)));
     upper.words.append(&mut lower.words);
+    upper.trailing_space = lower.trailing_space;
     upper.newline &= lower.newline;
 }
 
-impl<'t, L> Iterator for Merge<L>
+impl<'t, L, B> Iterator for Merge<L, B>
 where
     L: Iterator<Item = Line<'t>>,
+    B: FnMut(&LineInfo<'_>, &LineInfo<'_>) -> bool,
 {
     type Item = Line<'t>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.aligned > 0 {
+            self.aligned -= 1;
+            let (line, merged) = self.queue.pop_front()?;
+            self.merged = merged;
+            return Some(line);
+        }
+
+        // The paragraph that ended the list before may start one of its own.
+        let (line, merged) = match self.queue.pop_front() {
+            Some(queued) => queued,
+            None => {
+                let line = self.normalized()?;
+                (line, self.merged)
+            }
+        };
+        self.merged = merged;
+
+        if !self.align_numbers || !is_numbered(&line) {
+            return Some(line);
+        }
+
+        // Read ahead until the end of the list to find its widest number.
+        self.queue.push_front((line, merged));
+        let mut len = 1;
+
+        loop {
+            if self.queue.len() == len {
+                let Some(line) = self.normalized() else {
+                    break;
+                };
+                self.queue.push_back((line, self.merged));
+            }
+
+            let (last, _) = &self.queue[len - 1];
+            let (line, _) = &self.queue[len];
+            if !is_numbered(line) || !same_list(last, line, &self.tabs, self.cjk) {
+                break;
+            }
+            len += 1;
+        }
+
+        align_numbers(
+            &mut self.queue.make_contiguous()[..len],
+            &self.tabs,
+            self.cjk,
+        );
+        self.aligned = len;
+
+        self.next()
+    }
+}
+
+impl<'t, L, B> Merge<L, B>
+where
+    L: Iterator<Item = Line<'t>>,
+    B: FnMut(&LineInfo<'_>, &LineInfo<'_>) -> bool,
+{
+    /// The next paragraph, with the indentation of list items normalized.
+    fn normalized(&mut self) -> Option<Line<'t>> {
+        let mut line = self.paragraph()?;
+
+        if let Some(nesting) = &mut self.nesting {
+            nesting.normalize(&mut line);
+        }
+
+        Some(line)
+    }
+
+    /// The next paragraph, with any continuation lines merged into it.
+    fn paragraph(&mut self) -> Option<Line<'t>> {
         let mut upper = self.lines.next()?;
+        self.merged = false;
+
+        if self.single_blank && is_blank(&upper) {
+            while self.lines.next_if(is_blank).is_some() {}
+
+            // Blank lines at the start or end of input don't separate anything.
+            if !self.started {
+                upper = self.lines.next()?;
+            } else if self.lines.peek().is_none() {
+                return None;
+            }
+        }
+
+        self.started = true;
+
+        if let Some(max) = self.max_blank {
+            while is_blank(&upper) && self.blank_run >= max {
+                upper = self.lines.next()?;
+            }
+
+            self.blank_run = match is_blank(&upper) {
+                true => self.blank_run + 1,
+                false => 0,
+            };
+        }
+
+        // Continued lines are joined no matter what, see
+        // [Toppings::continuation].
+        if let Some(token) = upper.continuation {
+            let mut continued = true;
+
+            while continued {
+                let Some(lower) = self
+                    .lines
+                    .next_if(|lower| lower.verbatim.is_none() && !is_blank(lower))
+                else {
+                    // Nothing to continue on, so the token stays where it is.
+                    upper.words.push(token);
+                    break;
+                };
+
+                continued = lower.continuation.is_some();
 
-        while let Some(lower) = self.lines.next_if(|lower| should_merge(&upper, lower)) {
+                // Wrapped lines are indented like the first continued line.
+                if !self.merged {
+                    upper.hanging = Some(continued_hanging(&upper, &lower, &self.tabs));
+                }
+
+                merge(&mut upper, lower);
+                self.merged = true;
+            }
+
+            return Some(upper);
+        }
+
+        if !self.reflow {
+            return Some(upper);
+        }
+
+        let mut hanging = None;
+
+        while let Some(lower) = self.lines.next_if(|lower| {
+            // Present visually equal indentation, or that of comment lines
+            // which is only slightly off, as equal to the boundary.
+            let visually_equal = self.indent_by_width
+                && self.tabs.indent_width(lower.indent) == self.tabs.indent_width(upper.indent);
+            let slightly_off = self.lenient_comments && lenient_comment(&upper, lower, &self.tabs);
+
+            let normalized;
+            let lower = match lower.indent != upper.indent && (visually_equal || slightly_off) {
+                true => {
+                    normalized = Line {
+                        indent: upper.indent,
+                        ..lower.clone()
+                    };
+                    &normalized
+                }
+                false => lower,
+            };
+
+            // Likewise for continuation lines of a bulleted line that are
+            // indented by a different amount than the bullet's width.
+            let aligned;
+            hanging = None;
+            let lower = match self.preserve_hanging {
+                true => match realigned(&upper, lower, self.cjk) {
+                    Some((line, width)) => {
+                        aligned = line;
+                        hanging = Some(width);
+                        &aligned
+                    }
+                    None => lower,
+                },
+                false => lower,
+            };
+
+            // Continued lines start a paragraph of their own.
+            lower.continuation.is_none()
+                && !(self.boundary)(
+                    &LineInfo(&upper, self.cjk, &self.tabs),
+                    &LineInfo(lower, self.cjk, &self.tabs),
+                )
+        }) {
+            upper.hanging = hanging.or(upper.hanging);
             merge(&mut upper, lower);
+            self.merged = true;
         }
 
         Some(upper)
@@ -69,10 +561,15 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::{Line, Whitespace::*, line};
+    use crate::{Line, LineInfo, Toppings, Whitespace::*, line};
 
     fn merge(lines: Vec<Line>) -> Vec<Line> {
-        super::Merge::new(lines.into_iter()).collect()
+        super::Merge::new(
+            lines.into_iter(),
+            super::paragraph_boundary,
+            &Toppings::default(),
+        )
+        .collect()
     }
 
     #[test]
@@ -110,6 +607,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn merge_numbered_bullet_in_doc_comment() {
+        assert_eq!(
+            merge(vec![
+                line!(Space(0), Some("///"), Space(1), Some("10."), "foo" ;),
+                line!(Space(0), Some("///"), Space(5), None, "bar" ;),
+            ]),
+            vec![line!(Space(0), Some("///"), Space(1), Some("10."), "foo", "bar" ;)]
+        );
+    }
+
+    #[test]
+    fn do_not_merge_after_trailing_space() {
+        assert_eq!(
+            merge(vec![
+                line!(Space(0), None, Space(0), None, "foo" ;),
+                Line {
+                    trailing_space: Some("  "),
+                    ..line!(Space(0), None, Space(0), None, "bar" ;)
+                },
+                line!(Space(0), None, Space(0), None, "baz"),
+            ]),
+            vec![
+                Line {
+                    trailing_space: Some("  "),
+                    ..line!(Space(0), None, Space(0), None, "foo", "bar" ;)
+                },
+                line!(Space(0), None, Space(0), None, "baz"),
+            ]
+        );
+    }
+
+    #[test]
+    fn ambiguous_width_bullet() {
+        let lines = vec![
+            line!(Space(0), None, Space(0), Some("•"), "foo" ;),
+            line!(Space(0), None, Space(2), None, "bar" ;),
+        ];
+        let merge_with = |toppings: &Toppings| {
+            super::Merge::new(
+                lines.clone().into_iter(),
+                super::paragraph_boundary,
+                toppings,
+            )
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            merge_with(&Toppings::default().cjk(false)),
+            vec![line!(Space(0), None, Space(0), Some("•"), "foo", "bar" ;)]
+        );
+        assert_eq!(merge_with(&Toppings::default()), lines);
+    }
+
     #[test]
     fn do_not_merge_different_comments() {
         assert_eq!(
@@ -187,4 +738,350 @@ mod tests {
             )]
         );
     }
+
+    #[test]
+    fn was_merged() {
+        let mut merge = super::Merge::new(
+            vec![
+                line!(Space(0), None, Space(0), None, "foo" ;),
+                line!(Space(0), None, Space(0), None, "bar" ;),
+                line!(Space(0), None, Space(0), None ;),
+                line!(Space(0), None, Space(0), None, "baz"),
+            ]
+            .into_iter(),
+            super::paragraph_boundary,
+            &Toppings::default(),
+        );
+
+        let mut merged = Vec::new();
+        while merge.next().is_some() {
+            merged.push(merge.was_merged());
+        }
+
+        assert_eq!(merged, vec![true, false, false]);
+    }
+
+    #[test]
+    fn custom_boundary_sentence_per_paragraph() {
+        let sentences = |upper: &LineInfo, lower: &LineInfo| {
+            super::paragraph_boundary(upper, lower)
+                || upper.words().last().is_some_and(|word| word.ends_with('.'))
+        };
+
+        assert_eq!(
+            super::Merge::new(
+                vec![
+                    line!(Space(0), None, Space(0), None, "Foo", "bar." ;),
+                    line!(Space(0), None, Space(0), None, "Baz" ;),
+                    line!(Space(0), None, Space(0), None, "qux." ;),
+                    line!(Space(0), None, Space(0), None, "Quux."),
+                ]
+                .into_iter(),
+                sentences,
+                &Toppings::default(),
+            )
+            .collect::<Vec<_>>(),
+            vec![
+                line!(Space(0), None, Space(0), None, "Foo", "bar." ;),
+                line!(Space(0), None, Space(0), None, "Baz", "qux." ;),
+                line!(Space(0), None, Space(0), None, "Quux."),
+            ]
+        );
+    }
+
+    #[test]
+    fn indent_by_width() {
+        let lines = || {
+            vec![
+                line!(Tab(1), None, Space(0), None, "foo" ;),
+                line!(Space(4), None, Space(0), None, "bar" ;),
+                line!(Space(2), None, Space(0), None, "baz"),
+            ]
+            .into_iter()
+        };
+
+        assert_eq!(merge(lines().collect()), lines().collect::<Vec<_>>());
+
+        assert_eq!(
+            super::Merge::new(
+                lines(),
+                super::paragraph_boundary,
+                &Toppings::default().indent_by_width(true),
+            )
+            .collect::<Vec<_>>(),
+            vec![
+                line!(Tab(1), None, Space(0), None, "foo", "bar" ;),
+                line!(Space(2), None, Space(0), None, "baz"),
+            ]
+        );
+    }
+
+    #[test]
+    fn lenient_comment_indent() {
+        let lines = || {
+            vec![
+                line!(Space(2), Some("//"), Space(1), None, "foo" ;),
+                line!(Space(3), Some("//"), Space(1), None, "bar" ;),
+                line!(Space(3), None, Space(0), None, "baz" ;),
+                line!(Space(6), Some("//"), Space(1), None, "qux"),
+            ]
+            .into_iter()
+        };
+
+        assert_eq!(merge(lines().collect()), lines().collect::<Vec<_>>());
+
+        assert_eq!(
+            super::Merge::new(
+                lines(),
+                super::paragraph_boundary,
+                &Toppings::default().lenient_comment_indent(true),
+            )
+            .collect::<Vec<_>>(),
+            vec![
+                line!(Space(2), Some("//"), Space(1), None, "foo", "bar" ;),
+                line!(Space(3), None, Space(0), None, "baz" ;),
+                line!(Space(6), Some("//"), Space(1), None, "qux"),
+            ]
+        );
+    }
+
+    #[test]
+    fn preserve_continuation_indent() {
+        let lines = || {
+            vec![
+                line!(Space(0), Some("//"), Space(1), Some("-"), "foo" ;),
+                line!(Space(0), Some("//"), Space(5), None, "bar" ;),
+                line!(Space(0), Some("//"), Space(5), None, "baz" ;),
+                line!(Space(0), Some("//"), Space(4), None, "qux" ;),
+            ]
+            .into_iter()
+        };
+
+        assert_eq!(
+            merge(lines().collect()),
+            vec![
+                line!(Space(0), Some("//"), Space(1), Some("-"), "foo" ;),
+                line!(Space(0), Some("//"), Space(5), None, "bar", "baz" ;),
+                line!(Space(0), Some("//"), Space(4), None, "qux" ;),
+            ]
+        );
+
+        assert_eq!(
+            super::Merge::new(
+                lines(),
+                super::paragraph_boundary,
+                &Toppings::default().preserve_continuation_indent(true),
+            )
+            .collect::<Vec<_>>(),
+            vec![
+                Line {
+                    hanging: Some(4),
+                    ..line!(Space(0), Some("//"), Space(1), Some("-"), "foo", "bar", "baz" ;)
+                },
+                line!(Space(0), Some("//"), Space(4), None, "qux" ;),
+            ]
+        );
+    }
+
+    #[test]
+    fn single_blank_between_paragraphs() {
+        assert_eq!(
+            super::Merge::new(
+                vec![
+                    line!(Space(0), None, Space(0), None ;),
+                    line!(Space(0), None, Space(0), None, "a" ;),
+                    line!(Space(0), None, Space(0), None ;),
+                    line!(Space(0), None, Space(0), None ;),
+                    line!(Space(0), None, Space(0), None ;),
+                    line!(Space(0), None, Space(0), None, "b" ;),
+                    line!(Space(0), None, Space(0), None, "c" ;),
+                    line!(Space(0), None, Space(0), None ;),
+                ]
+                .into_iter(),
+                super::paragraph_boundary,
+                &Toppings::default().single_blank_between_paragraphs(true),
+            )
+            .collect::<Vec<_>>(),
+            vec![
+                line!(Space(0), None, Space(0), None, "a" ;),
+                line!(Space(0), None, Space(0), None ;),
+                line!(Space(0), None, Space(0), None, "b", "c" ;),
+            ]
+        );
+    }
+
+    #[test]
+    fn max_blank_lines() {
+        assert_eq!(
+            super::Merge::new(
+                vec![
+                    line!(Space(0), None, Space(0), None, "a" ;),
+                    line!(Space(0), None, Space(0), None ;),
+                    line!(Space(0), None, Space(0), None, "b" ;),
+                    line!(Space(0), None, Space(0), None ;),
+                    line!(Space(0), None, Space(0), None ;),
+                    line!(Space(0), None, Space(0), None ;),
+                    line!(Space(0), None, Space(0), None, "c" ;),
+                ]
+                .into_iter(),
+                super::paragraph_boundary,
+                &Toppings::default().max_blank_lines(2),
+            )
+            .collect::<Vec<_>>(),
+            vec![
+                line!(Space(0), None, Space(0), None, "a" ;),
+                line!(Space(0), None, Space(0), None ;),
+                line!(Space(0), None, Space(0), None, "b" ;),
+                line!(Space(0), None, Space(0), None ;),
+                line!(Space(0), None, Space(0), None ;),
+                line!(Space(0), None, Space(0), None, "c" ;),
+            ]
+        );
+    }
+
+    #[test]
+    fn list_indent() {
+        assert_eq!(
+            super::Merge::new(
+                vec![
+                    line!(Space(1), None, Space(0), Some("-"), "foo" ;),
+                    line!(Space(5), None, Space(0), Some("-"), "bar" ;),
+                    line!(Tab(2), None, Space(0), Some("1."), "baz" ;),
+                    line!(Space(3), None, Space(0), Some("-"), "qux" ;),
+                    line!(Space(1), None, Space(0), Some("-"), "quux" ;),
+                    line!(Space(0), None, Space(0), None, "corge" ;),
+                    line!(Space(4), None, Space(0), Some("-"), "grault"),
+                ]
+                .into_iter(),
+                super::paragraph_boundary,
+                &Toppings::default().list_indent(2),
+            )
+            .collect::<Vec<_>>(),
+            vec![
+                line!(Space(1), None, Space(0), Some("-"), "foo" ;),
+                line!(Space(3), None, Space(0), Some("-"), "bar" ;),
+                line!(Space(5), None, Space(0), Some("1."), "baz" ;),
+                line!(Space(3), None, Space(0), Some("-"), "qux" ;),
+                line!(Space(1), None, Space(0), Some("-"), "quux" ;),
+                line!(Space(0), None, Space(0), None, "corge" ;),
+                line!(Space(4), None, Space(0), Some("-"), "grault"),
+            ]
+        );
+    }
+
+    #[test]
+    fn list_indent_in_comment() {
+        assert_eq!(
+            super::Merge::new(
+                vec![
+                    line!(Space(4), Some("//"), Space(1), Some("-"), "foo" ;),
+                    line!(Space(4), Some("//"), Space(5), Some("-"), "bar" ;),
+                    line!(Space(4), Some("//"), Space(1), Some("-"), "baz"),
+                ]
+                .into_iter(),
+                super::paragraph_boundary,
+                &Toppings::default().list_indent(2),
+            )
+            .collect::<Vec<_>>(),
+            vec![
+                line!(Space(4), Some("//"), Space(1), Some("-"), "foo" ;),
+                line!(Space(4), Some("//"), Space(3), Some("-"), "bar" ;),
+                line!(Space(4), Some("//"), Space(1), Some("-"), "baz"),
+            ]
+        );
+    }
+
+    #[test]
+    fn align_list_numbers() {
+        assert_eq!(
+            super::Merge::new(
+                vec![
+                    line!(Space(0), None, Space(0), Some("9."), "foo" ;),
+                    line!(Space(0), None, Space(0), Some("10."), "bar" ;),
+                    line!(Space(0), None, Space(4), None, "baz" ;),
+                    line!(Space(0), None, Space(0), Some("11."), "qux" ;),
+                    line!(Space(0), None, Space(0), None ;),
+                    line!(Space(0), None, Space(0), Some("1."), "quux"),
+                ]
+                .into_iter(),
+                super::paragraph_boundary,
+                &Toppings::default().align_list_numbers(true),
+            )
+            .collect::<Vec<_>>(),
+            vec![
+                line!(Space(0), None, Space(1), Some("9."), "foo" ;),
+                line!(Space(0), None, Space(0), Some("10."), "bar", "baz" ;),
+                line!(Space(0), None, Space(0), Some("11."), "qux" ;),
+                line!(Space(0), None, Space(0), None ;),
+                line!(Space(0), None, Space(0), Some("1."), "quux"),
+            ]
+        );
+    }
+
+    #[test]
+    fn align_list_numbers_in_comment() {
+        assert_eq!(
+            super::Merge::new(
+                vec![
+                    line!(Space(0), Some("//"), Space(1), Some("9)"), "foo" ;),
+                    line!(Space(0), Some("//"), Space(1), Some("10)"), "bar"),
+                ]
+                .into_iter(),
+                super::paragraph_boundary,
+                &Toppings::default().align_list_numbers(true),
+            )
+            .collect::<Vec<_>>(),
+            vec![
+                line!(Space(0), Some("//"), Space(2), Some("9)"), "foo" ;),
+                line!(Space(0), Some("//"), Space(1), Some("10)"), "bar"),
+            ]
+        );
+    }
+
+    #[test]
+    fn align_list_numbers_in_consecutive_lists() {
+        assert_eq!(
+            super::Merge::new(
+                vec![
+                    line!(Space(2), None, Space(0), Some("9."), "foo" ;),
+                    line!(Space(2), None, Space(0), Some("10."), "bar" ;),
+                    line!(Space(0), None, Space(0), Some("8."), "baz" ;),
+                    line!(Space(0), None, Space(0), Some("9."), "qux" ;),
+                    line!(Space(0), None, Space(0), Some("10."), "quux" ;),
+                ]
+                .into_iter(),
+                super::paragraph_boundary,
+                &Toppings::default().align_list_numbers(true),
+            )
+            .collect::<Vec<_>>(),
+            vec![
+                line!(Space(2), None, Space(1), Some("9."), "foo" ;),
+                line!(Space(2), None, Space(0), Some("10."), "bar" ;),
+                line!(Space(0), None, Space(1), Some("8."), "baz" ;),
+                line!(Space(0), None, Space(1), Some("9."), "qux" ;),
+                line!(Space(0), None, Space(0), Some("10."), "quux" ;),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_reflow() {
+        let lines = || {
+            vec![
+                line!(Space(0), Some("#"), Space(1), None, "foo" ;),
+                line!(Space(0), Some("#"), Space(1), None, "bar" ;),
+            ]
+            .into_iter()
+        };
+
+        assert_eq!(
+            super::Merge::new(
+                lines(),
+                super::paragraph_boundary,
+                &Toppings::default().reflow(false),
+            )
+            .collect::<Vec<_>>(),
+            lines().collect::<Vec<_>>()
+        );
+    }
 }
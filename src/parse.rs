@@ -1,73 +1,395 @@
 use std::iter::Peekable;
 
-use super::{Line, Token, Whitespace};
+#[cfg(feature = "regex")]
+use regex::Regex;
+use unicode_width::UnicodeWidthStr;
 
-pub(super) struct Parse<I: Iterator> {
+use super::{Line, Token, Toppings, Whitespace};
+
+pub(super) struct Parse<'t, I: Iterator> {
+    input: &'t str,
     tokens: Peekable<I>,
+    collapse_spaces: bool,
+    overflow_only: bool,
+    horizontal_rules: bool,
+    punctuation_lines: bool,
+    line_numbers: bool,
+    restructured_text: bool,
+    exact_indent: bool,
+    /// Columns to round indentation to, and whether to round tabs as well,
+    /// see [Toppings::indent_unit].
+    indent_unit: Option<(usize, bool)>,
+    /// Columns that the words of the last list item start at, before and
+    /// after rounding its indentation, see [Toppings::indent_unit].
+    list_item: Option<(usize, usize)>,
+    diff: bool,
+    markdown: bool,
+    /// Whether the current line may still belong to the leading metadata
+    /// block, see [Toppings::metadata].
+    metadata: bool,
+    trailing_space_breaks: bool,
+    preserve_trailing_whitespace: bool,
+    continuation: Option<String>,
+    /// Lines that end paragraphs, and whether to drop them, see
+    /// [Toppings::paragraph_separator].
+    paragraph_separator: Option<String>,
+    drop_separators: bool,
+    /// Whether the current line is within a `/* ... */` comment, if block
+    /// comments are recognized at all, see [Toppings::block_comments].
+    block_comment: Option<bool>,
+    width: usize,
+    tabs: usize,
+    cjk: bool,
+    comment_tokens: Vec<String>,
+    bullet_markers: Vec<String>,
+    alphabetic_bullets: bool,
+    /// Text to keep together as a single word, see [Toppings::protect].
+    #[cfg(feature = "regex")]
+    protect: Option<regex::Regex>,
 }
 
-impl<I: Iterator> Parse<I> {
-    pub fn new(tokens: I) -> Self {
+/// The comment tokens recognized by default, see [Toppings::comment_tokens].
+pub(super) const COMMENT_TOKENS: &[&str] = &["#", ">", ";", "//", "--", ";;", "///", "//!"];
+
+/// The bullet markers recognized by default, see [Toppings::bullet_markers].
+pub(super) const BULLET_MARKERS: &[&str] = &["-", "*", "•"];
+
+impl<'t, I: Iterator> Parse<'t, I> {
+    pub fn new(input: &'t str, tokens: I, toppings: &Toppings) -> Self {
         Self {
+            input,
             tokens: tokens.peekable(),
+            collapse_spaces: toppings.collapse_spaces,
+            overflow_only: toppings.overflow_only,
+            horizontal_rules: toppings.horizontal_rules,
+            punctuation_lines: toppings.punctuation_lines,
+            line_numbers: toppings.line_numbers.is_some(),
+            restructured_text: toppings.restructured_text,
+            exact_indent: toppings.exact_indent,
+            indent_unit: toppings
+                .indent_unit
+                .filter(|unit| *unit > 0)
+                .map(|unit| (unit, toppings.expand_tabs)),
+            list_item: None,
+            diff: toppings.diff,
+            markdown: toppings.markdown,
+            metadata: toppings.metadata,
+            trailing_space_breaks: toppings.trailing_space_breaks,
+            preserve_trailing_whitespace: toppings.preserve_trailing_whitespace,
+            continuation: toppings.continuation.clone(),
+            paragraph_separator: toppings.paragraph_separator.clone(),
+            drop_separators: toppings.drop_paragraph_separators,
+            block_comment: toppings.block_comments.then_some(false),
+            width: toppings.width,
+            tabs: toppings.tabs,
+            cjk: toppings.cjk,
+            comment_tokens: toppings.comment_tokens.clone(),
+            bullet_markers: toppings.bullet_markers.clone(),
+            alphabetic_bullets: toppings.alphabetic_bullets,
+            #[cfg(feature = "regex")]
+            protect: toppings
+                .protect
+                .as_ref()
+                .map(|pattern| Regex::clone(&pattern.0)),
         }
     }
 
-    fn lookahead<F, T>(&mut self, f: F) -> Option<T>
-    where
-        F: FnOnce(&I::Item) -> Option<T>,
-    {
-        let mut ret = None;
+    /// What the lines parsed so far tell about the lines that follow.
+    pub fn state(&self) -> ParseState {
+        ParseState {
+            metadata: self.metadata,
+            block_comment: self.block_comment,
+            list_item: self.list_item,
+        }
+    }
 
-        self.tokens.next_if(|token| {
-            ret = f(token);
-            ret.is_some()
-        });
+    /// Parse as if the lines that `state` was taken from preceded the input.
+    pub fn resume(self, state: ParseState) -> Self {
+        Self {
+            metadata: state.metadata,
+            block_comment: state.block_comment,
+            list_item: state.list_item,
+            ..self
+        }
+    }
+}
+
+/// State of a [Parse] that carries over from one line to the next, see
+/// [Parse::state].
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ParseState {
+    metadata: bool,
+    block_comment: Option<bool>,
+    list_item: Option<(usize, usize)>,
+}
 
-        ret
+/// Width of some text, counting characters of ambiguous width as wide if `cjk`
+/// is set, see [Toppings::cjk].
+pub(super) fn str_width(text: &str, cjk: bool) -> usize {
+    match cjk {
+        true => text.width_cjk(),
+        false => text.width(),
     }
 }
 
-impl<'t, I> Iterator for Parse<I>
+/// Width of some original text, counting each tab character (`\t`) as `tabs`
+/// columns.
+pub(super) fn text_width(text: &str, tabs: usize, cjk: bool) -> usize {
+    let tabs = (text.split('\t').count() - 1).saturating_mul(tabs);
+    text.split('\t')
+        .map(|segment| str_width(segment, cjk))
+        .fold(tabs, usize::saturating_add)
+}
+
+/// Whether a word is a horizontal rule, e.g., `---`, see
+/// [Toppings::horizontal_rules].
+fn is_horizontal_rule(word: &str) -> bool {
+    word.len() >= 3
+        && ['-', '*', '_']
+            .into_iter()
+            .any(|rule| word.chars().all(|c| c == rule))
+}
+
+/// Whether a word consists of punctuation and symbols only, e.g., `...`, see
+/// [Toppings::punctuation_lines].
+fn is_punctuation(word: &str) -> bool {
+    !word.chars().any(char::is_alphanumeric)
+}
+
+/// Indentation rounded to the nearest multiple of `unit` columns, see
+/// [Toppings::indent_unit]. Tabs are only rounded, as `tabs` columns each, if
+/// given.
+fn rounded_indent(indent: Whitespace, unit: usize, tabs: Option<usize>) -> Whitespace {
+    let columns = match (indent, tabs) {
+        (Whitespace::Space(count), _) => count,
+        (Whitespace::Tab(count), Some(tabs)) => count.saturating_mul(tabs),
+        (Whitespace::Tab(_), None) => return indent,
+    };
+
+    Whitespace::Space(columns.saturating_add(unit / 2) / unit * unit)
+}
+
+/// Whether a word is the key of a metadata line, e.g., `Title:`, see
+/// [Toppings::metadata].
+fn is_metadata_key(word: &str) -> bool {
+    word.strip_suffix(':').is_some_and(|key| {
+        !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    })
+}
+
+impl<'t, I> Iterator for Parse<'t, I>
 where
-    I: Iterator<Item = Token<'t>>,
+    I: Iterator<Item = (usize, Token<'t>)>,
 {
     type Item = Line<'t>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.tokens.peek()?;
+        let (start_idx, _) = *self.tokens.peek()?;
+
+        let (mut indent, mut raw_indent) = self.indent();
+        let gutter = self.gutter(start_idx);
+        if gutter.is_some() {
+            (indent, raw_indent) = self.indent();
+        }
 
-        let indent = self.whitespace();
         let comment = self.comment();
         let padding = self.whitespace();
-        let bullet = self.bullet();
-        let (words, newline) = self.words();
+        let mut bullet = self.bullet();
+        let (mut words, mut gaps, end_idx, mut newline) = self.words();
+
+        if self.restructured_text && bullet.is_none() {
+            bullet = self.field(&mut words, &mut gaps);
+        }
+
+        if let Some((unit, expand_tabs)) = self.indent_unit {
+            let rounded = self.rounded_indent(indent, comment, bullet, &words, unit, expand_tabs);
+            if rounded != indent {
+                (indent, raw_indent) = (rounded, None);
+            }
+        }
+
+        #[cfg(feature = "regex")]
+        self.protect(&mut words, &mut gaps);
+
+        if let Some(inside) = &mut self.block_comment {
+            if comment.is_none() && words.first().is_some_and(|word| word.starts_with("/*")) {
+                *inside = true;
+            }
+            if words.last().is_some_and(|word| word.ends_with("*/")) {
+                *inside = false;
+            }
+        }
+
+        let raw = &self.input[start_idx..end_idx];
+        let is_rule = self.horizontal_rules
+            && bullet.is_none()
+            && matches!(words[..], [word] if is_horizontal_rule(word));
+        let is_punctuation = self.punctuation_lines
+            && bullet.is_none()
+            && !words.is_empty()
+            && words.iter().all(|word| is_punctuation(word));
+        let is_directive =
+            self.restructured_text && bullet.is_none() && words.first() == Some(&"..");
+        let is_diff = self.diff && (raw.starts_with(['+', '-', ' ']) || raw.starts_with("@@"));
+        if is_diff && let Some(bullet) = bullet.take() {
+            // Removed lines are no list items.
+            words.insert(0, bullet);
+            gaps.iter_mut().for_each(|(idx, _)| *idx += 1);
+        }
+
+        // Once the metadata block ends, it's gone for good.
+        self.metadata = self.metadata
+            && indent == Whitespace::Space(0)
+            && comment.is_none()
+            && bullet.is_none()
+            && words.first().is_some_and(|word| is_metadata_key(word));
+
+        let is_separator = self.paragraph_separator.as_deref() == Some(raw);
+
+        // Indentation and padding are a single run of spaces or tabs each, so
+        // lines of nothing but more runs than that are kept as they are.
+        let runs = 1 + raw
+            .as_bytes()
+            .windows(2)
+            .filter(|pair| pair[0] != pair[1])
+            .count();
+        let is_whitespace = runs > 2
+            && raw_indent.is_none()
+            && gutter.is_none()
+            && comment.is_none()
+            && bullet.is_none()
+            && words.is_empty();
+
+        let verbatim = (is_rule
+            || is_punctuation
+            || is_separator
+            || is_directive
+            || is_diff
+            || is_whitespace
+            || self.metadata
+            || (self.overflow_only && text_width(raw, self.tabs, self.cjk) <= self.width))
+            .then_some(raw);
+
+        // A dropped separator emits nothing, but still ends the paragraph.
+        let verbatim = match is_separator && self.drop_separators {
+            true => {
+                words.clear();
+                gaps.clear();
+                newline = false;
+                Some(&raw[..0])
+            }
+            false => verbatim,
+        };
+
+        // Markdown hard line breaks, i.e., two trailing spaces or an unescaped
+        // trailing backslash.
+        let spaces_break = self.markdown && raw.ends_with("  ");
+        let backslash_break = self.markdown
+            && words
+                .last()
+                .is_some_and(|word| (word.len() - word.trim_end_matches('\\').len()) % 2 == 1);
+
+        // Whitespace that is kept ends the line as well, the rest is dropped.
+        let trimmed = raw.trim_end_matches([' ', '\t']);
+        let kept = (self.preserve_trailing_whitespace || spaces_break) && trimmed.len() < raw.len();
+        let breaks = kept || backslash_break || (self.trailing_space_breaks && raw.ends_with(' '));
+        let trailing_space =
+            (breaks && verbatim.is_none() && !words.is_empty()).then(|| match kept {
+                true => &raw[trimmed.len()..],
+                false => &raw[raw.len()..],
+            });
+
+        // Markdown headings of level 2 and up lex as a word of their own.
+        let heading = self.markdown
+            && verbatim.is_none()
+            && bullet.is_none()
+            && match (comment, words.as_slice()) {
+                (Some("#"), [_, ..]) => true,
+                (None, [marker, _, ..]) => {
+                    marker.len() <= 6 && marker.bytes().all(|byte| byte == b'#')
+                }
+                _ => false,
+            };
+
+        // The continuation token is emitted at breaks instead, if the line
+        // is joined with the next one, see [Toppings::continuation].
+        let continuation = match (verbatim, words.last()) {
+            (None, Some(word)) if self.continuation.as_deref() == Some(*word) => {
+                gaps.retain(|(idx, _)| *idx + 1 < words.len());
+                words.pop()
+            }
+            _ => None,
+        };
 
         Some(Line {
+            gutter,
             indent,
+            raw_indent,
             comment,
             padding,
             bullet,
+            hanging: None,
             words,
+            gaps,
+            verbatim,
+            trailing_space,
+            heading,
+            continuation,
             newline,
         })
     }
 }
 
-impl<'t, I> Parse<I>
+impl<'t, I> Parse<'t, I>
 where
-    I: Iterator<Item = Token<'t>>,
+    I: Iterator<Item = (usize, Token<'t>)>,
 {
+    /// Indentation rounded to a multiple of `unit` columns, see
+    /// [rounded_indent]. Lines continuing a list item are moved along with its
+    /// bullet instead, so that they still line up with its words, even past
+    /// blank lines.
+    fn rounded_indent(
+        &mut self,
+        indent: Whitespace,
+        comment: Option<&str>,
+        bullet: Option<&str>,
+        words: &[&str],
+        unit: usize,
+        expand_tabs: bool,
+    ) -> Whitespace {
+        let tabs = expand_tabs.then_some(self.tabs);
+        let blank = comment.is_none() && bullet.is_none() && words.is_empty();
+
+        let (rounded, list_item) = match (indent, comment, bullet, self.list_item) {
+            _ if blank => (rounded_indent(indent, unit, tabs), self.list_item),
+            (Whitespace::Space(count), None, Some(bullet), _) => {
+                let rounded = rounded_indent(indent, unit, tabs);
+                let width = str_width(bullet, self.cjk) + 1;
+                (rounded, Some((count + width, rounded.count() + width)))
+            }
+            (Whitespace::Space(count), None, None, Some((end, rounded_end))) if count == end => {
+                (Whitespace::Space(rounded_end), self.list_item)
+            }
+            _ => (rounded_indent(indent, unit, tabs), None),
+        };
+
+        self.list_item = list_item;
+        rounded
+    }
+
     fn whitespace(&mut self) -> Whitespace {
-        let Some(first) = self
+        let Some((_, first)) = self
             .tokens
-            .next_if(|token| *token == Token::Space || *token == Token::Tab)
+            .next_if(|(_, token)| *token == Token::Space || *token == Token::Tab)
         else {
             return Whitespace::Space(0);
         };
 
         let mut count = 1;
-        while self.tokens.next_if_eq(&first).is_some() {
+        while self.tokens.next_if(|(_, token)| *token == first).is_some() {
             count += 1;
         }
 
@@ -78,79 +400,286 @@ where
         }
     }
 
+    /// Indentation, along with its original text if it mixes tabs and spaces
+    /// and is to be preserved as it is, see [Toppings::exact_indent].
+    fn indent(&mut self) -> (Whitespace, Option<&'t str>) {
+        let Some(&(start_idx, _)) = self.tokens.peek() else {
+            return (Whitespace::Space(0), None);
+        };
+
+        let indent = self.whitespace();
+        let is_whitespace =
+            |(_, token): &(usize, Token<'t>)| *token == Token::Space || *token == Token::Tab;
+
+        if !self.exact_indent || self.tokens.next_if(is_whitespace).is_none() {
+            return (indent, None);
+        }
+        while self.tokens.next_if(is_whitespace).is_some() {}
+
+        let end_idx = self.tokens.peek().map_or(self.input.len(), |(idx, _)| *idx);
+
+        let raw = &self.input[start_idx..end_idx];
+        (
+            Whitespace::Space(text_width(raw, self.tabs, self.cjk)),
+            Some(raw),
+        )
+    }
+
+    /// A line number, along with the whitespace preceding it that has already
+    /// been consumed, see [Toppings::line_numbers].
+    fn gutter(&mut self, start_idx: usize) -> Option<&'t str> {
+        if !self.line_numbers {
+            return None;
+        }
+
+        let &(byte_idx, Token::Word(word)) = self.tokens.peek()? else {
+            return None;
+        };
+
+        if !word.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        self.tokens.next();
+        Some(&self.input[start_idx..byte_idx + word.len()])
+    }
+
     fn comment(&mut self) -> Option<&'t str> {
-        const COMMENT_TOKENS: &[&str] = &["#", ">", ";", "//", "--", ";;", "///", "//!"];
+        let &(_, Token::Word(word)) = self.tokens.peek()? else {
+            return None;
+        };
 
-        self.lookahead(|token| match token {
-            Token::Word(word) => COMMENT_TOKENS.contains(word).then_some(*word),
-            _ => None,
+        // Within block comments, a leading `*` continues the comment.
+        let is_comment = self.comment_tokens.iter().any(|token| token == word)
+            || (self.block_comment == Some(true) && word == "*");
+
+        is_comment.then(|| {
+            self.tokens.next();
+            word
         })
     }
 
     fn bullet(&mut self) -> Option<&'t str> {
-        self.lookahead(|token| {
-            let Token::Word(word) = token else {
-                return None;
-            };
+        let &(_, Token::Word(word)) = self.tokens.peek()? else {
+            return None;
+        };
+
+        let ordinal = word.strip_suffix(['.', ')']).unwrap_or_default();
 
-            let is_bullet = ["-", "*", "•"].contains(word)
-                || (word.ends_with(['.', ')'])
-                    && word.len() > 1
-                    && word
-                        .chars()
-                        .take(word.len() - 1)
-                        .all(|c| c.is_ascii_digit()));
+        let is_bullet = self.bullet_markers.iter().any(|marker| marker == word)
+            || (!ordinal.is_empty() && ordinal.chars().all(|c| c.is_ascii_digit()))
+            || (self.alphabetic_bullets
+                && ordinal.len() == 1
+                && ordinal.chars().all(|c| c.is_ascii_alphabetic()));
 
-            is_bullet.then_some(*word)
+        is_bullet.then(|| {
+            self.tokens.next();
+            word
         })
     }
 
-    fn words(&mut self) -> (Vec<&'t str>, bool) {
+    /// Split a reStructuredText field marker, e.g., `:param foo:`, off the
+    /// start of a line's words, to be treated like a bullet. See
+    /// [Toppings::restructured_text].
+    fn field(
+        &self,
+        words: &mut Vec<&'t str>,
+        gaps: &mut Vec<(usize, Whitespace)>,
+    ) -> Option<&'t str> {
+        // Field names may consist of a few words, e.g., `:param int foo:`.
+        const MAX_FIELD_WORDS: usize = 3;
+
+        let first = words.first()?;
+        if !first.starts_with(':') {
+            return None;
+        }
+
+        let last_idx = words
+            .iter()
+            .take(MAX_FIELD_WORDS)
+            .position(|word| word.ends_with(':'))?;
+
+        let offset = |word: &str| word.as_ptr() as usize - self.input.as_ptr() as usize;
+        let start_idx = offset(first);
+        let end_idx = offset(words[last_idx]) + words[last_idx].len();
+
+        let marker = &self.input[start_idx..end_idx];
+        if marker.len() < 3 || marker[1..marker.len() - 1].trim().is_empty() {
+            return None;
+        }
+
+        let removed = last_idx + 1;
+        words.drain(..removed);
+        gaps.retain_mut(|(idx, _)| {
+            // Whitespace within the marker or right after it is no gap.
+            let keep = *idx > removed;
+            *idx = idx.saturating_sub(removed);
+            keep
+        });
+
+        Some(marker)
+    }
+
+    /// Join the words that each match of [Toppings::protect] touches into a
+    /// single word, along with the whitespace between them.
+    #[cfg(feature = "regex")]
+    fn protect(&self, words: &mut Vec<&'t str>, gaps: &mut Vec<(usize, Whitespace)>) {
+        let (Some(pattern), Some(first), Some(last)) = (&self.protect, words.first(), words.last())
+        else {
+            return;
+        };
+
+        let offset = |word: &str| word.as_ptr() as usize - self.input.as_ptr() as usize;
+        let start_idx = offset(first);
+        let text = &self.input[start_idx..offset(last) + last.len()];
+
+        for found in pattern.find_iter(text).filter(|found| !found.is_empty()) {
+            let (match_start, match_end) = (start_idx + found.start(), start_idx + found.end());
+            let first_idx = words.partition_point(|word| offset(word) + word.len() <= match_start);
+            let last_idx = words.partition_point(|word| offset(word) < match_end) - 1;
+
+            if first_idx >= last_idx {
+                continue;
+            }
+
+            let end_idx = offset(words[last_idx]) + words[last_idx].len();
+            let joined = &self.input[offset(words[first_idx])..end_idx];
+            words.splice(first_idx..=last_idx, [joined]);
+
+            let removed = last_idx - first_idx;
+            gaps.retain_mut(|(idx, _)| {
+                // Whitespace within the joined word is no gap.
+                let keep = *idx <= first_idx || *idx > last_idx;
+                if *idx > last_idx {
+                    *idx -= removed;
+                }
+                keep
+            });
+        }
+    }
+
+    fn words(&mut self) -> (Vec<&'t str>, Vec<(usize, Whitespace)>, usize, bool) {
         let mut words = Vec::new();
+        let mut gaps: Vec<(usize, Whitespace)> = Vec::new();
+        let mut end_idx = self.input.len();
+        let mut newline = false;
+
+        for (byte_idx, token) in self.tokens.by_ref() {
+            let whitespace = match token {
+                Token::Space => Whitespace::Space(1),
+                Token::Tab => Whitespace::Tab(1),
+                Token::Word(word) => {
+                    words.push(word);
+                    continue;
+                }
+                Token::Newline(_) => {
+                    end_idx = byte_idx;
+                    newline = true;
+                    break;
+                }
+            };
+
+            // Whitespace before the first word is not a gap between words.
+            if self.collapse_spaces || words.is_empty() {
+                continue;
+            }
 
-        for token in self.tokens.by_ref() {
-            match token {
-                Token::Space | Token::Tab => {}
-                Token::Word(word) => words.push(word),
-                Token::Newline(_) => return (words, true),
+            match gaps.last_mut() {
+                Some((idx, Whitespace::Space(count)))
+                    if *idx == words.len() && token == Token::Space =>
+                {
+                    *count += 1
+                }
+                Some((idx, Whitespace::Tab(count)))
+                    if *idx == words.len() && token == Token::Tab =>
+                {
+                    *count += 1
+                }
+                _ => gaps.push((words.len(), whitespace)),
             }
         }
 
-        (words, false)
+        // Neither is trailing whitespace.
+        while gaps.last().is_some_and(|(idx, _)| *idx == words.len()) {
+            gaps.pop();
+        }
+
+        (words, gaps, end_idx, newline)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Line, Token, Whitespace::*};
+    use crate::{Line, Token, Toppings, Whitespace::*};
     use crate::{line, tokens};
 
-    fn parse(tokens: Vec<Token>) -> Vec<Line> {
-        super::Parse::new(tokens.into_iter()).collect()
+    /// Tokens along with the input they would have been lexed from, such that
+    /// their words are slices of it.
+    struct Lexed {
+        input: String,
+        tokens: Vec<(usize, Token<'static>)>,
+    }
+
+    impl Lexed {
+        fn new(tokens: Vec<Token<'static>>) -> Self {
+            let mut input = String::new();
+            let mut indexed = Vec::with_capacity(tokens.len());
+
+            for token in tokens {
+                indexed.push((input.len(), token));
+                input.push_str(match token {
+                    Token::Space => " ",
+                    Token::Tab => "\t",
+                    Token::Newline(newline) => newline.as_str(),
+                    Token::Word(word) => word,
+                });
+            }
+
+            Self {
+                input,
+                tokens: indexed,
+            }
+        }
+
+        fn parse(&self) -> Vec<Line<'_>> {
+            self.parse_with(&Toppings::default())
+        }
+
+        fn parse_with(&self, toppings: &Toppings) -> Vec<Line<'_>> {
+            let tokens = self.tokens.iter().map(|&(idx, token)| match token {
+                Token::Word(word) => (idx, Token::Word(&self.input[idx..idx + word.len()])),
+                token => (idx, token),
+            });
+
+            super::Parse::new(&self.input, tokens, toppings).collect()
+        }
     }
 
     #[test]
     fn empty() {
-        assert_eq!(parse(tokens![]), vec![]);
+        assert_eq!(Lexed::new(tokens![]).parse(), vec![]);
     }
 
     #[test]
     fn single_space_indent() {
         assert_eq!(
-            parse(tokens![s]),
+            Lexed::new(tokens![s]).parse(),
             vec![line!(Space(1), None, Space(0), None)]
         );
     }
 
     #[test]
     fn single_tab_indent() {
-        assert_eq!(parse(tokens![t]), vec![line!(Tab(1), None, Space(0), None)]);
+        assert_eq!(
+            Lexed::new(tokens![t]).parse(),
+            vec![line!(Tab(1), None, Space(0), None)]
+        );
     }
 
     #[test]
     fn multiple_spaces_indent() {
         assert_eq!(
-            parse(tokens![s, s, s, s]),
+            Lexed::new(tokens![s, s, s, s]).parse(),
             vec![line!(Space(4), None, Space(0), None)]
         );
     }
@@ -158,7 +687,7 @@ mod tests {
     #[test]
     fn multiple_tabs_indent() {
         assert_eq!(
-            parse(tokens![t, t]),
+            Lexed::new(tokens![t, t]).parse(),
             vec![line!(Tab(2), None, Space(0), None)]
         );
     }
@@ -166,7 +695,7 @@ mod tests {
     #[test]
     fn spaces_then_tabs() {
         assert_eq!(
-            parse(tokens![s, s, s, t, t]),
+            Lexed::new(tokens![s, s, s, t, t]).parse(),
             vec![line!(Space(3), None, Tab(2), None)]
         );
     }
@@ -174,7 +703,7 @@ mod tests {
     #[test]
     fn tabs_then_spaces() {
         assert_eq!(
-            parse(tokens![t, t, t, s, s]),
+            Lexed::new(tokens![t, t, t, s, s]).parse(),
             vec![line!(Tab(3), None, Space(2), None)]
         );
     }
@@ -182,11 +711,11 @@ mod tests {
     #[test]
     fn comment_only() {
         assert_eq!(
-            parse(tokens!["#"]),
+            Lexed::new(tokens!["#"]).parse(),
             vec![line!(Space(0), Some("#"), Space(0), None)]
         );
         assert_eq!(
-            parse(tokens!["//"]),
+            Lexed::new(tokens!["//"]).parse(),
             vec![line!(Space(0), Some("//"), Space(0), None)]
         );
     }
@@ -194,11 +723,11 @@ mod tests {
     #[test]
     fn indented_comment() {
         assert_eq!(
-            parse(tokens![s, s, s, s, "#"]),
+            Lexed::new(tokens![s, s, s, s, "#"]).parse(),
             vec![line!(Space(4), Some("#"), Space(0), None)]
         );
         assert_eq!(
-            parse(tokens![t, "//"]),
+            Lexed::new(tokens![t, "//"]).parse(),
             vec![line!(Tab(1), Some("//"), Space(0), None)]
         );
     }
@@ -206,7 +735,7 @@ mod tests {
     #[test]
     fn indented_comment_and_padding() {
         assert_eq!(
-            parse(tokens![s, s, s, s, "#", t, s]),
+            Lexed::new(tokens![s, s, s, s, "#", t, s]).parse(),
             vec![line!(Space(4), Some("#"), Tab(1), None)]
         );
     }
@@ -214,11 +743,11 @@ mod tests {
     #[test]
     fn bullets() {
         assert_eq!(
-            parse(tokens!["-"]),
+            Lexed::new(tokens!["-"]).parse(),
             vec![line!(Space(0), None, Space(0), Some("-"))]
         );
         assert_eq!(
-            parse(tokens!["123."]),
+            Lexed::new(tokens!["123."]).parse(),
             vec![line!(Space(0), None, Space(0), Some("123."))]
         );
     }
@@ -226,11 +755,11 @@ mod tests {
     #[test]
     fn indented_bullets() {
         assert_eq!(
-            parse(tokens![s, s, s, s, "-"]),
+            Lexed::new(tokens![s, s, s, s, "-"]).parse(),
             vec![line!(Space(4), None, Space(0), Some("-"))]
         );
         assert_eq!(
-            parse(tokens![t, "123."]),
+            Lexed::new(tokens![t, "123."]).parse(),
             vec![line!(Tab(1), None, Space(0), Some("123."))]
         );
     }
@@ -238,7 +767,7 @@ mod tests {
     #[test]
     fn comment_and_bullet() {
         assert_eq!(
-            parse(tokens![t, "//", s, "-"]),
+            Lexed::new(tokens![t, "//", s, "-"]).parse(),
             vec![line!(Tab(1), Some("//"), Space(1), Some("-"))]
         );
     }
@@ -246,7 +775,7 @@ mod tests {
     #[test]
     fn words() {
         assert_eq!(
-            parse(tokens!["foo", s, s, "bar", t, "baz"]),
+            Lexed::new(tokens!["foo", s, s, "bar", t, "baz"]).parse(),
             vec![line!(Space(0), None, Space(0), None, "foo", "bar", "baz")]
         );
     }
@@ -254,9 +783,10 @@ mod tests {
     #[test]
     fn all_together() {
         assert_eq!(
-            parse(tokens![
+            Lexed::new(tokens![
                 t, t, "//", s, s, s, "-", s, s, "foo", s, s, "bar", t, "baz"
-            ]),
+            ])
+            .parse(),
             vec![line!(
                 Tab(2),
                 Some("//"),
@@ -272,7 +802,7 @@ mod tests {
     #[test]
     fn newlines() {
         assert_eq!(
-            parse(tokens!["foo", "bar", lf, crlf, "baz"]),
+            Lexed::new(tokens!["foo", "bar", lf, crlf, "baz"]).parse(),
             vec![
                 line!(Space(0), None, Space(0), None, "foo", "bar" ;),
                 line!(Space(0), None, Space(0), None ;),
@@ -280,4 +810,591 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn mixed_newlines() {
+        assert_eq!(
+            Lexed::new(tokens!["a", lf, crlf, lf, "b"]).parse(),
+            vec![
+                line!(Space(0), None, Space(0), None, "a" ;),
+                line!(Space(0), None, Space(0), None ;),
+                line!(Space(0), None, Space(0), None ;),
+                line!(Space(0), None, Space(0), None, "b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn gaps() {
+        assert_eq!(
+            Lexed::new(tokens![s, "foo", s, s, "bar", t, s, "baz", s, lf])
+                .parse_with(&Toppings::default().collapse_spaces(false)),
+            vec![Line {
+                gaps: vec![(1, Space(2)), (2, Tab(1)), (2, Space(1))],
+                ..line!(Space(1), None, Space(0), None, "foo", "bar", "baz" ;)
+            }]
+        );
+    }
+
+    #[test]
+    fn overflow_only() {
+        assert_eq!(
+            Lexed::new(tokens![
+                "#", s, "foo", s, s, "bar", lf, "#", s, "foo", s, "barbaz", lf, t, "foo"
+            ])
+            .parse_with(&Toppings::default().width(10).overflow_only(true)),
+            vec![
+                Line {
+                    verbatim: Some("# foo  bar"),
+                    ..line!(Space(0), Some("#"), Space(1), None, "foo", "bar" ;)
+                },
+                line!(Space(0), Some("#"), Space(1), None, "foo", "barbaz" ;),
+                Line {
+                    verbatim: Some("\tfoo"),
+                    ..line!(Tab(1), None, Space(0), None, "foo")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn horizontal_rules() {
+        assert_eq!(
+            Lexed::new(tokens!["---", lf, "***", lf, "___"]).parse(),
+            vec![
+                Line {
+                    verbatim: Some("---"),
+                    ..line!(Space(0), None, Space(0), None, "---" ;)
+                },
+                Line {
+                    verbatim: Some("***"),
+                    ..line!(Space(0), None, Space(0), None, "***" ;)
+                },
+                Line {
+                    verbatim: Some("___"),
+                    ..line!(Space(0), None, Space(0), None, "___")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn horizontal_rule_comment_bullet() {
+        assert_eq!(
+            Lexed::new(tokens!["---", lf, "--", s, "comment", lf, "-", s, "item"]).parse(),
+            vec![
+                Line {
+                    verbatim: Some("---"),
+                    ..line!(Space(0), None, Space(0), None, "---" ;)
+                },
+                line!(Space(0), Some("--"), Space(1), None, "comment" ;),
+                line!(Space(0), None, Space(0), Some("-"), "item"),
+            ]
+        );
+    }
+
+    #[test]
+    fn not_horizontal_rules() {
+        assert_eq!(
+            Lexed::new(tokens!["--", lf, "-*-", lf, "foo", s, "---"]).parse(),
+            vec![
+                line!(Space(0), Some("--"), Space(0), None ;),
+                line!(Space(0), None, Space(0), None, "-*-" ;),
+                line!(Space(0), None, Space(0), None, "foo", "---"),
+            ]
+        );
+        assert_eq!(
+            Lexed::new(tokens!["---"]).parse_with(&Toppings::default().horizontal_rules(false)),
+            vec![line!(Space(0), None, Space(0), None, "---")]
+        );
+    }
+
+    #[test]
+    fn punctuation_lines() {
+        assert_eq!(
+            Lexed::new(tokens![
+                "...", lf, "#", s, "!!!", s, "?", lf, "foo", s, "!!!", lf, "-", s, "..."
+            ])
+            .parse_with(&Toppings::default().punctuation_lines(true)),
+            vec![
+                Line {
+                    verbatim: Some("..."),
+                    ..line!(Space(0), None, Space(0), None, "..." ;)
+                },
+                Line {
+                    verbatim: Some("# !!! ?"),
+                    ..line!(Space(0), Some("#"), Space(1), None, "!!!", "?" ;)
+                },
+                line!(Space(0), None, Space(0), None, "foo", "!!!" ;),
+                line!(Space(0), None, Space(0), Some("-"), "..."),
+            ]
+        );
+    }
+
+    #[test]
+    fn line_numbers() {
+        assert_eq!(
+            Lexed::new(tokens![
+                s, "12", s, s, "//", s, "foo", lf, "13", lf, "14.", s, "bar"
+            ])
+            .parse_with(&Toppings::default().line_numbers(crate::Gutter::Blank)),
+            vec![
+                Line {
+                    gutter: Some(" 12"),
+                    ..line!(Space(2), Some("//"), Space(1), None, "foo" ;)
+                },
+                Line {
+                    gutter: Some("13"),
+                    ..line!(Space(0), None, Space(0), None ;)
+                },
+                line!(Space(0), None, Space(0), Some("14."), "bar"),
+            ]
+        );
+        assert_eq!(
+            Lexed::new(tokens!["12", s, "foo"]).parse(),
+            vec![line!(Space(0), None, Space(0), None, "12", "foo")]
+        );
+    }
+
+    #[test]
+    fn restructured_text() {
+        let toppings = Toppings::default().restructured_text(true);
+
+        assert_eq!(
+            Lexed::new(tokens![
+                ":param",
+                s,
+                "x:",
+                s,
+                "desc",
+                lf,
+                ":",
+                s,
+                "desc",
+                lf,
+                "..",
+                s,
+                "note::",
+                lf,
+                ":returns:"
+            ])
+            .parse_with(&toppings),
+            vec![
+                line!(Space(0), None, Space(0), Some(":param x:"), "desc" ;),
+                line!(Space(0), None, Space(0), None, ":", "desc" ;),
+                Line {
+                    verbatim: Some(".. note::"),
+                    ..line!(Space(0), None, Space(0), None, "..", "note::" ;)
+                },
+                line!(Space(0), None, Space(0), Some(":returns:")),
+            ]
+        );
+    }
+
+    #[test]
+    fn restructured_text_not_fields() {
+        let toppings = Toppings::default().restructured_text(true);
+
+        assert_eq!(
+            Lexed::new(tokens![
+                "::",
+                s,
+                "foo",
+                lf,
+                ":ref:`foo`",
+                s,
+                "bar",
+                lf,
+                ":)",
+                s,
+                "a",
+                s,
+                "b",
+                s,
+                "c:"
+            ])
+            .parse_with(&toppings),
+            vec![
+                line!(Space(0), None, Space(0), None, "::", "foo" ;),
+                line!(Space(0), None, Space(0), None, ":ref:`foo`", "bar" ;),
+                line!(Space(0), None, Space(0), None, ":)", "a", "b", "c:"),
+            ]
+        );
+        assert_eq!(
+            Lexed::new(tokens![":param", s, "x:", s, "desc"]).parse(),
+            vec![line!(
+                Space(0),
+                None,
+                Space(0),
+                None,
+                ":param",
+                "x:",
+                "desc"
+            )]
+        );
+    }
+
+    #[test]
+    fn restructured_text_gaps() {
+        assert_eq!(
+            Lexed::new(tokens![":param", s, s, "x:", s, s, "foo", s, s, "bar"]).parse_with(
+                &Toppings::default()
+                    .restructured_text(true)
+                    .collapse_spaces(false)
+            ),
+            vec![Line {
+                gaps: vec![(1, Space(2))],
+                ..line!(Space(0), None, Space(0), Some(":param  x:"), "foo", "bar")
+            }]
+        );
+    }
+
+    #[test]
+    fn block_comments() {
+        assert_eq!(
+            Lexed::new(tokens![
+                "/**", lf, s, "*", s, "foo", lf, s, "*", s, "-", s, "bar", lf, s, "*/", lf, "*", s,
+                "baz"
+            ])
+            .parse_with(&Toppings::default().block_comments(true)),
+            vec![
+                line!(Space(0), None, Space(0), None, "/**" ;),
+                line!(Space(1), Some("*"), Space(1), None, "foo" ;),
+                line!(Space(1), Some("*"), Space(1), Some("-"), "bar" ;),
+                line!(Space(1), None, Space(0), None, "*/" ;),
+                line!(Space(0), None, Space(0), Some("*"), "baz"),
+            ]
+        );
+    }
+
+    #[test]
+    fn block_comments_disabled() {
+        assert_eq!(
+            Lexed::new(tokens!["/*", lf, s, "*", s, "foo"]).parse(),
+            vec![
+                line!(Space(0), None, Space(0), None, "/*" ;),
+                line!(Space(1), None, Space(0), Some("*"), "foo"),
+            ]
+        );
+    }
+
+    #[test]
+    fn exact_indent() {
+        assert_eq!(
+            Lexed::new(tokens![t, s, s, t, "foo", lf, s, s, "#", s, "bar"])
+                .parse_with(&Toppings::default().exact_indent(true)),
+            vec![
+                Line {
+                    raw_indent: Some("\t  \t"),
+                    ..line!(Space(10), None, Space(0), None, "foo" ;)
+                },
+                line!(Space(2), Some("#"), Space(1), None, "bar"),
+            ]
+        );
+        assert_eq!(
+            Lexed::new(tokens![t, s, s, t, "foo"]).parse(),
+            vec![line!(Tab(1), None, Space(2), None, "foo")]
+        );
+    }
+
+    #[test]
+    fn diff() {
+        assert_eq!(
+            Lexed::new(tokens![
+                "@@", lf, "-foo", lf, "-", s, "bar", lf, s, "baz", lf, "qux"
+            ])
+            .parse_with(&Toppings::default().diff(true)),
+            vec![
+                Line {
+                    verbatim: Some("@@"),
+                    ..line!(Space(0), None, Space(0), None, "@@" ;)
+                },
+                Line {
+                    verbatim: Some("-foo"),
+                    ..line!(Space(0), None, Space(0), None, "-foo" ;)
+                },
+                Line {
+                    verbatim: Some("- bar"),
+                    ..line!(Space(0), None, Space(0), None, "-", "bar" ;)
+                },
+                Line {
+                    verbatim: Some(" baz"),
+                    ..line!(Space(1), None, Space(0), None, "baz" ;)
+                },
+                line!(Space(0), None, Space(0), None, "qux"),
+            ]
+        );
+    }
+
+    #[test]
+    fn trailing_space() {
+        let toppings = Toppings::default().trailing_space_breaks(true);
+
+        assert_eq!(
+            Lexed::new(tokens!["foo", s, lf, "bar", lf, s, lf]).parse_with(&toppings),
+            vec![
+                Line {
+                    trailing_space: Some(""),
+                    ..line!(Space(0), None, Space(0), None, "foo" ;)
+                },
+                line!(Space(0), None, Space(0), None, "bar" ;),
+                line!(Space(1), None, Space(0), None ;),
+            ]
+        );
+        assert_eq!(
+            Lexed::new(tokens!["foo", s, lf, "bar"]).parse(),
+            vec![
+                line!(Space(0), None, Space(0), None, "foo" ;),
+                line!(Space(0), None, Space(0), None, "bar"),
+            ]
+        );
+    }
+
+    #[test]
+    fn paragraph_separator() {
+        let toppings = Toppings::default().paragraph_separator(Some("%%".to_owned()));
+
+        assert_eq!(
+            Lexed::new(tokens!["foo", lf, "%%", lf, s, "%%", lf, "bar"]).parse_with(&toppings),
+            vec![
+                line!(Space(0), None, Space(0), None, "foo" ;),
+                Line {
+                    verbatim: Some("%%"),
+                    ..line!(Space(0), None, Space(0), None, "%%" ;)
+                },
+                line!(Space(1), None, Space(0), None, "%%" ;),
+                line!(Space(0), None, Space(0), None, "bar"),
+            ]
+        );
+        assert_eq!(
+            Lexed::new(tokens!["foo", lf, "%%", lf, "bar"])
+                .parse_with(&toppings.drop_paragraph_separators(true)),
+            vec![
+                line!(Space(0), None, Space(0), None, "foo" ;),
+                Line {
+                    verbatim: Some(""),
+                    ..line!(Space(0), None, Space(0), None)
+                },
+                line!(Space(0), None, Space(0), None, "bar"),
+            ]
+        );
+    }
+
+    #[test]
+    fn comment_tokens() {
+        assert_eq!(
+            Lexed::new(tokens!["%", s, "foo", lf, "#", s, "bar"])
+                .parse_with(&Toppings::default().comment_tokens(&["%", "--"])),
+            vec![
+                line!(Space(0), Some("%"), Space(1), None, "foo" ;),
+                line!(Space(0), None, Space(0), None, "#", "bar"),
+            ]
+        );
+    }
+
+    #[test]
+    fn bullet_markers() {
+        assert_eq!(
+            Lexed::new(tokens![
+                "+", s, "foo", lf, "-", s, "bar", lf, "a.", s, "baz", lf, "12)", s, "qux"
+            ])
+            .parse_with(&Toppings::default().bullet_markers(&["+"])),
+            vec![
+                line!(Space(0), None, Space(0), Some("+"), "foo" ;),
+                line!(Space(0), None, Space(0), None, "-", "bar" ;),
+                line!(Space(0), None, Space(0), None, "a.", "baz" ;),
+                line!(Space(0), None, Space(0), Some("12)"), "qux"),
+            ]
+        );
+    }
+
+    #[test]
+    fn alphabetic_bullets() {
+        assert_eq!(
+            Lexed::new(tokens![
+                "a.", s, "foo", lf, "Z)", s, "bar", lf, "ab.", s, "baz", lf, "é.", s, "qux"
+            ])
+            .parse_with(&Toppings::default().alphabetic_bullets(true)),
+            vec![
+                line!(Space(0), None, Space(0), Some("a."), "foo" ;),
+                line!(Space(0), None, Space(0), Some("Z)"), "bar" ;),
+                line!(Space(0), None, Space(0), None, "ab.", "baz" ;),
+                line!(Space(0), None, Space(0), None, "é.", "qux"),
+            ]
+        );
+    }
+
+    #[test]
+    fn preserve_trailing_whitespace() {
+        assert_eq!(
+            Lexed::new(tokens!["foo", s, s, lf, s, t, lf, "bar", s, t])
+                .parse_with(&Toppings::default().preserve_trailing_whitespace(true)),
+            vec![
+                Line {
+                    trailing_space: Some("  "),
+                    ..line!(Space(0), None, Space(0), None, "foo" ;)
+                },
+                line!(Space(1), None, Tab(1), None ;),
+                Line {
+                    trailing_space: Some(" \t"),
+                    ..line!(Space(0), None, Space(0), None, "bar")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn indent_unit() {
+        let tokens = || tokens![s, s, s, "foo", lf, t, "bar", lf, s, "#", s, s, s, "baz"];
+
+        assert_eq!(
+            Lexed::new(tokens()).parse_with(&Toppings::default().indent_unit(2)),
+            vec![
+                line!(Space(4), None, Space(0), None, "foo" ;),
+                line!(Tab(1), None, Space(0), None, "bar" ;),
+                line!(Space(2), Some("#"), Space(3), None, "baz"),
+            ]
+        );
+
+        assert_eq!(
+            Lexed::new(tokens())
+                .parse_with(&Toppings::default().indent_unit(3).tabs(4).expand_tabs(true)),
+            vec![
+                line!(Space(3), None, Space(0), None, "foo" ;),
+                line!(Space(3), None, Space(0), None, "bar" ;),
+                line!(Space(0), Some("#"), Space(3), None, "baz"),
+            ]
+        );
+    }
+
+    #[test]
+    fn indent_unit_list_items() {
+        // Continuation lines move along with the bullet of their list item.
+        let tokens = || tokens![s, "-", s, "foo", lf, s, s, s, "bar", lf, lf, s, s, s, "baz"];
+
+        assert_eq!(
+            Lexed::new(tokens()).parse_with(&Toppings::default().indent_unit(4)),
+            vec![
+                line!(Space(0), None, Space(0), Some("-"), "foo" ;),
+                line!(Space(2), None, Space(0), None, "bar" ;),
+                line!(Space(0), None, Space(0), None ;),
+                line!(Space(2), None, Space(0), None, "baz"),
+            ]
+        );
+
+        let tokens = || tokens!["1.", s, "foo", lf, s, s, s, "bar", lf, s, s, "baz"];
+
+        assert_eq!(
+            Lexed::new(tokens()).parse_with(&Toppings::default().indent_unit(2)),
+            vec![
+                line!(Space(0), None, Space(0), Some("1."), "foo" ;),
+                line!(Space(3), None, Space(0), None, "bar" ;),
+                line!(Space(2), None, Space(0), None, "baz"),
+            ]
+        );
+    }
+
+    #[test]
+    fn markdown_hard_breaks() {
+        let hard_break = |line| Line {
+            trailing_space: Some(""),
+            ..line
+        };
+
+        assert_eq!(
+            Lexed::new(tokens![
+                "foo", s, s, lf, "bar\\", lf, "baz\\\\", lf, "qux", s, lf, "\\"
+            ])
+            .parse_with(&Toppings::default().markdown(true)),
+            vec![
+                Line {
+                    trailing_space: Some("  "),
+                    ..line!(Space(0), None, Space(0), None, "foo" ;)
+                },
+                hard_break(line!(Space(0), None, Space(0), None, "bar\\" ;)),
+                line!(Space(0), None, Space(0), None, "baz\\\\" ;),
+                line!(Space(0), None, Space(0), None, "qux" ;),
+                hard_break(line!(Space(0), None, Space(0), None, "\\")),
+            ]
+        );
+    }
+
+    #[test]
+    fn continuation() {
+        assert_eq!(
+            Lexed::new(tokens![
+                "foo", s, "\\", lf, "bar\\", s, "\\", s, "baz", lf, "\\"
+            ])
+            .parse_with(&Toppings::default().continuation("\\")),
+            vec![
+                Line {
+                    continuation: Some("\\"),
+                    ..line!(Space(0), None, Space(0), None, "foo" ;)
+                },
+                line!(Space(0), None, Space(0), None, "bar\\", "\\", "baz" ;),
+                Line {
+                    continuation: Some("\\"),
+                    ..line!(Space(0), None, Space(0), None)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn no_comment_tokens() {
+        assert_eq!(
+            Lexed::new(tokens!["//", s, "-", s, "foo"])
+                .parse_with(&Toppings::default().comment_tokens(&[])),
+            vec![line!(Space(0), None, Space(0), None, "//", "-", "foo")]
+        );
+    }
+
+    #[test]
+    fn metadata() {
+        assert_eq!(
+            Lexed::new(tokens![
+                "Title:", s, "foo", lf, "Key-2:", lf, "bar:", s, "baz", lf, "a", s, "b:"
+            ])
+            .parse_with(&Toppings::default().metadata(true)),
+            vec![
+                Line {
+                    verbatim: Some("Title: foo"),
+                    ..line!(Space(0), None, Space(0), None, "Title:", "foo" ;)
+                },
+                Line {
+                    verbatim: Some("Key-2:"),
+                    ..line!(Space(0), None, Space(0), None, "Key-2:" ;)
+                },
+                Line {
+                    verbatim: Some("bar: baz"),
+                    ..line!(Space(0), None, Space(0), None, "bar:", "baz" ;)
+                },
+                line!(Space(0), None, Space(0), None, "a", "b:"),
+            ]
+        );
+    }
+
+    #[test]
+    fn metadata_only_at_start() {
+        assert_eq!(
+            Lexed::new(tokens!["foo", lf, "Title:", s, "bar"])
+                .parse_with(&Toppings::default().metadata(true)),
+            vec![
+                line!(Space(0), None, Space(0), None, "foo" ;),
+                line!(Space(0), None, Space(0), None, "Title:", "bar"),
+            ]
+        );
+        assert_eq!(
+            Lexed::new(tokens!["Title:", s, "foo", lf, lf, "Date:", s, "now"])
+                .parse_with(&Toppings::default().metadata(true)),
+            vec![
+                Line {
+                    verbatim: Some("Title: foo"),
+                    ..line!(Space(0), None, Space(0), None, "Title:", "foo" ;)
+                },
+                line!(Space(0), None, Space(0), None ;),
+                line!(Space(0), None, Space(0), None, "Date:", "now"),
+            ]
+        );
+    }
 }
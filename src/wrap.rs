@@ -1,24 +1,100 @@
 use std::collections::HashSet;
+use std::iter;
+use std::ops::Range;
 
-use unicode_width::UnicodeWidthStr;
+use unicode_segmentation::UnicodeSegmentation;
 
-use super::{Line, Newline, Toppings, Whitespace};
+use super::parse::{str_width, text_width};
+use super::{Align, Fill, Gutter, KeepRule, Line, Newline, Toppings, Whitespace};
 
 /// A line breaking algorithm.
+///
+/// A sauce is prepared once per paragraph, and then asked about each of its
+/// words in order. Other than [Sauce::prepare], methods take `&mut self`, so a
+/// prepared sauce may be used as a trait object, e.g., `Box<dyn Sauce>`.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{Guacamole, Salsa, Sauce, Toppings};
+///
+/// let words = ["foo", "bar", "baz"];
+/// let toppings = Toppings::default();
+///
+/// let sauces: [Box<dyn Sauce>; 2] = [
+///     Box::new(Salsa::prepare(&words, 7, &toppings)),
+///     Box::new(Guacamole::prepare(&words, 7, &toppings)),
+/// ];
+///
+/// for mut sauce in sauces {
+///     let breaks = (0..words.len())
+///         .filter(|idx| sauce.should_break(&words, *idx) && *idx != 0)
+///         .collect::<Vec<_>>();
+///     assert_eq!(breaks, vec![2]);
+/// }
+/// ```
 pub trait Sauce {
-    fn prepare(words: &[&str], max: usize) -> Self;
+    /// Get ready to break `words` into lines of maximum width `max`.
+    /// `toppings` are the ones wrapping is configured with, e.g., to tell
+    /// whether characters of ambiguous width count as wide (see
+    /// [Toppings::cjk]). `max` already accounts for indentation, comment token
+    /// and bullet, and is what the algorithm should adhere to rather than
+    /// [Toppings::width].
+    fn prepare(words: &[&str], max: usize, toppings: &Toppings) -> Self
+    where
+        Self: Sized;
+
+    /// Whether to break the line right before `words[idx]`. Called for each
+    /// word in order, starting at index 0, where the answer is ignored.
     fn should_break(&mut self, words: &[&str], idx: usize) -> bool;
+
+    /// Change the maximum width of the lines following the most recent break.
+    /// Algorithms that decide on all breaks in [Sauce::prepare] may ignore
+    /// this, which is what the default implementation does.
+    fn resize(&mut self, _max: usize) {}
+
+    /// Whether the algorithm decides on breaks within words itself, weighing
+    /// each against [Toppings::intra_word_penalty]. If so, and the penalty is
+    /// set, it is prepared with [Sauce::prepare_joined] rather than
+    /// [Sauce::prepare]. False by default.
+    fn breaks_words() -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
+
+    /// Like [Sauce::prepare], where `joined[idx]` tells whether `words[idx]`
+    /// continues the word before it without a space in between, i.e., both
+    /// are grapheme clusters of a single word. The default implementation
+    /// ignores `joined`.
+    fn prepare_joined(words: &[&str], _joined: &[bool], max: usize, toppings: &Toppings) -> Self
+    where
+        Self: Sized,
+    {
+        Self::prepare(words, max, toppings)
+    }
 }
 
 /// Naive "first-fit" line breaking algorithm.
 ///
-/// Doesn't produce optimal results, but time complexity is O(n) and space
-/// complexity is O(1).
+/// Doesn't produce optimal results, but time and space complexity are O(n).
+/// Each break is decided on as soon as the word following it is seen, based on
+/// nothing but the width of the current line, while the breaks of a paragraph
+/// are kept until it has been wrapped.
 ///
 /// Also see: <https://en.wikipedia.org/wiki/Wrapping_(text)#Minimum_number_of_lines>
 pub struct Guacamole {
     max: usize,
-    width: usize,
+    cjk: bool,
+    /// Width of the current line, if it holds any words yet.
+    width: Option<usize>,
+    /// Which words continue the one before them, see [Sauce::prepare_joined].
+    /// Empty unless words were broken apart.
+    joined: Vec<bool>,
+    /// Width of the hyphen ending a line within a word, see
+    /// [Toppings::hyphenate].
+    hyphen: usize,
 }
 
 /// More sophisticated "optimal-fit" line breaking algorithm.
@@ -26,496 +102,4290 @@ pub struct Guacamole {
 /// Time complexity is O(n^2), space complexity is O(n). This is fast enough
 /// for inputs of common size (i.e., reasonably sized paragraphs in a plain text
 /// document or code file). This is the default algorithm used by the tortilla
-/// CLI.
+/// CLI for paragraphs of common size, see [Auto].
+///
+/// If several sets of breaks are equally ragged, the one with fewer lines wins.
+/// Any remaining tie is broken in favor of the earliest breaks, starting from
+/// the last line (i.e., later lines are filled up first).
+///
+/// Breaks are decided on for the entire paragraph up front. If the maximum
+/// width changes (see [Sauce::resize]), the lines following the most recent
+/// break are decided on again.
 ///
 /// Also see:
 /// - <https://en.wikipedia.org/wiki/Wrapping_(text)#Minimum_raggedness>
 /// - <https://en.wikipedia.org/wiki/Knuth%E2%80%93Plass_line-breaking_algorithm>
-pub struct Salsa(HashSet<usize>);
+pub struct Salsa(Plan);
+
+/// Variant of [Salsa] with configurable penalties for the raggedness of lines,
+/// see [Penalties] and [Toppings::penalties].
+///
+/// Lines that exceed the maximum width may be allowed at a cost, such that a
+/// slightly overfull line can win over a very underfull one. With the default
+/// penalties, breaks are the same as those of [Salsa]. Time complexity is
+/// O(n^2), space complexity is O(n).
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{wrap, Mole, Penalties, Salsa, Toppings};
+///
+/// let input = "aaaa bbbb cc";
+/// let toppings = Toppings::default().width(9);
+///
+/// let salsa = wrap::<Salsa>(input, toppings.clone()).collect::<String>();
+/// let mole = wrap::<Mole>(input, toppings.clone()).collect::<String>();
+/// assert_eq!(salsa, "aaaa bbbb\ncc");
+/// assert_eq!(mole, salsa);
+///
+/// // Count the last line like any other.
+/// let penalties = Penalties {
+///     last_line: 1,
+///     ..Penalties::default()
+/// };
+/// let mole = wrap::<Mole>(input, toppings.penalties(penalties)).collect::<String>();
+/// assert_eq!(mole, "aaaa\nbbbb cc");
+/// ```
+///
+/// Also see: <https://en.wikipedia.org/wiki/Knuth%E2%80%93Plass_line-breaking_algorithm>
+pub struct Mole(Plan);
+
+/// Penalties for the raggedness of lines broken by [Mole], see
+/// [Toppings::penalties].
+///
+/// Each line costs the square of the columns it falls short of or exceeds the
+/// maximum width by, multiplied by the respective penalty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Penalties {
+    /// Penalty for lines shorter than the maximum width, except for the last
+    /// one. 1 by default.
+    pub underfull: usize,
+    /// Penalty for lines longer than the maximum width, or [None] if such
+    /// lines are never allowed. A single word that exceeds the maximum width
+    /// by itself costs nothing either way. [None] by default.
+    pub overfull: Option<usize>,
+    /// Penalty for the last line being shorter than the maximum width. 0 by
+    /// default, i.e., the last line may be as short as it likes.
+    pub last_line: usize,
+}
+
+impl Default for Penalties {
+    fn default() -> Self {
+        Self {
+            underfull: 1,
+            overfull: None,
+            last_line: 0,
+        }
+    }
+}
+
+/// Variant of [Salsa] that never uses more lines than [Guacamole] would.
+///
+/// [Salsa] may trade an additional line for less raggedness overall. This
+/// algorithm first determines the minimum number of lines, and then finds the
+/// least ragged set of breaks among those with exactly that many lines.
+///
+/// Time complexity is O(n^2 * l), where l is the number of lines, and space
+/// complexity is O(n * l). Ties and changes of the maximum width are handled
+/// the same way as in [Salsa].
+pub struct SalsaMinLines(Plan);
+
+/// Line breaking algorithm that minimizes the width of the widest line, while
+/// using no more lines than [Guacamole] would, e.g., to fit text into the
+/// narrowest possible column.
+///
+/// The narrowest width is found by binary search, with lines filled up first-fit
+/// at that width. Time complexity is O(n * log w), where w is the width of all
+/// words on a single line, and space complexity is O(n). Changes of the maximum
+/// width are handled the same way as in [Salsa].
+///
+/// Also see: <https://en.wikipedia.org/wiki/Linear_partitioning>
+pub struct Minimax(Plan);
+
+/// Places exactly [Toppings::words_per_line] words on each line (except for
+/// the last one, which may hold fewer), regardless of their width, e.g., for
+/// interlinear text.
+///
+/// The maximum line width is ignored entirely.
+pub struct WordsPerLine(usize);
+
+/// Uses [Salsa] for paragraphs of fewer than `THRESHOLD` words, and
+/// [Guacamole] for longer ones, to bound the cost of optimal-fit line breaking
+/// on huge paragraphs. This is the default algorithm used by the tortilla CLI.
+///
+/// The default threshold of 1000 words is well beyond the length of common
+/// paragraphs, such that most text gets optimal breaks.
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{wrap, Auto, Toppings};
+///
+/// let small = "a b c d e f g h i j k l m n o p qqqqqqqqq";
+/// let huge = "a b c d e f g h i j k l m n o p qqqqqqqqq r";
+///
+/// let toppings = Toppings::default().width(10);
+/// let output = wrap::<Auto<18>>(&format!("{small}\n\n{huge}"), toppings)
+///     .collect::<String>();
+///
+/// // The first paragraph is broken by Salsa, the second one by Guacamole.
+/// assert_eq!(output, "\
+/// a b c d
+/// e f g h
+/// i j k l
+/// m n o p
+/// qqqqqqqqq
+///
+/// a b c d e
+/// f g h i j
+/// k l m n o
+/// p
+/// qqqqqqqqq
+/// r");
+/// ```
+pub struct Auto<const THRESHOLD: usize = 1000>(Choice);
+
+/// The algorithm chosen by [Auto] for a paragraph.
+enum Choice {
+    Salsa(Salsa),
+    Guacamole(Guacamole),
+}
+
+/// Prefix sums of word widths, such that the width of words `a..b` is
+/// `offsets[b] - offsets[a]`.
+fn offsets(words: &[&str], cjk: bool) -> Vec<usize> {
+    let mut offsets = vec![0; words.len() + 1];
+    for (idx, word) in words.iter().enumerate() {
+        offsets[idx + 1] = offsets[idx] + str_width(word, cjk);
+    }
+    offsets
+}
+
+/// Width of a line consisting of words `start..end`, including spaces.
+fn line_length(offsets: &[usize], start: usize, end: usize) -> usize {
+    offsets[end] - offsets[start] + end - start - 1
+}
+
+/// Decides on the breaks for words described by prefix sums of their widths,
+/// given a maximum width, see [Plan].
+type DecideFn = dyn Fn(&[usize], usize) -> Vec<usize>;
+
+/// Breaks decided on up front for all words described by `offsets`, which are
+/// decided on again for the remaining words if the maximum width changes.
+struct Plan {
+    offsets: Vec<usize>,
+    breaks: HashSet<usize>,
+    decide: Box<DecideFn>,
+    max: usize,
+    last_break: usize,
+}
+
+impl Plan {
+    fn new(
+        words: &[&str],
+        max: usize,
+        cjk: bool,
+        decide: impl Fn(&[usize], usize) -> Vec<usize> + 'static,
+    ) -> Self {
+        let offsets = offsets(words, cjk);
+        let breaks = decide(&offsets, max).into_iter().collect();
+
+        Self {
+            offsets,
+            breaks,
+            decide: Box::new(decide),
+            max,
+            last_break: 0,
+        }
+    }
+
+    fn should_break(&mut self, idx: usize) -> bool {
+        let should_break = self.breaks.contains(&idx);
+        if should_break {
+            self.last_break = idx;
+        }
+        should_break
+    }
+
+    fn resize(&mut self, max: usize) {
+        if max == self.max {
+            return;
+        }
+
+        let last_break = self.last_break;
+        let base = self.offsets[last_break];
+        let remaining = self.offsets[last_break..]
+            .iter()
+            .map(|offset| offset - base)
+            .collect::<Vec<_>>();
+
+        self.max = max;
+        self.breaks.retain(|idx| *idx <= last_break);
+        self.breaks.extend(
+            (self.decide)(&remaining, max)
+                .into_iter()
+                .map(|idx| idx + last_break),
+        );
+    }
+}
 
 impl Sauce for Guacamole {
-    fn prepare(_: &[&str], max: usize) -> Self {
-        Self { max, width: 0 }
+    fn prepare(_: &[&str], max: usize, toppings: &Toppings) -> Self {
+        Self {
+            max,
+            cjk: toppings.cjk,
+            width: None,
+            joined: Vec::new(),
+            hyphen: 0,
+        }
     }
 
     fn should_break(&mut self, words: &[&str], idx: usize) -> bool {
-        let width = words[idx].width_cjk();
+        let width = str_width(words[idx], self.cjk);
+        let joined = self.joined.get(idx) == Some(&true);
+        // Room for a hyphen, in case the line ends within the word.
+        let hyphen = match self.joined.get(idx + 1) {
+            Some(true) => self.hyphen,
+            _ => 0,
+        };
+
+        // Words broken apart are only broken within if they don't fit on a
+        // line of their own.
+        let fragments = self.joined.get(idx + 1..).unwrap_or_default();
+        let whole = words[idx + 1..]
+            .iter()
+            .zip(fragments.iter().take_while(|joined| **joined))
+            .map(|(word, _)| str_width(word, self.cjk))
+            .fold(width, usize::saturating_add);
 
         let (updated, should_break) = match self.width {
             // First word always fits, and doesn't produce an extra space.
-            0 => (width, false),
+            None => (width, false),
+            // Continue the word on the current line, without a space.
+            Some(line) if joined && line + width + hyphen <= self.max => (line + width, false),
             // Add to the current line, and add a space in front.
-            _ if self.width + width < self.max => (self.width + width + 1, false),
+            Some(line) if !joined && line + whole < self.max => (line + width + 1, false),
             // Start a new line first, again no need for a space.
-            _ => (width, true),
+            Some(_) => (width, true),
         };
 
-        self.width = updated;
+        self.width = Some(updated);
         should_break
     }
+
+    fn resize(&mut self, max: usize) {
+        self.max = max;
+    }
+
+    fn prepare_joined(words: &[&str], joined: &[bool], max: usize, toppings: &Toppings) -> Self {
+        Self {
+            joined: joined.to_vec(),
+            hyphen: toppings.hyphen_width(),
+            ..Self::prepare(words, max, toppings)
+        }
+    }
 }
 
-impl Sauce for Salsa {
-    fn prepare(words: &[&str], max: usize) -> Self {
-        // This is shamelessly ported from:
-        // https://gist.github.com/dieter-medium/ad9f47a4e7e8ef4127461771a421e614#file-shortest_path_breaks-rb
+/// Cost of a last line with too few words, which outweighs any raggedness, see
+/// [Toppings::min_last_line_words].
+const SHORT_LAST_LINE_PENALTY: usize = usize::MAX / 2;
 
-        // TODO: Maybe bother with:
-        // https://www.sciencedirect.com/science/article/pii/S0166218X98000213,
-        // but probably not. O(n^2) is good enough for me since I don't plan to
-        // wrap megabytes of single-paragraph text... I think?
+/// The breaks chosen by [Salsa], excluding the start of the first line. A last
+/// line of fewer than `min_last` words is avoided if at all possible. Each
+/// break before a word that is `joined` to the one before it costs `penalty`,
+/// and widens the line by `hyphen`, see [Sauce::prepare_joined]. `joined` is
+/// empty if no words are joined.
+fn salsa(
+    offsets: &[usize],
+    joined: &[bool],
+    max: usize,
+    min_last: usize,
+    penalty: usize,
+    hyphen: usize,
+) -> Vec<usize> {
+    // This is shamelessly ported from:
+    // https://gist.github.com/dieter-medium/ad9f47a4e7e8ef4127461771a421e614#file-shortest_path_breaks-rb
 
-        let mut offsets = vec![0; words.len() + 1];
-        for (idx, word) in words.iter().enumerate() {
-            offsets[idx + 1] = offsets[idx] + word.width_cjk();
-        }
+    // TODO: Maybe bother with:
+    // https://www.sciencedirect.com/science/article/pii/S0166218X98000213,
+    // but probably not. O(n^2) is good enough for me since I don't plan to
+    // wrap megabytes of single-paragraph text... I think?
 
-        let mut minimas = vec![(0, usize::MAX); offsets.len()];
-        minimas[0].1 = 0;
+    let words = offsets.len() - 1;
 
-        for start_node_idx in 0..words.len() {
-            for end_node_idx in (start_node_idx + 1)..offsets.len() {
-                let line_length = offsets[end_node_idx] - offsets[start_node_idx] + end_node_idx
-                    - start_node_idx
-                    - 1;
+    // Joined words up to each node, which aren't preceded by a space, and
+    // don't count as words of their own.
+    let joins = iter::once(0)
+        .chain(joined.iter().scan(0, |joins, joined| {
+            *joins += usize::from(*joined);
+            Some(*joins)
+        }))
+        .collect::<Vec<_>>();
+    let joins_within = |start: usize, end: usize| match joined.is_empty() {
+        true => 0,
+        false => joins[end] - joins[start + 1],
+    };
 
-                if line_length > max && end_node_idx != start_node_idx + 1 {
-                    break;
-                }
+    // For each node: The node starting the previous line, the total cost and
+    // the number of lines up to here.
+    let mut minimas = vec![(0, usize::MAX, 0); offsets.len()];
+    minimas[0].1 = 0;
 
-                let penalty = match end_node_idx != words.len() {
-                    true => max.saturating_sub(line_length).pow(2),
-                    false => 0,
-                };
+    // Explicit tie-breaking, see [Salsa].
+    let key = |(start, cost, lines): (usize, usize, usize)| (cost, lines, start);
 
-                let cost = minimas[start_node_idx].1 + penalty;
-                if cost < minimas[end_node_idx].1 {
-                    minimas[end_node_idx] = (start_node_idx, cost);
-                }
+    for start_node_idx in 0..words {
+        for end_node_idx in (start_node_idx + 1)..offsets.len() {
+            let joins = joins_within(start_node_idx, end_node_idx);
+            let within = end_node_idx != words && joined.get(end_node_idx) == Some(&true);
+            let line_length = line_length(offsets, start_node_idx, end_node_idx) - joins
+                + usize::from(within) * hyphen;
+            // The rest of a word broken apart doesn't count as a word of its
+            // own either.
+            let tail = joined.get(start_node_idx) == Some(&true);
+            let line_words = end_node_idx - start_node_idx - joins - usize::from(tail);
+
+            if line_length > max && end_node_idx != start_node_idx + 1 {
+                break;
             }
-        }
 
-        let backtrack = std::iter::successors(Some(words.len()), |idx| {
-            (*idx != 0).then_some(minimas[*idx].0)
-        });
+            let penalty = match end_node_idx != words {
+                true if within => max
+                    .saturating_sub(line_length)
+                    .pow(2)
+                    .saturating_add(penalty),
+                true => max.saturating_sub(line_length).pow(2),
+                false if start_node_idx > 0 && line_words < min_last => SHORT_LAST_LINE_PENALTY,
+                false => 0,
+            };
 
-        Self(backtrack.skip(1).collect())
-    }
+            let (_, cost, lines) = minimas[start_node_idx];
+            let candidate = (start_node_idx, cost.saturating_add(penalty), lines + 1);
 
-    fn should_break(&mut self, _: &[&str], idx: usize) -> bool {
-        self.0.contains(&idx)
+            if key(candidate) < key(minimas[end_node_idx]) {
+                minimas[end_node_idx] = candidate;
+            }
+        }
     }
-}
 
-#[derive(Debug)]
-enum State {
-    Words,
-    Indent,
-    Comment,
-    Padding,
-    Bullet,
-    BulletSpace,
-    Final,
-}
+    let backtrack =
+        std::iter::successors(Some(words), |idx| (*idx != 0).then_some(minimas[*idx].0));
 
-pub(super) struct LineWrap<'t, S> {
-    line: Line<'t>,
-    sauce: S,
-    state: State,
-    newline: Newline,
-    pending: Option<&'t str>,
-    word_idx: usize,
-    whitespace_idx: usize,
-    bullet_width: usize,
+    backtrack.skip(1).filter(|idx| *idx != 0).collect()
 }
 
-impl<'t, S: Sauce> LineWrap<'t, S> {
-    pub fn new(line: Line<'t>, toppings: &Toppings) -> Self {
-        let whitespace_width = |whitespace| match whitespace {
-            Whitespace::Space(count) => count,
-            Whitespace::Tab(count) => toppings.tabs * count,
-        };
+/// The breaks chosen by [Mole], excluding the start of the first line.
+fn mole(offsets: &[usize], max: usize, penalties: Penalties) -> Vec<usize> {
+    let words = offsets.len() - 1;
 
-        let bullet_width = line
-            .bullet
-            .map(|bullet| bullet.width_cjk() + 1)
-            .unwrap_or(0);
+    // Same as Salsa, with penalties applied to the squared deviation.
+    let mut minimas = vec![(0, usize::MAX, 0); offsets.len()];
+    minimas[0].1 = 0;
 
-        let unbreakable_width = whitespace_width(line.indent)
-            + line.comment.map(|comment| comment.width_cjk()).unwrap_or(0)
-            + whitespace_width(line.padding)
-            + bullet_width;
+    let key = |(start, cost, lines): (usize, usize, usize)| (cost, lines, start);
 
-        let breakable_width = toppings.width.saturating_sub(unbreakable_width);
-        let sauce = S::prepare(&line.words, breakable_width);
+    for start_node_idx in 0..words {
+        if minimas[start_node_idx].1 == usize::MAX {
+            continue;
+        }
 
-        let state = if line.words.is_empty() {
-            State::Indent
-        } else {
-            State::Words
-        };
+        for end_node_idx in (start_node_idx + 1)..offsets.len() {
+            let line_length = line_length(offsets, start_node_idx, end_node_idx);
+            let single = end_node_idx == start_node_idx + 1;
 
-        Self {
-            line,
-            sauce,
-            state,
-            newline: toppings.newline,
-            pending: None,
-            word_idx: 0,
-            whitespace_idx: 0,
-            bullet_width,
+            let penalty = match (line_length > max, penalties.overfull) {
+                (true, _) if single => 0,
+                (true, None) => break,
+                (true, Some(overfull)) => (line_length - max)
+                    .saturating_pow(2)
+                    .saturating_mul(overfull),
+                (false, _) => max.saturating_sub(line_length).pow(2).saturating_mul(
+                    match end_node_idx != words {
+                        true => penalties.underfull,
+                        false => penalties.last_line,
+                    },
+                ),
+            };
+
+            // Longer lines only get more expensive from here.
+            if penalty == usize::MAX && line_length > max {
+                break;
+            }
+
+            let (_, cost, lines) = minimas[start_node_idx];
+            let candidate = (start_node_idx, cost.saturating_add(penalty), lines + 1);
+
+            if key(candidate) < key(minimas[end_node_idx]) {
+                minimas[end_node_idx] = candidate;
+            }
         }
     }
+
+    let backtrack =
+        std::iter::successors(Some(words), |idx| (*idx != 0).then_some(minimas[*idx].0));
+
+    backtrack.skip(1).filter(|idx| *idx != 0).collect()
 }
 
-impl<'t, S: Sauce> Iterator for LineWrap<'t, S> {
-    type Item = &'t str;
+/// The breaks chosen by [Guacamole], excluding the start of the first line.
+fn first_fit(offsets: &[usize], max: usize) -> Vec<usize> {
+    let mut breaks = Vec::new();
+    let mut start_node_idx = 0;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            match self.state {
-                State::Words => {
-                    if let Some(s) = self.pending.take() {
-                        break Some(s);
-                    }
+    for end_node_idx in 2..offsets.len() {
+        if line_length(offsets, start_node_idx, end_node_idx) > max {
+            start_node_idx = end_node_idx - 1;
+            breaks.push(start_node_idx);
+        }
+    }
 
-                    let s = match self.line.words.get(self.word_idx) {
-                        Some(s) => s,
-                        None => {
-                            self.state = State::Final;
-                            break self.line.newline.then_some(self.newline.as_str());
-                        }
-                    };
+    breaks
+}
 
-                    let should_break = self.sauce.should_break(&self.line.words, self.word_idx);
-                    self.word_idx += 1;
+/// The number of lines chosen by [Guacamole].
+fn first_fit_lines(offsets: &[usize], max: usize) -> usize {
+    first_fit(offsets, max).len() + 1
+}
 
-                    // Queue up this word:
-                    self.pending = Some(s);
+/// The breaks chosen by [SalsaMinLines], excluding the start of the first line.
+fn salsa_min_lines(offsets: &[usize], max: usize) -> Vec<usize> {
+    let words = offsets.len() - 1;
+    if words == 0 {
+        return Vec::new();
+    }
 
-                    // First word special case: Start a new line, but don't
-                    // prepend a newline token, rather skip right to the indent.
-                    if self.word_idx == 1 {
-                        self.state = State::Indent;
-                        continue;
-                    }
+    let lines = first_fit_lines(offsets, max);
 
-                    break Some(if should_break {
-                        // Word doesn't fit, start a new line.
-                        self.state = State::Indent;
-                        self.newline.as_str()
-                    } else {
-                        // Word fits, but needs a space first.
-                        self.state = State::Words;
-                        " "
-                    });
-                }
+    // Same as Salsa, but with an additional dimension for the number of lines
+    // used so far.
+    let mut minimas = vec![vec![(0, usize::MAX); offsets.len()]; lines + 1];
+    minimas[0][0].1 = 0;
 
-                State::Indent if self.whitespace_idx == self.line.indent.count() => {
-                    self.whitespace_idx = 0;
-                    self.state = State::Comment;
-                }
+    for line_idx in 1..=lines {
+        let (previous, current) = minimas.split_at_mut(line_idx);
+        let (previous, current) = (&previous[line_idx - 1], &mut current[0]);
 
-                State::Indent => {
-                    self.whitespace_idx += 1;
-                    break Some(self.line.indent.as_str());
-                }
+        for (start_node_idx, (_, start_cost)) in previous[..words].iter().enumerate() {
+            if *start_cost == usize::MAX {
+                continue;
+            }
 
-                State::Comment => {
-                    self.state = State::Padding;
-                    if let Some(token) = self.line.comment {
-                        break Some(token);
-                    }
-                }
+            let ends = current.iter_mut().enumerate().skip(start_node_idx + 1);
 
-                State::Padding if self.whitespace_idx == self.line.padding.count() => {
-                    self.whitespace_idx = 0;
-                    self.state = State::Bullet;
-                }
+            for (end_node_idx, minima) in ends {
+                let line_length = line_length(offsets, start_node_idx, end_node_idx);
 
-                State::Padding => {
-                    self.whitespace_idx += 1;
-                    break Some(self.line.padding.as_str());
+                if line_length > max && end_node_idx != start_node_idx + 1 {
+                    break;
                 }
 
-                State::Bullet => {
-                    let token = match self.line.bullet {
-                        Some(token) => token,
-                        None => {
-                            self.state = State::Words;
-                            continue;
-                        }
-                    };
+                let penalty = match end_node_idx != words {
+                    true => max.saturating_sub(line_length).pow(2),
+                    false => 0,
+                };
 
-                    // No more words come after this bullet, don't insert space.
-                    if self.pending.is_none() {
-                        // Go back to words to finalize the line.
-                        self.state = State::Words;
-                        break Some(token);
-                    }
+                let cost = *start_cost + penalty;
+                if (cost, start_node_idx) < (minima.1, minima.0) {
+                    *minima = (start_node_idx, cost);
+                }
+            }
+        }
+    }
 
-                    self.state = State::BulletSpace;
+    let mut breaks = Vec::with_capacity(lines - 1);
+    let mut idx = words;
+    for line_idx in (1..=lines).rev() {
+        idx = minimas[line_idx][idx].0;
+        if idx != 0 {
+            breaks.push(idx);
+        }
+    }
 
-                    if self.word_idx == 1 {
-                        // Only add a single space, after the bullet.
-                        self.whitespace_idx = self.bullet_width.saturating_sub(1);
-                        break Some(token);
-                    }
-                }
+    breaks
+}
 
-                State::BulletSpace if self.whitespace_idx == self.bullet_width => {
-                    self.whitespace_idx = 0;
-                    self.state = State::Words;
-                }
+/// The breaks chosen by [Minimax], excluding the start of the first line.
+fn minimax(offsets: &[usize], max: usize) -> Vec<usize> {
+    let words = offsets.len() - 1;
+    if words == 0 {
+        return Vec::new();
+    }
 
-                State::BulletSpace => {
-                    self.whitespace_idx += 1;
-                    break Some(" ");
-                }
+    let lines = first_fit_lines(offsets, max);
 
-                State::Final => {
-                    break None;
-                }
-            }
+    // The narrowest width that still fits into as many lines is somewhere
+    // between the widest word and all words on a single line.
+    let mut low = (0..words)
+        .map(|idx| offsets[idx + 1] - offsets[idx])
+        .max()
+        .unwrap_or(0);
+    let mut high = line_length(offsets, 0, words);
+
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match first_fit_lines(offsets, mid) <= lines {
+            true => high = mid,
+            false => low = mid + 1,
         }
     }
+
+    first_fit(offsets, low)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::{Guacamole, Salsa};
-    use crate::{Line, Toppings, Whitespace::*};
+impl Sauce for Salsa {
+    fn prepare(words: &[&str], max: usize, toppings: &Toppings) -> Self {
+        let min_last = toppings.min_last_line_words;
+        Self(Plan::new(words, max, toppings.cjk, move |offsets, max| {
+            salsa(offsets, &[], max, min_last, 0, 0)
+        }))
+    }
 
-    use std::sync::LazyLock;
+    fn should_break(&mut self, _: &[&str], idx: usize) -> bool {
+        self.0.should_break(idx)
+    }
 
-    static MINI_LINE: LazyLock<Toppings> = LazyLock::new(|| Toppings::default().width(10));
-    static HUGE_LINE: LazyLock<Toppings> = LazyLock::new(|| Toppings::default().width(1000));
+    fn resize(&mut self, max: usize) {
+        self.0.resize(max);
+    }
 
-    fn salsa<'t>(line: Line<'t>, toppings: &Toppings) -> Vec<&'t str> {
-        super::LineWrap::<Salsa>::new(line, toppings).collect()
+    fn breaks_words() -> bool {
+        true
     }
 
-    fn guacamole<'t>(line: Line<'t>, toppings: &Toppings) -> Vec<&'t str> {
-        super::LineWrap::<Guacamole>::new(line, toppings).collect()
+    fn prepare_joined(words: &[&str], joined: &[bool], max: usize, toppings: &Toppings) -> Self {
+        let min_last = toppings.min_last_line_words;
+        let penalty = toppings.intra_word_penalty.unwrap_or_default();
+        let hyphen = toppings.hyphen_width();
+        let joined = joined.to_vec();
+        Self(Plan::new(words, max, toppings.cjk, move |offsets, max| {
+            // Once resized, only the words following the most recent break
+            // are decided on again.
+            let joined = &joined[joined.len() + 1 - offsets.len()..];
+            salsa(offsets, joined, max, min_last, penalty, hyphen)
+        }))
+    }
+}
+
+impl Sauce for Mole {
+    fn prepare(words: &[&str], max: usize, toppings: &Toppings) -> Self {
+        let penalties = toppings.penalties;
+        Self(Plan::new(words, max, toppings.cjk, move |offsets, max| {
+            mole(offsets, max, penalties)
+        }))
     }
 
-    /// For tests where we expect [Salsa] and [Guacamole] to yield equal
-    /// results.
-    #[track_caller]
-    fn all<'t>(line: Line<'t>, toppings: &Toppings) -> Vec<&'t str> {
-        let salsa = salsa(line.clone(), toppings);
-        let guacamole = guacamole(line, toppings);
-        assert_eq!(salsa, guacamole);
-        salsa
+    fn should_break(&mut self, _: &[&str], idx: usize) -> bool {
+        self.0.should_break(idx)
+    }
+
+    fn resize(&mut self, max: usize) {
+        self.0.resize(max);
+    }
+}
+
+impl Sauce for Minimax {
+    fn prepare(words: &[&str], max: usize, toppings: &Toppings) -> Self {
+        Self(Plan::new(words, max, toppings.cjk, minimax))
+    }
+
+    fn should_break(&mut self, _: &[&str], idx: usize) -> bool {
+        self.0.should_break(idx)
+    }
+
+    fn resize(&mut self, max: usize) {
+        self.0.resize(max);
+    }
+}
+
+impl Sauce for SalsaMinLines {
+    fn prepare(words: &[&str], max: usize, toppings: &Toppings) -> Self {
+        Self(Plan::new(words, max, toppings.cjk, salsa_min_lines))
+    }
+
+    fn should_break(&mut self, _: &[&str], idx: usize) -> bool {
+        self.0.should_break(idx)
+    }
+
+    fn resize(&mut self, max: usize) {
+        self.0.resize(max);
+    }
+}
+
+impl<const THRESHOLD: usize> Sauce for Auto<THRESHOLD> {
+    fn prepare(words: &[&str], max: usize, toppings: &Toppings) -> Self {
+        Self(match words.len() < THRESHOLD {
+            true => Choice::Salsa(Salsa::prepare(words, max, toppings)),
+            false => Choice::Guacamole(Guacamole::prepare(words, max, toppings)),
+        })
+    }
+
+    fn should_break(&mut self, words: &[&str], idx: usize) -> bool {
+        match &mut self.0 {
+            Choice::Salsa(salsa) => salsa.should_break(words, idx),
+            Choice::Guacamole(guacamole) => guacamole.should_break(words, idx),
+        }
+    }
+
+    fn resize(&mut self, max: usize) {
+        match &mut self.0 {
+            Choice::Salsa(salsa) => salsa.resize(max),
+            Choice::Guacamole(guacamole) => guacamole.resize(max),
+        }
+    }
+
+    fn breaks_words() -> bool {
+        Salsa::breaks_words()
+    }
+
+    fn prepare_joined(words: &[&str], joined: &[bool], max: usize, toppings: &Toppings) -> Self {
+        // Paragraphs are told apart by their number of words, not fragments.
+        let count = joined.iter().filter(|joined| !**joined).count();
+
+        Self(match count < THRESHOLD {
+            true => Choice::Salsa(Salsa::prepare_joined(words, joined, max, toppings)),
+            false => Choice::Guacamole(Guacamole::prepare_joined(words, joined, max, toppings)),
+        })
+    }
+}
+
+impl Sauce for WordsPerLine {
+    fn prepare(_: &[&str], _: usize, toppings: &Toppings) -> Self {
+        Self(toppings.words_per_line)
+    }
+
+    fn should_break(&mut self, _: &[&str], idx: usize) -> bool {
+        idx.is_multiple_of(self.0)
+    }
+}
+
+fn whitespace_width(whitespace: Whitespace, tabs: usize) -> usize {
+    match whitespace {
+        Whitespace::Space(count) => count,
+        Whitespace::Tab(count) => tabs.saturating_mul(count),
+    }
+}
+
+/// Width of the bullet token of a line, including the space following it.
+fn bullet_width(line: &Line<'_>, cjk: bool) -> usize {
+    line.bullet
+        .map(|bullet| str_width(bullet, cjk) + 1)
+        .unwrap_or(0)
+}
+
+/// Width of the line number gutter, indentation and comment token of a line.
+fn comment_width(line: &Line<'_>, toppings: &Toppings) -> usize {
+    line.gutter
+        .map_or(0, |gutter| text_width(gutter, toppings.tabs, toppings.cjk))
+        .saturating_add(toppings.tab_widths().indent_width(line.indent))
+        .saturating_add(
+            line.comment
+                .map_or(0, |comment| str_width(comment, toppings.cjk)),
+        )
+}
+
+/// Width of everything in front of the first word of a line, which is repeated
+/// on each wrapped line rather than wrapped itself.
+pub(super) fn unbreakable_width(line: &Line<'_>, toppings: &Toppings) -> usize {
+    comment_width(line, toppings)
+        .saturating_add(whitespace_width(line.padding, toppings.tabs))
+        .saturating_add(bullet_width(line, toppings.cjk))
+}
+
+/// Replace tabs in indentation and padding with spaces of equal width, see
+/// [Toppings::expand_tabs].
+fn expand_tabs(line: &mut Line<'_>, tabs: usize) {
+    for whitespace in [&mut line.indent, &mut line.padding] {
+        if let Whitespace::Tab(count) = *whitespace {
+            *whitespace = Whitespace::Space(count.saturating_mul(tabs));
+        }
+    }
+
+    // Mixed indentation is already held as spaces of equal width.
+    line.raw_indent = None;
+}
+
+/// Split a word into fragments no wider than `max`, without breaking apart any
+/// grapheme clusters.
+fn fragments(word: &str, max: usize, cjk: bool) -> impl Iterator<Item = &str> {
+    let mut graphemes = word.grapheme_indices(true).peekable();
+
+    std::iter::from_fn(move || {
+        let (start_idx, first) = graphemes.next()?;
+        let mut width = str_width(first, cjk);
+
+        while let Some((end_idx, grapheme)) = graphemes.peek() {
+            if width + str_width(grapheme, cjk) > max {
+                return Some(&word[start_idx..*end_idx]);
+            }
+
+            width += str_width(grapheme, cjk);
+            graphemes.next();
+        }
+
+        Some(&word[start_idx..])
+    })
+}
+
+/// Split all words wider than `max` into fragments, see
+/// [Toppings::break_long_words]. The first fragment of the first word is cut to
+/// `first` instead, as it ends up on the first line. Protected words are left
+/// intact, see [protected].
+fn break_long_words(line: &mut Line<'_>, first: usize, max: usize, toppings: &Toppings) {
+    let cjk = toppings.cjk;
+    let protected = |word: &str| protected(word, toppings);
+    let fits = |(idx, word): (usize, &&str)| match idx {
+        _ if protected(word) => true,
+        0 => str_width(word, cjk) <= first,
+        _ => str_width(word, cjk) <= max,
+    };
+    if line.words.iter().enumerate().all(fits) {
+        return;
+    }
+
+    let mut words = Vec::with_capacity(line.words.len());
+    let mut gaps = line.gaps.iter().copied().peekable();
+    let mut new_gaps = Vec::with_capacity(line.gaps.len());
+
+    for (idx, word) in line.words.iter().enumerate() {
+        while let Some((_, gap)) = gaps.next_if(|(gap_idx, _)| *gap_idx == idx) {
+            new_gaps.push((words.len(), gap));
+        }
+
+        if protected(word) {
+            words.push(*word);
+            continue;
+        }
+
+        let mut rest = *word;
+        if idx == 0 && first > 0 && str_width(word, cjk) > first {
+            let head = fragments(word, first, cjk).next().unwrap_or(word);
+            words.push(head);
+            rest = &word[head.len()..];
+        }
+
+        match str_width(rest, cjk) > max {
+            true => words.extend(fragments(rest, max, cjk)),
+            false if rest.is_empty() => {}
+            false => words.push(rest),
+        }
+    }
+
+    line.words = words;
+    line.gaps = new_gaps;
+}
+
+/// Split all words into pieces (see [pieces]), for sauces that decide on
+/// breaks within words themselves, see [Toppings::intra_word_penalty]. Pieces
+/// of a single word are joined by empty gaps. Protected words are left intact (see
+/// [protected]), and so is a trailing continuation token.
+fn split_words(line: &mut Line<'_>, toppings: &Toppings) {
+    let last = line.words.len().saturating_sub(1);
+    let mut words = Vec::with_capacity(line.words.len());
+    let mut gaps = line.gaps.iter().copied().peekable();
+    let mut new_gaps = Vec::with_capacity(line.gaps.len());
+
+    for (idx, word) in line.words.iter().enumerate() {
+        while let Some((_, gap)) = gaps.next_if(|(gap_idx, _)| *gap_idx == idx) {
+            new_gaps.push((words.len(), gap));
+        }
+
+        if protected(word, toppings) || (idx == last && line.continuation == Some(word)) {
+            words.push(*word);
+            continue;
+        }
+
+        for (piece_idx, piece) in pieces(word, toppings).enumerate() {
+            if piece_idx > 0 {
+                new_gaps.push((words.len(), Whitespace::Space(0)));
+            }
+            words.push(piece);
+        }
+    }
+
+    line.words = words;
+    line.gaps = new_gaps;
+}
+
+/// The pieces [split_words] breaks a word into: Its grapheme clusters, or the
+/// fragments between its hyphenation points if words are hyphenated (see
+/// [Toppings::hyphenate]).
+#[cfg_attr(not(feature = "hyphen"), expect(unused_variables))]
+fn pieces<'w>(word: &'w str, toppings: &Toppings) -> Box<dyn Iterator<Item = &'w str> + 'w> {
+    #[cfg(feature = "hyphen")]
+    if let Some(lang) = toppings.hyphenate {
+        return Box::new(crate::hyphen::fragments(word, lang).into_iter());
+    }
+
+    Box::new(word.graphemes(true))
+}
+
+/// Whether a word looks like a URL, see [Toppings::protect_urls].
+fn is_url(word: &str) -> bool {
+    ["http://", "https://", "www."]
+        .iter()
+        .any(|prefix| word.starts_with(prefix))
+}
+
+/// Whether a word must not be broken apart, see [Toppings::protect_urls] and
+/// [Toppings::protect].
+fn protected(word: &str, toppings: &Toppings) -> bool {
+    #[cfg(feature = "regex")]
+    if let Some(pattern) = &toppings.protect {
+        // Words joined from several ones by a match have whitespace within.
+        let matches = pattern
+            .0
+            .find(word)
+            .is_some_and(|found| found.len() == word.len());
+        if matches || word.contains([' ', '\t']) {
+            return true;
+        }
+    }
+
+    toppings.protect_urls && is_url(word)
+}
+
+/// Keep opening brackets with the word following them, and closing brackets
+/// with the word preceding them, see [Toppings::keep_brackets].
+fn keep_brackets(word: &str, next: &str) -> KeepRule {
+    if word.ends_with(['(', '[', '{']) {
+        KeepRule::KeepWithNext
+    } else if next.starts_with([')', ']', '}']) {
+        KeepRule::KeepWithPrevious
+    } else {
+        KeepRule::Allow
+    }
+}
+
+/// Indices of words that start an inline code span at least `percent` percent
+/// as wide as `max`, see [Toppings::break_before_code]. Spans at the very
+/// start are left alone, as there is nothing to break before them.
+fn code_breaks(words: &[&str], percent: usize, max: usize, cjk: bool) -> Vec<usize> {
+    let mut breaks = Vec::new();
+    let mut idx = 0;
+
+    while idx < words.len() {
+        let start = idx;
+        idx += 1;
+
+        if !words[start].starts_with('`') {
+            continue;
+        }
+
+        // A lone backtick doesn't close the span it opens.
+        let closes = |word: &&str| word.ends_with('`') && word.len() > 1;
+        let Some(len) = words[start..].iter().position(closes) else {
+            continue;
+        };
+
+        let span = &words[start..=start + len];
+        let width = span.iter().map(|word| str_width(word, cjk)).sum::<usize>() + span.len() - 1;
+        if start > 0 && width * 100 >= percent * max {
+            breaks.push(start);
+        }
+        idx = start + len + 1;
+    }
+
+    breaks
+}
+
+/// Move the last break of a paragraph earlier, such that the last line holds
+/// at least `min` words, or as many as fit within `max` columns, see
+/// [Toppings::min_last_line_words]. The line above keeps at least one word,
+/// and breaks in `forced` stay where they are.
+fn pull_last_line(
+    breaks: &mut [bool],
+    words: &[&str],
+    separators: &[usize],
+    forced: &[usize],
+    min: usize,
+    max: usize,
+    cjk: bool,
+) {
+    let Some(last) = breaks.iter().rposition(|should_break| *should_break) else {
+        return;
+    };
+
+    if words.len() < min || words.len() - last >= min || forced.contains(&last) {
+        return;
+    }
+
+    let previous = breaks[..last]
+        .iter()
+        .rposition(|should_break| *should_break)
+        .unwrap_or(0);
+
+    let earliest = (words.len() - min).max(previous + 1);
+    // Grapheme clusters of a single word stay together, see [split_words].
+    let starts_word = |start: &usize| separators[*start] > 0;
+    let width = |start: usize| {
+        words[start..]
+            .iter()
+            .map(|word| str_width(word, cjk))
+            .sum::<usize>()
+            + separators[start + 1..].iter().sum::<usize>()
+    };
+
+    if let Some(start) = (earliest..last)
+        .filter(starts_word)
+        .find(|start| width(*start) <= max)
+    {
+        breaks[last] = false;
+        breaks[start] = true;
+    }
+}
+
+/// The number of extra spaces in front of each word that widen each line but
+/// the last one to its maximum width, see [Toppings::justify]. Spaces are
+/// distributed as evenly as possible, with earlier gaps getting more.
+fn fillers(
+    words: &[&str],
+    breaks: &[bool],
+    separators: &[usize],
+    max_for_line: &mut dyn FnMut(usize) -> usize,
+    cjk: bool,
+    hyphen: usize,
+) -> Vec<usize> {
+    let mut fillers = vec![0; words.len()];
+
+    for (line_idx, range) in line_ranges(breaks).enumerate() {
+        // Grapheme clusters of a single word aren't spread apart, see
+        // [split_words].
+        let starts = (range.start + 1..range.end).filter(|idx| separators[*idx] > 0);
+        let gaps = starts.clone().count();
+        if gaps == 0 || range.end == words.len() {
+            continue;
+        }
+
+        let width = words_width(words, separators, range.clone(), cjk)
+            + line_hyphen(separators, &range, hyphen);
+        let extra = max_for_line(line_idx).saturating_sub(width);
+        for (gap_idx, start) in starts.enumerate() {
+            fillers[start] = extra / gaps + usize::from(gap_idx < extra % gaps);
+        }
+    }
+
+    fillers
+}
+
+/// The number of spaces in front of the first word of each line that move it
+/// to the right of its maximum width, or to the middle, see [Toppings::align].
+/// `line_width` yields the width of the words in a range as they are emitted,
+/// i.e., including any spaces that justify them (see [fillers]).
+fn aligns(
+    breaks: &[bool],
+    line_width: &dyn Fn(Range<usize>) -> usize,
+    max_for_line: &mut dyn FnMut(usize) -> usize,
+    align: Align,
+) -> Vec<usize> {
+    let mut aligns = vec![0; breaks.len()];
+
+    for (line_idx, range) in line_ranges(breaks).enumerate() {
+        if range.is_empty() {
+            continue;
+        }
+
+        let room = max_for_line(line_idx).saturating_sub(line_width(range.clone()));
+        aligns[range.start] = match align {
+            Align::Left | Align::Auto => 0,
+            Align::Right => room,
+            // Any odd space left over goes to the right.
+            Align::Center => room / 2,
+        };
+    }
+
+    aligns
+}
+
+/// The range of words on each line, given whether a break precedes each word.
+fn line_ranges(breaks: &[bool]) -> impl Iterator<Item = Range<usize>> + '_ {
+    let starts = iter::once(0).chain((1..breaks.len()).filter(|idx| breaks[*idx]));
+    let ends = starts.clone().skip(1).chain(iter::once(breaks.len()));
+    starts.zip(ends).map(|(start, end)| start..end)
+}
+
+/// Width of the hyphen at the end of the line of words in `range`, if it ends
+/// within a word, see [Toppings::hyphenate].
+fn line_hyphen(separators: &[usize], range: &Range<usize>, hyphen: usize) -> usize {
+    match separators.get(range.end) {
+        Some(0) => hyphen,
+        _ => 0,
+    }
+}
+
+/// Width of the words in `range`, including the whitespace between them.
+fn words_width(words: &[&str], separators: &[usize], range: Range<usize>, cjk: bool) -> usize {
+    let start = range.start;
+    words[range.clone()]
+        .iter()
+        .map(|word| str_width(word, cjk))
+        .chain(separators[start + 1..range.end].iter().copied())
+        .fold(0, usize::saturating_add)
+}
+
+/// Run a sauce across all words ahead of time, returning whether a line break
+/// precedes each word. `max_for_line` yields the breakable width of each line.
+/// `joined` is empty unless words were split into grapheme clusters, see
+/// [Sauce::prepare_joined].
+fn plan<S: Sauce>(
+    words: &[&str],
+    joined: &[bool],
+    toppings: &Toppings,
+    max_for_line: &mut dyn FnMut(usize) -> usize,
+) -> Vec<bool> {
+    let mut sauce = match joined.is_empty() {
+        true => S::prepare(words, max_for_line(0), toppings),
+        false => S::prepare_joined(words, joined, max_for_line(0), toppings),
+    };
+    let mut line_idx = 0;
+
+    (0..words.len())
+        .map(|idx| {
+            // There is nothing to break before the first word.
+            let should_break = sauce.should_break(words, idx) && idx != 0;
+            if should_break {
+                line_idx += 1;
+                sauce.resize(max_for_line(line_idx));
+            }
+            should_break
+        })
+        .collect()
+}
+
+/// The indices of the words that `S` starts a new line with, when breaking
+/// `words` into lines of maximum width `max`. The sauce is prepared with
+/// `toppings`, e.g., to tell whether characters of ambiguous width count as
+/// wide (see [Toppings::cjk]).
+///
+/// # Examples
+///
+/// ```
+/// use tortilla::{break_indices, Guacamole, Salsa, Toppings};
+///
+/// let words = ["aaa", "bb", "cc", "ddddd"];
+/// let toppings = Toppings::default();
+///
+/// assert_eq!(break_indices::<Guacamole>(&words, 6, &toppings), [2, 3]);
+/// assert_eq!(break_indices::<Salsa>(&words, 6, &toppings), [1, 3]);
+/// ```
+pub fn break_indices<S: Sauce>(words: &[&str], max: usize, toppings: &Toppings) -> Vec<usize> {
+    plan::<S>(words, &[], toppings, &mut |_| max)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(idx, should_break)| should_break.then_some(idx))
+        .collect()
+}
+
+/// Run a sauce across units of words, as [plan] does for single words.
+///
+/// Adjacent words that must not be separated (see [Toppings::keep]) are glued
+/// into a single unit. `separators` holds the width of the whitespace preceding
+/// each word, which exceeds a single space if original whitespace is preserved
+/// (see [Toppings::collapse_spaces]). Words are only glued as long as the unit
+/// stays within `max_unit` columns. A unit is joined to the one before it if
+/// its first word is, see [plan].
+fn plan_units<S: Sauce>(
+    words: &[&str],
+    joined: &[bool],
+    keep: Option<impl Fn(&str, &str) -> KeepRule>,
+    separators: &[usize],
+    max_unit: usize,
+    toppings: &Toppings,
+    max_for_line: &mut dyn FnMut(usize) -> usize,
+) -> Vec<bool> {
+    let cjk = toppings.cjk;
+
+    // Units are laid out one after another in a single buffer, each as a
+    // byte range along with its width.
+    let mut buffer = String::new();
+    let mut units: Vec<(Range<usize>, usize)> = Vec::with_capacity(words.len());
+    let mut starts = Vec::with_capacity(words.len());
+
+    for (idx, word) in words.iter().enumerate() {
+        let glued = idx > 0
+            && keep
+                .as_ref()
+                .is_some_and(|keep| keep(words[idx - 1], word) != KeepRule::Allow);
+        let width = str_width(word, cjk);
+
+        match units.last_mut() {
+            Some((range, unit_width))
+                if glued && *unit_width + separators[idx] + width <= max_unit =>
+            {
+                buffer.extend(iter::repeat_n(' ', separators[idx]));
+                buffer.push_str(word);
+                range.end = buffer.len();
+                *unit_width += separators[idx] + width;
+            }
+            _ => {
+                // The sauce accounts for a single space in front of each unit.
+                let start = buffer.len();
+                let padding = separators[idx].saturating_sub(1);
+                buffer.extend(iter::repeat_n(' ', padding));
+                buffer.push_str(word);
+                units.push((start..buffer.len(), padding + width));
+                starts.push(idx);
+            }
+        }
+    }
+
+    let units = units
+        .into_iter()
+        .map(|(range, _)| &buffer[range])
+        .collect::<Vec<_>>();
+    let joined = match joined.is_empty() {
+        true => Vec::new(),
+        false => starts.iter().map(|start| joined[*start]).collect(),
+    };
+    let mut breaks = vec![false; words.len()];
+
+    for (start, should_break) in
+        starts
+            .into_iter()
+            .zip(plan::<S>(&units, &joined, toppings, max_for_line))
+    {
+        breaks[start] = should_break;
+    }
+
+    breaks
+}
+
+/// Width of the whitespace preceding each word, see [plan_units]. Grapheme
+/// clusters joined to the word before them have none, see [split_words].
+fn separators(line: &Line<'_>, tabs: usize) -> Vec<usize> {
+    let mut separators = vec![0; line.words.len()];
+    let mut joined = vec![false; line.words.len()];
+
+    for (idx, gap) in &line.gaps {
+        separators[*idx] += whitespace_width(*gap, tabs);
+        joined[*idx] |= gap.count() == 0;
+    }
+
+    for (separator, joined) in separators.iter_mut().zip(joined).skip(1) {
+        if !joined {
+            *separator = (*separator).max(1);
+        }
+    }
+
+    separators
+}
+
+#[derive(Debug)]
+enum State {
+    Verbatim,
+    Words,
+    Continuation,
+    Break,
+    Gap,
+    OutputIndent,
+    Gutter,
+    Indent,
+    Comment,
+    Column,
+    Padding,
+    Bullet,
+    BulletSpace,
+    Final,
+}
+
+pub(super) struct LineWrap<'t> {
+    line: Line<'t>,
+    /// Whether a line break precedes each word, decided on ahead of time for
+    /// all sauces alike, see [plan].
+    breaks: Vec<bool>,
+    state: State,
+    newline: Newline,
+    pending: Option<&'t str>,
+    word_idx: usize,
+    gap_idx: usize,
+    whitespace_idx: usize,
+    bullet_width: usize,
+    /// Spaces replacing the bullet on continuation lines, see
+    /// [Toppings::hanging].
+    hanging_width: usize,
+    column_width: Option<usize>,
+    /// Spaces in front of each line, see [Toppings::output_indent].
+    output_indent: usize,
+    /// Width of the spaces replacing the line number on continuation lines,
+    /// see [Gutter::Blank].
+    blank_gutter: Option<usize>,
+    /// Indentation of the first line, followed by a number of extra spaces.
+    first_indent: (Whitespace, usize),
+    trailing_space: bool,
+    /// Width to pad each line to, see [Toppings::fill]. Empty if lines are
+    /// not padded.
+    fill_widths: Vec<usize>,
+    fill_last: bool,
+    /// Extra spaces in front of each word, see [Toppings::justify]. Empty if
+    /// lines are not justified.
+    fillers: Vec<usize>,
+    /// Extra spaces left to emit in front of the pending word.
+    filler: usize,
+    /// Spaces following the indentation of the line that starts with each
+    /// word, see [Toppings::align]. Empty if lines are aligned left.
+    aligns: Vec<usize>,
+    tabs: usize,
+    cjk: bool,
+    line_idx: usize,
+    col: usize,
+    emitted_word: bool,
+    /// Whether lines that end within a word end with a hyphen, see
+    /// [Toppings::hyphenate].
+    hyphenate: bool,
+}
+
+impl<'t> LineWrap<'t> {
+    pub fn new<S: Sauce>(line: Line<'t>, toppings: &Toppings) -> Self {
+        Self::with_widths::<S>(line, toppings, &mut |_| toppings.width)
+    }
+
+    /// Same as [LineWrap::new], but with a maximum width that varies by line.
+    /// `width_for_line` is called with the index of each line within this
+    /// paragraph. See [Sauce::resize].
+    pub fn with_widths<S: Sauce>(
+        mut line: Line<'t>,
+        toppings: &Toppings,
+        width_for_line: &mut dyn FnMut(usize) -> usize,
+    ) -> Self {
+        if toppings.expand_tabs {
+            expand_tabs(&mut line, toppings.tabs);
+        }
+
+        let bullet_width = bullet_width(&line, toppings.cjk);
+        let prefix_width = comment_width(&line, toppings);
+        let unbreakable_width = unbreakable_width(&line, toppings);
+
+        // Continuation lines of a bulleted line may hang by a different
+        // amount than the bullet's width, see [Toppings::hanging] and
+        // [Toppings::preserve_continuation_indent], and those of continued
+        // lines by their original indentation, see [Toppings::continuation].
+        let hanging_width = match (line.bullet, line.hanging, toppings.hanging) {
+            (_, Some(spaces), _) | (Some(_), None, Some(spaces)) => spaces,
+            _ => bullet_width,
+        };
+
+        // Spaces between the prefix and words on continuation lines, see
+        // [Toppings::continuation_column].
+        let column_width = toppings
+            .continuation_column
+            .map(|column| column.saturating_sub(prefix_width).max(1));
+
+        // Indentation beyond the maximum doesn't count, see
+        // [Toppings::max_indent].
+        let clamp = |width: usize| toppings.max_indent.map_or(width, |max| width.min(max));
+        let tabs = toppings.tab_widths();
+        let indent_width = tabs.indent_width(line.indent);
+
+        let continuation_width = toppings
+            .output_indent
+            .saturating_add(match column_width {
+                Some(column_width) => prefix_width.saturating_add(column_width),
+                None => (unbreakable_width - bullet_width).saturating_add(hanging_width),
+            })
+            .saturating_sub(indent_width - clamp(indent_width));
+
+        // Indentation of the first line, see [Toppings::first_line_indent].
+        let first_indent = match toppings.first_line_indent {
+            0 => (line.indent, 0),
+            offset if offset > 0 => (line.indent, offset.unsigned_abs()),
+            offset => match line.indent {
+                Whitespace::Space(count) => (
+                    Whitespace::Space(count.saturating_sub(offset.unsigned_abs())),
+                    0,
+                ),
+                Whitespace::Tab(_) => (
+                    Whitespace::Space(indent_width.saturating_sub(offset.unsigned_abs())),
+                    0,
+                ),
+            },
+        };
+
+        let first_width = toppings
+            .output_indent
+            .saturating_add(unbreakable_width.saturating_sub(indent_width))
+            .saturating_add(clamp(
+                tabs.indent_width(first_indent.0)
+                    .saturating_add(first_indent.1),
+            ));
+
+        // Room for the token that continues each line, see
+        // [Toppings::continuation].
+        let continued_width = line
+            .continuation
+            .map_or(0, |token| str_width(token, toppings.cjk).saturating_add(1));
+
+        // Words always get at least some room, see [Toppings::min_width].
+        let mut max_for_line = |line_idx| {
+            width_for_line(line_idx)
+                .saturating_sub(match line_idx {
+                    0 => first_width,
+                    _ => continuation_width,
+                })
+                .saturating_sub(continued_width)
+                .max(toppings.min_width)
+        };
+
+        // If indentation, comment token and bullet leave no room for words at
+        // all, there is no point in breaking them apart.
+        let max = max_for_line(0);
+
+        // Fragments must fit on whichever line they end up on, see
+        // [Toppings::enforce_width].
+        let continuation = match toppings.enforce_width {
+            true => max_for_line(1),
+            false => max,
+        };
+
+        // Sauces that break words apart themselves weigh each break within a
+        // word instead, see [Toppings::intra_word_penalty].
+        // Hyphenated words are broken apart at hyphenation points alone, see
+        // [Toppings::hyphenate].
+        let hyphen = toppings.hyphen_width();
+        let split = (toppings.intra_word_penalty.is_some() || hyphen > 0)
+            && S::breaks_words()
+            && toppings.keep.is_none()
+            && !toppings.keep_brackets
+            && toppings.break_before_code.is_none()
+            && ((hyphen > 0 && max > 0)
+                || (toppings.enforce_width && continuation > 0)
+                || (toppings.break_long_words && max > 0));
+
+        // Lines only end with a hyphen if words are split into pieces.
+        let hyphen = usize::from(split) * hyphen;
+
+        if split {
+            split_words(&mut line, toppings);
+        } else if toppings.enforce_width && continuation > 0 {
+            break_long_words(&mut line, max, continuation, toppings);
+        } else if toppings.break_long_words && max > 0 {
+            break_long_words(&mut line, max, max, toppings);
+        }
+
+        // Words kept together must not overflow either.
+        let strict = match toppings.enforce_width {
+            true => max.min(continuation),
+            false => usize::MAX,
+        };
+
+        let custom = toppings.keep.as_ref().map(|keep| &*keep.0);
+        let combined = |word: &str, next: &str| match custom.map(|keep| keep(word, next)) {
+            Some(KeepRule::Allow) | None if toppings.keep_brackets => keep_brackets(word, next),
+            rule => rule.unwrap_or(KeepRule::Allow),
+        };
+        let keep = (custom.is_some() || toppings.keep_brackets).then_some(combined);
+
+        let separators = separators(&line, toppings.tabs);
+
+        let joined = match split {
+            true => (0..separators.len())
+                .map(|idx| idx > 0 && separators[idx] == 0)
+                .collect(),
+            false => Vec::new(),
+        };
+
+        let simple = keep.is_none() && separators.iter().skip(1).all(|width| *width <= 1);
+        let plan_range =
+            |range: Range<usize>, max_for_line: &mut dyn FnMut(usize) -> usize| match simple {
+                true => plan::<S>(
+                    &line.words[range.clone()],
+                    joined.get(range).unwrap_or_default(),
+                    toppings,
+                    max_for_line,
+                ),
+                false => plan_units::<S>(
+                    &line.words[range.clone()],
+                    joined.get(range.clone()).unwrap_or_default(),
+                    keep.as_ref(),
+                    &separators[range],
+                    strict,
+                    toppings,
+                    max_for_line,
+                ),
+            };
+
+        // Plan each stretch between forced breaks on its own, see
+        // [Toppings::break_before_code].
+        let forced = toppings.break_before_code.map_or_else(Vec::new, |percent| {
+            code_breaks(&line.words, percent, max, toppings.cjk)
+        });
+
+        let mut breaks = Vec::with_capacity(line.words.len());
+        let starts = iter::once(0).chain(forced.iter().copied());
+        let ends = forced.iter().copied().chain(iter::once(line.words.len()));
+        for (start, end) in starts.zip(ends) {
+            let first_line = breaks.iter().filter(|should_break| **should_break).count()
+                + usize::from(start > 0);
+            let mut segment = plan_range(start..end, &mut |idx| max_for_line(first_line + idx));
+            if start > 0 {
+                segment[0] = true;
+            }
+            breaks.append(&mut segment);
+        }
+
+        if toppings.min_last_line_words > 1 {
+            let lines = breaks.iter().filter(|should_break| **should_break).count() + 1;
+            let max = max_for_line(lines - 1);
+            let min = toppings.min_last_line_words;
+            pull_last_line(
+                &mut breaks,
+                &line.words,
+                &separators,
+                &forced,
+                min,
+                max,
+                toppings.cjk,
+            );
+        }
+
+        // A continuation token with nothing to continue on stays at the end
+        // of the last line, in the room left for it.
+        if line.continuation.is_some()
+            && line.words.last() == line.continuation.as_ref()
+            && let Some(should_break) = breaks.last_mut()
+        {
+            *should_break = false;
+        }
+
+        let fillers = match toppings.justify && line.verbatim.is_none() {
+            true => fillers(
+                &line.words,
+                &breaks,
+                &separators,
+                &mut max_for_line,
+                toppings.cjk,
+                hyphen,
+            ),
+            false => Vec::new(),
+        };
+
+        let line_width = |range: Range<usize>| {
+            let fillers = fillers.get(range.clone()).unwrap_or_default();
+            words_width(&line.words, &separators, range.clone(), toppings.cjk)
+                + line_hyphen(&separators, &range, hyphen)
+                + fillers.iter().sum::<usize>()
+        };
+
+        let aligns = match (toppings.align_for(&line), line.verbatim) {
+            (Align::Left | Align::Auto, _) | (_, Some(_)) => Vec::new(),
+            (align, None) => aligns(&breaks, &line_width, &mut max_for_line, align),
+        };
+
+        let fill_widths = match toppings.fill {
+            Fill::Ragged => Vec::new(),
+            Fill::AllButLast | Fill::All => {
+                let lines = breaks.iter().filter(|should_break| **should_break).count() + 1;
+                (0..lines).map(&mut *width_for_line).collect()
+            }
+        };
+
+        let state = if line.verbatim.is_some() {
+            State::Verbatim
+        } else if line.words.is_empty() {
+            State::OutputIndent
+        } else {
+            State::Words
+        };
+
+        // Don't turn empty lines into trailing whitespace.
+        let output_indent = match line.verbatim {
+            Some(_) if line.words.is_empty() => 0,
+            Some(_) => toppings.output_indent,
+            None if line.gutter.is_none()
+                && line.indent.count() == 0
+                && line.comment.is_none()
+                && line.padding.count() == 0
+                && line.bullet.is_none()
+                && line.words.is_empty() =>
+            {
+                0
+            }
+            None => toppings.output_indent,
+        };
+
+        let blank_gutter = match (line.gutter, toppings.line_numbers) {
+            (Some(gutter), Some(Gutter::Blank)) => {
+                Some(text_width(gutter, toppings.tabs, toppings.cjk))
+            }
+            _ => None,
+        };
+
+        Self {
+            line,
+            breaks,
+            state,
+            newline: toppings.newline,
+            pending: None,
+            word_idx: 0,
+            gap_idx: 0,
+            whitespace_idx: 0,
+            bullet_width,
+            hanging_width,
+            column_width,
+            output_indent,
+            blank_gutter,
+            first_indent,
+            trailing_space: toppings.trailing_space_on_break,
+            fill_widths,
+            fill_last: toppings.fill == Fill::All,
+            fillers,
+            filler: 0,
+            aligns,
+            tabs: toppings.tabs,
+            cjk: toppings.cjk,
+            line_idx: 0,
+            col: 0,
+            emitted_word: false,
+            hyphenate: hyphen > 0,
+        }
+    }
+
+    /// Whether the chunk returned last is one of the line's words, rather than
+    /// whitespace, a token or a newline.
+    pub fn emitted_word(&self) -> bool {
+        self.emitted_word
+    }
+
+    /// Width of the space following the bullet on the current line, or
+    /// replacing it on continuation lines.
+    fn bullet_space(&self) -> usize {
+        match self.word_idx {
+            1 => self.bullet_width,
+            _ => self.hanging_width,
+        }
+    }
+
+    /// Whether the current line still needs padding, see [Toppings::fill].
+    fn needs_fill(&self) -> bool {
+        self.fill_widths
+            .get(self.line_idx)
+            .is_some_and(|width| self.col < *width)
+    }
+
+    /// Keep track of the current column, for padding lines.
+    fn advance(&mut self, chunk: &str) {
+        if chunk == self.newline.as_str() {
+            self.line_idx += 1;
+            self.col = 0;
+        } else {
+            self.col = self
+                .col
+                .saturating_add(text_width(chunk, self.tabs, self.cjk));
+        }
+    }
+}
+
+impl<'t> Iterator for LineWrap<'t> {
+    type Item = &'t str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = self.step();
+
+        if let (Some(chunk), false) = (chunk, self.fill_widths.is_empty()) {
+            self.advance(chunk);
+        }
+
+        chunk
+    }
+}
+
+impl<'t> LineWrap<'t> {
+    fn step(&mut self) -> Option<&'t str> {
+        self.emitted_word = false;
+
+        loop {
+            match self.state {
+                State::Verbatim if self.whitespace_idx < self.output_indent => {
+                    self.whitespace_idx += 1;
+                    break Some(" ");
+                }
+
+                State::Verbatim => {
+                    self.whitespace_idx = 0;
+
+                    // Skip right to the end of the line.
+                    self.word_idx = self.line.words.len();
+                    self.state = State::Words;
+
+                    match self.line.verbatim {
+                        Some(raw) if !raw.is_empty() => break Some(raw),
+                        _ => continue,
+                    }
+                }
+
+                State::Words | State::Gap if self.filler > 0 => {
+                    self.filler -= 1;
+                    break Some(" ");
+                }
+
+                State::Words => {
+                    if let Some(s) = self.pending.take() {
+                        self.emitted_word = true;
+                        break Some(s);
+                    }
+
+                    let s = match self.line.words.get(self.word_idx) {
+                        Some(s) => s,
+                        None => {
+                            if self.fill_last
+                                && self.line.verbatim.is_none()
+                                && !self.line.words.is_empty()
+                                && self.needs_fill()
+                            {
+                                break Some(" ");
+                            }
+
+                            if let Some(whitespace) = self
+                                .line
+                                .trailing_space
+                                .take()
+                                .filter(|whitespace| !whitespace.is_empty())
+                            {
+                                break Some(whitespace);
+                            }
+
+                            self.state = State::Final;
+                            break self.line.newline.then_some(self.newline.as_str());
+                        }
+                    };
+
+                    let should_break = self.breaks[self.word_idx];
+                    self.word_idx += 1;
+
+                    // Queue up this word:
+                    self.pending = Some(s);
+
+                    // First word special case: Start a new line, but don't
+                    // prepend a newline token, rather skip right to the indent.
+                    if self.word_idx == 1 {
+                        self.state = State::OutputIndent;
+                        continue;
+                    }
+
+                    let has_gap = self.line.gaps.get(self.gap_idx).map(|(idx, _)| *idx)
+                        == Some(self.word_idx - 1);
+
+                    if !should_break {
+                        self.filler = self.fillers.get(self.word_idx - 1).copied().unwrap_or(0);
+                    }
+
+                    break Some(if should_break {
+                        // Ending within a word, see [Toppings::hyphenate].
+                        let hyphen = self.hyphenate
+                            && self.line.gaps.get(self.gap_idx).is_some_and(|(idx, gap)| {
+                                *idx == self.word_idx - 1 && gap.count() == 0
+                            });
+
+                        // Word doesn't fit, start a new line.
+                        while self
+                            .line
+                            .gaps
+                            .get(self.gap_idx)
+                            .is_some_and(|(idx, _)| *idx < self.word_idx)
+                        {
+                            self.gap_idx += 1;
+                        }
+
+                        self.state = match self.line.continuation {
+                            Some(_) => State::Continuation,
+                            None => State::Break,
+                        };
+                        match self.trailing_space || self.line.continuation.is_some() {
+                            _ if hyphen => "-",
+                            true => " ",
+                            false => continue,
+                        }
+                    } else if has_gap {
+                        // Word fits, and needs its original whitespace first.
+                        self.state = State::Gap;
+                        continue;
+                    } else {
+                        // Word fits, but needs a space first.
+                        self.state = State::Words;
+                        " "
+                    });
+                }
+
+                State::Continuation => {
+                    self.state = State::Break;
+                    if let Some(token) = self.line.continuation {
+                        break Some(token);
+                    }
+                }
+
+                State::Break if self.needs_fill() => break Some(" "),
+
+                State::Break => {
+                    self.state = State::OutputIndent;
+                    break Some(self.newline.as_str());
+                }
+
+                State::Gap => match self.line.gaps.get(self.gap_idx) {
+                    Some((idx, gap)) if *idx == self.word_idx - 1 => {
+                        if self.whitespace_idx == gap.count() {
+                            self.whitespace_idx = 0;
+                            self.gap_idx += 1;
+                        } else {
+                            self.whitespace_idx += 1;
+                            break Some(gap.as_str());
+                        }
+                    }
+                    _ => self.state = State::Words,
+                },
+
+                State::OutputIndent if self.whitespace_idx < self.output_indent => {
+                    self.whitespace_idx += 1;
+                    break Some(" ");
+                }
+
+                State::OutputIndent => {
+                    self.whitespace_idx = 0;
+                    self.state = State::Gutter;
+                }
+
+                State::Gutter => match (self.line.gutter, self.blank_gutter) {
+                    (Some(_), Some(width)) if self.word_idx > 1 && self.whitespace_idx < width => {
+                        self.whitespace_idx += 1;
+                        break Some(" ");
+                    }
+                    (Some(_), Some(_)) if self.word_idx > 1 => {
+                        self.whitespace_idx = 0;
+                        self.state = State::Indent;
+                    }
+                    (gutter, _) => {
+                        self.state = State::Indent;
+                        if let Some(gutter) = gutter {
+                            break Some(gutter);
+                        }
+                    }
+                },
+
+                State::Indent => {
+                    let (indent, extra) = match self.word_idx {
+                        1 => self.first_indent,
+                        _ => (self.line.indent, 0),
+                    };
+                    let align = self
+                        .word_idx
+                        .checked_sub(1)
+                        .and_then(|idx| self.aligns.get(idx));
+                    let extra = extra + align.copied().unwrap_or(0);
+
+                    // Original indentation is emitted in one piece.
+                    let raw = self.line.raw_indent.filter(|_| indent == self.line.indent);
+                    let count = match raw {
+                        Some(_) => 1,
+                        None => indent.count(),
+                    };
+
+                    if self.whitespace_idx == count + extra {
+                        self.whitespace_idx = 0;
+                        self.state = State::Comment;
+                        continue;
+                    }
+
+                    self.whitespace_idx += 1;
+                    break Some(match (self.whitespace_idx <= count, raw) {
+                        (true, Some(raw)) => raw,
+                        (true, None) => indent.as_str(),
+                        (false, _) => " ",
+                    });
+                }
+
+                State::Comment => {
+                    self.state = match self.column_width {
+                        // Continuation lines replace padding and bullet.
+                        Some(_) if self.word_idx > 1 => State::Column,
+                        _ => State::Padding,
+                    };
+
+                    if let Some(token) = self.line.comment {
+                        break Some(token);
+                    }
+                }
+
+                State::Column if Some(self.whitespace_idx) == self.column_width => {
+                    self.whitespace_idx = 0;
+                    self.state = State::Words;
+                }
+
+                State::Column => {
+                    self.whitespace_idx += 1;
+                    break Some(" ");
+                }
+
+                State::Padding if self.whitespace_idx == self.line.padding.count() => {
+                    self.whitespace_idx = 0;
+                    self.state = State::Bullet;
+                }
+
+                State::Padding => {
+                    self.whitespace_idx += 1;
+                    break Some(self.line.padding.as_str());
+                }
+
+                State::Bullet => {
+                    let token = match self.line.bullet {
+                        Some(token) => token,
+                        // Continued lines may hang without a bullet, see
+                        // [Toppings::continuation].
+                        None if self.word_idx > 1 && self.hanging_width > 0 => {
+                            self.state = State::BulletSpace;
+                            continue;
+                        }
+                        None => {
+                            self.state = State::Words;
+                            continue;
+                        }
+                    };
+
+                    // No more words come after this bullet, don't insert space.
+                    if self.pending.is_none() {
+                        // Go back to words to finalize the line.
+                        self.state = State::Words;
+                        break Some(token);
+                    }
+
+                    self.state = State::BulletSpace;
+
+                    if self.word_idx == 1 {
+                        // Only add a single space, after the bullet.
+                        self.whitespace_idx = self.bullet_width.saturating_sub(1);
+                        break Some(token);
+                    }
+                }
+
+                State::BulletSpace if self.whitespace_idx == self.bullet_space() => {
+                    self.whitespace_idx = 0;
+                    self.state = State::Words;
+                }
+
+                State::BulletSpace => {
+                    self.whitespace_idx += 1;
+                    break Some(" ");
+                }
+
+                State::Final => {
+                    break None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Guacamole, Mole, Penalties, Salsa, SalsaMinLines};
+    use crate::{KeepRule, Line, Toppings, Whitespace::*};
+
+    use std::sync::LazyLock;
+
+    static MINI_LINE: LazyLock<Toppings> = LazyLock::new(|| Toppings::default().width(10));
+    static HUGE_LINE: LazyLock<Toppings> = LazyLock::new(|| Toppings::default().width(1000));
+
+    fn salsa<'t>(line: Line<'t>, toppings: &Toppings) -> Vec<&'t str> {
+        super::LineWrap::new::<Salsa>(line, toppings).collect()
+    }
+
+    fn guacamole<'t>(line: Line<'t>, toppings: &Toppings) -> Vec<&'t str> {
+        super::LineWrap::new::<Guacamole>(line, toppings).collect()
+    }
+
+    fn salsa_min_lines<'t>(line: Line<'t>, toppings: &Toppings) -> Vec<&'t str> {
+        super::LineWrap::new::<SalsaMinLines>(line, toppings).collect()
+    }
+
+    /// For tests where we expect [Salsa] and [Guacamole] to yield equal
+    /// results.
+    #[track_caller]
+    fn all<'t>(line: Line<'t>, toppings: &Toppings) -> Vec<&'t str> {
+        let salsa = salsa(line.clone(), toppings);
+        let guacamole = guacamole(line, toppings);
+        assert_eq!(salsa, guacamole);
+        salsa
+    }
+
+    /// Tests for inputs that yield just a single line.
+    mod single_line {
+        use super::*;
+        use crate::{Newline, line};
+
+        #[test]
+        fn empty() {
+            assert_eq!(
+                all(line!(Space(0), None, Space(0), None), &HUGE_LINE),
+                Vec::<&str>::new()
+            );
+        }
+
+        #[test]
+        fn indent_space() {
+            assert_eq!(
+                all(line!(Space(4), None, Space(0), None), &HUGE_LINE),
+                vec![" ", " ", " ", " "]
+            );
+        }
+
+        #[test]
+        fn indent_tab() {
+            assert_eq!(
+                all(line!(Tab(2), None, Space(0), None), &HUGE_LINE),
+                vec!["\t", "\t"]
+            );
+        }
+
+        #[test]
+        fn comment() {
+            assert_eq!(
+                all(line!(Space(0), Some("//"), Space(0), None), &HUGE_LINE),
+                vec!["//"]
+            );
+        }
+
+        #[test]
+        fn padding_space() {
+            assert_eq!(
+                all(line!(Space(0), None, Space(4), None), &HUGE_LINE),
+                vec![" ", " ", " ", " "]
+            );
+        }
+
+        #[test]
+        fn padding_tab() {
+            assert_eq!(
+                all(line!(Space(0), None, Tab(2), None), &HUGE_LINE),
+                vec!["\t", "\t"]
+            );
+        }
+
+        #[test]
+        fn bullet() {
+            assert_eq!(
+                all(line!(Space(0), None, Space(0), Some("123.")), &HUGE_LINE),
+                vec!["123."]
+            );
+        }
+
+        #[test]
+        fn words() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "foo", "bar", "baz"),
+                    &HUGE_LINE
+                ),
+                vec!["foo", " ", "bar", " ", "baz"]
+            );
+        }
+
+        #[test]
+        fn preserved_gaps() {
+            assert_eq!(
+                all(
+                    Line {
+                        gaps: vec![(1, Space(2))],
+                        ..line!(Space(0), None, Space(0), None, "foo", "bar", "baz")
+                    },
+                    &HUGE_LINE
+                ),
+                vec!["foo", " ", " ", "bar", " ", "baz"]
+            );
+        }
+
+        #[test]
+        fn verbatim() {
+            assert_eq!(
+                all(
+                    Line {
+                        verbatim: Some("  foo   bar "),
+                        ..line!(Space(2), None, Space(0), None, "foo", "bar" ;)
+                    },
+                    &MINI_LINE
+                ),
+                vec!["  foo   bar ", "\n"]
+            );
+        }
+
+        #[test]
+        fn final_newline_lf() {
+            assert_eq!(
+                all(line!(Space(0), None, Space(0), None ;), &HUGE_LINE),
+                vec!["\n"]
+            );
+        }
+
+        #[test]
+        fn final_newline_crlf() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None ;),
+                    &HUGE_LINE.clone().newline(Newline::CRLF)
+                ),
+                vec!["\r\n"]
+            );
+        }
+
+        #[test]
+        fn indent_and_padding() {
+            assert_eq!(
+                all(line!(Tab(2), None, Space(2), None), &HUGE_LINE),
+                vec!["\t", "\t", " ", " "]
+            );
+        }
+
+        #[test]
+        fn all_together() {
+            assert_eq!(
+                all(
+                    line!(Tab(2), Some("//"), Space(2), Some("-"), "foo", "bar", "baz" ;),
+                    &HUGE_LINE
+                ),
+                vec![
+                    "\t", "\t", "//", " ", " ", "-", " ", "foo", " ", "bar", " ", "baz", "\n"
+                ]
+            );
+        }
+    }
+
+    /// Tests for inputs that should yield several lines.
+    mod multi_line {
+        use super::*;
+        use crate::line;
+
+        #[test]
+        fn words() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "foo", "bar", "baz"),
+                    &MINI_LINE
+                ),
+                vec!["foo", " ", "bar", "\n", "baz"]
+            );
+        }
+
+        #[test]
+        fn big_word() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "foo", "barbarbarbar", "baz"),
+                    &MINI_LINE
+                ),
+                vec!["foo", "\n", "barbarbarbar", "\n", "baz"]
+            );
+        }
+
+        #[test]
+        fn indent_space() {
+            assert_eq!(
+                all(
+                    line!(Space(2), None, Space(0), None, "foo", "bar", "baz" ;),
+                    &MINI_LINE.clone().tabs(1)
+                ),
+                vec![" ", " ", "foo", " ", "bar", "\n", " ", " ", "baz", "\n"]
+            );
+        }
+
+        #[test]
+        fn indent_low_tabs() {
+            assert_eq!(
+                all(
+                    line!(Tab(2), None, Space(0), None, "foo", "bar", "baz" ;),
+                    &MINI_LINE.clone().tabs(1)
+                ),
+                vec!["\t", "\t", "foo", " ", "bar", "\n", "\t", "\t", "baz", "\n"]
+            );
+        }
+
+        #[test]
+        fn indent_high_tabs() {
+            assert_eq!(
+                all(
+                    line!(Tab(2), None, Space(0), None, "foo", "bar", "baz"),
+                    &MINI_LINE.clone().tabs(8)
+                ),
+                vec![
+                    "\t", "\t", "foo", "\n", "\t", "\t", "bar", "\n", "\t", "\t", "baz"
+                ]
+            );
+        }
+
+        #[test]
+        fn comment() {
+            assert_eq!(
+                all(
+                    line!(Space(0), Some("//"), Space(1), None, "foo", "bar", "baz"),
+                    &MINI_LINE
+                ),
+                vec!["//", " ", "foo", " ", "bar", "\n", "//", " ", "baz"]
+            );
+        }
+
+        #[test]
+        fn bullet() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), Some("1."), "foo", "bar", "baz"),
+                    &MINI_LINE
+                ),
+                vec!["1.", " ", "foo", " ", "bar", "\n", " ", " ", " ", "baz"]
+            );
+        }
+
+        #[test]
+        fn numbered_bullet_in_doc_comment() {
+            assert_eq!(
+                all(
+                    line!(
+                        Space(4),
+                        Some("///"),
+                        Space(1),
+                        Some("10."),
+                        "foo",
+                        "bar",
+                        "baz"
+                    ),
+                    &Toppings::default().width(16)
+                ),
+                vec![
+                    " ", " ", " ", " ", "///", " ", "10.", " ", "foo", "\n", " ", " ", " ", " ",
+                    "///", " ", " ", " ", " ", " ", "bar", "\n", " ", " ", " ", " ", "///", " ",
+                    " ", " ", " ", " ", "baz"
+                ]
+            );
+        }
+
+        #[test]
+        fn all_together() {
+            assert_eq!(
+                all(
+                    line!(Tab(2), Some("#"), Space(1), Some("-"), "foo", "bar", "baz" ;),
+                    &MINI_LINE
+                ),
+                vec![
+                    "\t", "\t", "#", " ", "-", " ", "foo", "\n", "\t", "\t", "#", " ", " ", " ",
+                    "bar", "\n", "\t", "\t", "#", " ", " ", " ", "baz", "\n"
+                ]
+            );
+        }
+
+        #[test]
+        fn mixed_newlines() {
+            let input = "a\n\r\n\nb";
+
+            assert_eq!(
+                crate::wrap::<Salsa>(input, HUGE_LINE.clone()).collect::<Vec<_>>(),
+                vec!["a", "\n", "\n", "\n", "b"]
+            );
+            assert_eq!(
+                crate::wrap::<Salsa>(input, HUGE_LINE.clone().newline(crate::Newline::CRLF))
+                    .collect::<String>(),
+                "a\r\n\r\n\r\nb"
+            );
+        }
+    }
+
+    /// Tests for newlines between paragraphs of different kinds, going through
+    /// the entire pipeline.
+    mod boundaries {
+        use super::*;
+
+        #[track_caller]
+        fn assert_reflowed(input: &str, expected: &str) {
+            assert_eq!(
+                crate::wrap::<Salsa>(input, HUGE_LINE.clone()).collect::<String>(),
+                expected
+            );
+            assert_eq!(
+                crate::wrap::<Guacamole>(input, HUGE_LINE.clone()).collect::<String>(),
+                expected
+            );
+        }
+
+        #[test]
+        fn comment_to_code() {
+            assert_reflowed("// foo\n// bar\nfn baz() {}\n", "// foo bar\nfn baz() {}\n");
+        }
+
+        #[test]
+        fn bullet_to_prose() {
+            assert_reflowed("- foo\n  bar\nbaz\nqux\n", "- foo bar\nbaz qux\n");
+        }
+
+        #[test]
+        fn prose_to_bullet() {
+            assert_reflowed("foo\nbar\n- baz\n  qux", "foo bar\n- baz qux");
+        }
+
+        #[test]
+        fn blank_line() {
+            assert_reflowed("- foo\n  bar\n\nbaz\n", "- foo bar\n\nbaz\n");
+        }
+
+        #[test]
+        fn blank_comment_line() {
+            assert_reflowed("# foo\n# bar\n#\n# baz\n", "# foo bar\n#\n# baz\n");
+        }
+
+        #[test]
+        fn leading_and_trailing_blank_lines() {
+            assert_reflowed("\n\nfoo\nbar\n\n\n", "\n\nfoo bar\n\n\n");
+        }
+    }
+
+    /// Tests for inputs without any words, going through the entire pipeline.
+    mod whitespace_only {
+        use super::*;
+
+        #[track_caller]
+        fn assert_round_trip(input: &str) {
+            assert_eq!(
+                crate::wrap::<Salsa>(input, Toppings::default()).collect::<String>(),
+                input
+            );
+            assert_eq!(
+                crate::wrap::<Guacamole>(input, Toppings::default()).collect::<String>(),
+                input
+            );
+            assert_eq!(
+                crate::wrap::<SalsaMinLines>(input, Toppings::default()).collect::<String>(),
+                input
+            );
+        }
+
+        #[test]
+        fn empty() {
+            assert_eq!(crate::wrap::<Salsa>("", Toppings::default()).next(), None);
+            assert_eq!(
+                crate::wrap::<Guacamole>("", Toppings::default()).next(),
+                None
+            );
+        }
+
+        #[test]
+        fn spaces() {
+            assert_round_trip("   ");
+        }
+
+        #[test]
+        fn newlines() {
+            assert_round_trip("\n\n");
+        }
+
+        #[test]
+        fn tab_and_spaces() {
+            assert_round_trip("\t  \n");
+        }
+
+        #[test]
+        fn trailing_indent() {
+            assert_round_trip(" \n\t");
+        }
+
+        #[test]
+        fn mixed_tabs_and_spaces() {
+            assert_round_trip("\t \t\n");
+            assert_round_trip(" \t \t");
+            assert_round_trip("  \t \n");
+        }
+    }
+
+    /// Tests for words that have a width of 0 (e.g., a zero-width space).
+    mod zero_width {
+        use super::*;
+        use crate::line;
+
+        #[test]
+        fn fits() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "foo", "\u{200B}", "bar"),
+                    &Toppings::default().width(8)
+                ),
+                vec!["foo", " ", "\u{200B}", " ", "bar"]
+            );
+        }
+
+        #[test]
+        fn starts_line() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "foo", "\u{200B}", "bar"),
+                    &Toppings::default().width(3)
+                ),
+                vec!["foo", "\n", "\u{200B}", "\n", "bar"]
+            );
+        }
+
+        #[test]
+        fn ends_line() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "foo", "\u{200B}", "bar"),
+                    &Toppings::default().width(4)
+                ),
+                vec!["foo", " ", "\u{200B}", "\n", "bar"]
+            );
+        }
+
+        #[test]
+        fn zero_width_line() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "\u{200B}", "\u{301}"),
+                    &Toppings::default().width(0)
+                ),
+                vec!["\u{200B}", "\n", "\u{301}"]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::collapse_spaces], which keeps original gaps.
+    mod gaps {
+        use super::*;
+        use crate::line;
+
+        #[test]
+        fn fitting_line() {
+            assert_eq!(
+                all(
+                    Line {
+                        gaps: vec![(1, Space(2)), (2, Tab(1)), (2, Space(1))],
+                        ..line!(Space(0), None, Space(0), None, "foo", "bar", "baz")
+                    },
+                    &HUGE_LINE
+                ),
+                vec!["foo", " ", " ", "bar", "\t", " ", "baz"]
+            );
+        }
+
+        #[test]
+        fn gap_replaced_by_break() {
+            assert_eq!(
+                all(
+                    Line {
+                        gaps: vec![(1, Space(2)), (2, Space(2))],
+                        ..line!(Space(0), None, Space(0), None, "foo", "bar", "baz")
+                    },
+                    &MINI_LINE
+                ),
+                vec!["foo", " ", " ", "bar", "\n", "baz"]
+            );
+        }
+
+        #[test]
+        fn tab_fits() {
+            // 3 + 4 + 3 columns.
+            assert_eq!(
+                all(
+                    Line {
+                        gaps: vec![(1, Tab(1))],
+                        ..line!(Space(0), None, Space(0), None, "foo", "bar")
+                    },
+                    &MINI_LINE
+                ),
+                vec!["foo", "\t", "bar"]
+            );
+        }
+
+        #[test]
+        fn tab_overfills() {
+            // 3 + 4 + 4 columns.
+            assert_eq!(
+                all(
+                    Line {
+                        gaps: vec![(1, Tab(1))],
+                        ..line!(Space(0), None, Space(0), None, "foo", "barr")
+                    },
+                    &MINI_LINE
+                ),
+                vec!["foo", "\n", "barr"]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::trailing_space_on_break].
+    mod trailing_space_on_break {
+        use super::*;
+        use crate::line;
+
+        #[test]
+        fn trailing_space() {
+            assert_eq!(
+                all(
+                    line!(Space(0), Some("#"), Space(1), None, "foo", "bar", "baz" ;),
+                    &MINI_LINE.clone().trailing_space_on_break(true)
+                ),
+                vec![
+                    "#", " ", "foo", " ", "bar", " ", "\n", "#", " ", "baz", "\n"
+                ]
+            );
+        }
+    }
+
+    /// Tests for bullets that are wider than the line by themselves.
+    mod wide_bullet {
+        use super::*;
+        use crate::line;
+
+        static TINY_LINE: LazyLock<Toppings> = LazyLock::new(|| Toppings::default().width(3));
+
+        #[test]
+        fn one_word_per_line() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), Some("1000."), "foo", "bar", "baz" ;),
+                    &TINY_LINE
+                ),
+                vec![
+                    "1000.", " ", "foo", "\n", " ", " ", " ", " ", " ", " ", "bar", "\n", " ", " ",
+                    " ", " ", " ", " ", "baz", "\n"
+                ]
+            );
+        }
+
+        #[test]
+        fn bullet_only() {
+            assert_eq!(
+                all(line!(Space(0), None, Space(0), Some("1000.") ;), &TINY_LINE),
+                vec!["1000.", "\n"]
+            );
+        }
+
+        #[test]
+        fn words_not_broken() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), Some("1000."), "foo", "bar"),
+                    &TINY_LINE.clone().break_long_words(true)
+                ),
+                vec![
+                    "1000.", " ", "foo", "\n", " ", " ", " ", " ", " ", " ", "bar"
+                ]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::fill].
+    mod fill {
+        use super::*;
+        use crate::{Fill, line};
+
+        #[test]
+        fn all_but_last() {
+            assert_eq!(
+                all(
+                    line!(Space(0), Some("#"), Space(1), None, "foo", "bar", "baz", "qux" ;),
+                    &MINI_LINE.clone().fill(Fill::AllButLast)
+                ),
+                vec![
+                    "#", " ", "foo", " ", "bar", " ", "\n", "#", " ", "baz", " ", "qux", "\n"
+                ]
+            );
+        }
+
+        #[test]
+        fn every_line() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "foo", "bar", "bazbaz" ;),
+                    &MINI_LINE.clone().fill(Fill::All)
+                ),
+                vec![
+                    "foo", " ", "bar", " ", " ", " ", "\n", "bazbaz", " ", " ", " ", " ", "\n"
+                ]
+            );
+        }
+
+        #[test]
+        fn overlong() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "foofoofoofoo", "bar"),
+                    &MINI_LINE.clone().fill(Fill::All)
+                ),
+                vec![
+                    "foofoofoofoo",
+                    "\n",
+                    "bar",
+                    " ",
+                    " ",
+                    " ",
+                    " ",
+                    " ",
+                    " ",
+                    " "
+                ]
+            );
+        }
+
+        #[test]
+        fn empty_line() {
+            assert_eq!(
+                all(
+                    line!(Space(2), Some("#"), Space(0), None ;),
+                    &MINI_LINE.clone().fill(Fill::All)
+                ),
+                vec![" ", " ", "#", "\n"]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::break_long_words].
+    mod long_words {
+        use super::*;
+        use crate::line;
+
+        #[test]
+        fn fragments_keep_grapheme_clusters() {
+            // "é" as "e" + combining acute accent, then a family emoji (ZWJ
+            // sequence of width 2):
+            let word = "abe\u{301}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}cd";
+
+            assert_eq!(
+                super::super::fragments(word, 3, true).collect::<Vec<_>>(),
+                vec![
+                    "abe\u{301}",
+                    "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}c",
+                    "d"
+                ]
+            );
+        }
+
+        #[test]
+        fn protected_url() {
+            let line = || {
+                line!(
+                    Space(2),
+                    Some("//"),
+                    Space(1),
+                    None,
+                    "see",
+                    "https://example.com/foo",
+                    "bar"
+                )
+            };
+            let toppings = MINI_LINE.clone().break_long_words(true);
+
+            assert_eq!(
+                all(line(), &toppings.clone().protect_urls(true)).concat(),
+                "  // see\n  // https://example.com/foo\n  // bar"
+            );
+            assert_eq!(
+                all(line(), &toppings).concat(),
+                "  // see\n  // https\n  // ://ex\n  // ample\n  // .com/\n  // foo\n  // bar"
+            );
+        }
+
+        #[test]
+        fn fragments_wider_than_max() {
+            assert_eq!(
+                super::super::fragments("\u{1F600}\u{1F600}", 1, true).collect::<Vec<_>>(),
+                vec!["\u{1F600}", "\u{1F600}"]
+            );
+        }
+
+        #[test]
+        fn broken_onto_own_lines() {
+            assert_eq!(
+                all(
+                    line!(
+                        Space(0),
+                        Some("#"),
+                        Space(1),
+                        None,
+                        "foo",
+                        "barbarbarbar",
+                        "baz"
+                    ),
+                    &MINI_LINE.clone().break_long_words(true)
+                ),
+                vec![
+                    "#", " ", "foo", "\n", "#", " ", "barbarba", "\n", "#", " ", "rbar", " ", "baz"
+                ]
+            );
+        }
+
+        #[test]
+        fn disabled_by_default() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "barbarbarbar"),
+                    &MINI_LINE
+                ),
+                vec!["barbarbarbar"]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::protect], going through the entire pipeline.
+    #[cfg(feature = "regex")]
+    mod protect {
+        use super::*;
+
+        use regex::Regex;
+
+        #[test]
+        fn date_not_broken_apart() {
+            let toppings = Toppings::default().width(6).break_long_words(true);
+            assert_eq!(
+                crate::wrap::<Salsa>("on 2024-01-02", toppings.clone()).collect::<String>(),
+                "on\n2024-0\n1-02"
+            );
+
+            let toppings = toppings.protect(Regex::new(r"\d{4}-\d{2}-\d{2}").unwrap());
+            assert_eq!(
+                crate::wrap::<Salsa>("on 2024-01-02", toppings).collect::<String>(),
+                "on\n2024-01-02"
+            );
+        }
+
+        #[test]
+        fn date_not_split() {
+            let toppings = Toppings::default()
+                .width(6)
+                .enforce_width(true)
+                .intra_word_penalty(0)
+                .protect(Regex::new(r"\d{4}-\d{2}-\d{2}").unwrap());
+            assert_eq!(
+                crate::wrap::<Salsa>("on 2024-01-02 ok", toppings).collect::<String>(),
+                "on\n2024-01-02\nok"
+            );
+        }
+
+        #[test]
+        fn one_wide_word() {
+            let toppings = Toppings::default()
+                .width(16)
+                .protect(Regex::new(r"\d+\s+km").unwrap());
+            let wrap = |input| crate::wrap::<Salsa>(input, toppings.clone()).collect::<String>();
+
+            // Without the match, "10" would still fit on the first line.
+            assert_eq!(wrap("foo bar baz 10 km"), "foo bar baz\n10 km");
+            assert_eq!(wrap("foo bar baz 10   km"), "foo bar baz\n10   km");
+            assert_eq!(wrap("foo bar baz (10 km)"), "foo bar baz\n(10 km)");
+        }
+    }
+
+    /// Tests for [Toppings::hyphenate], going through the entire pipeline.
+    #[cfg(feature = "hyphen")]
+    mod hyphenate {
+        use super::super::Auto;
+        use super::*;
+        use crate::Align;
+
+        const INPUT: &str = "see internationalization";
+
+        #[test]
+        fn salsa() {
+            let toppings = Toppings::default().width(13);
+            assert_eq!(
+                crate::wrap::<Salsa>(INPUT, toppings.clone()).collect::<String>(),
+                "see\ninternationalization"
+            );
+            assert_eq!(
+                crate::wrap::<Salsa>(INPUT, toppings.hyphenate("en")).collect::<String>(),
+                "see interna-\ntionalization"
+            );
+        }
+
+        #[test]
+        fn penalty() {
+            let toppings = Toppings::default().width(20).hyphenate("en");
+            assert_eq!(
+                crate::wrap::<Salsa>(INPUT, toppings.clone()).collect::<String>(),
+                "see international-\nization"
+            );
+            assert_eq!(
+                crate::wrap::<Salsa>(INPUT, toppings.intra_word_penalty(300)).collect::<String>(),
+                "see\ninternationalization"
+            );
+        }
+
+        #[test]
+        fn auto() {
+            // Guacamole leaves room for the hyphen, too.
+            let toppings = Toppings::default().width(10).hyphenate("en");
+            assert_eq!(
+                crate::wrap::<Auto<2>>(INPUT, toppings).collect::<String>(),
+                "see\ninterna-\ntionaliza-\ntion"
+            );
+        }
+
+        #[test]
+        fn justify() {
+            let toppings = Toppings::default().width(15).justify(true).hyphenate("en");
+            assert_eq!(
+                crate::wrap::<Salsa>("a see internationalization", toppings).collect::<String>(),
+                "a  see interna-\ntionalization"
+            );
+        }
+
+        #[test]
+        fn align_right() {
+            let toppings = Toppings::default()
+                .width(14)
+                .align(Align::Right)
+                .hyphenate("en");
+            assert_eq!(
+                crate::wrap::<Salsa>(INPUT, toppings).collect::<String>(),
+                "  see interna-\n tionalization"
+            );
+        }
+
+        #[test]
+        fn preserved_gaps() {
+            let toppings = Toppings::default()
+                .width(14)
+                .collapse_spaces(false)
+                .hyphenate("en");
+            assert_eq!(
+                crate::wrap::<Salsa>("see  internationalization", toppings).collect::<String>(),
+                "see  interna-\ntionalization"
+            );
+        }
+
+        #[test]
+        fn unknown_language() {
+            let toppings = Toppings::default().width(13).hyphenate("de");
+            assert_eq!(
+                crate::wrap::<Salsa>(INPUT, toppings).collect::<String>(),
+                "see\ninternationalization"
+            );
+        }
+
+        #[test]
+        fn not_breaking_words() {
+            let toppings = Toppings::default().width(13).hyphenate("en");
+            assert_eq!(
+                crate::wrap::<Guacamole>(INPUT, toppings).collect::<String>(),
+                "see\ninternationalization"
+            );
+        }
+    }
+
+    /// Tests for [crate::layout].
+    mod layout {
+        use super::*;
+        use crate::WordPlacement;
+
+        #[test]
+        fn pieces() {
+            let toppings = Toppings::default()
+                .width(10)
+                .break_long_words(true)
+                .intra_word_penalty(10);
+            let input = "aaaa bbbbbbbb";
+            assert_eq!(
+                crate::wrap::<Salsa>(input, toppings.clone()).collect::<String>(),
+                "aaaa bbbbb\nbbb"
+            );
+
+            let placement = |source_range, line, start_col, end_col| WordPlacement {
+                source_range,
+                line,
+                start_col,
+                end_col,
+            };
+            assert_eq!(
+                crate::layout::<Salsa>(input, toppings),
+                vec![
+                    placement(0..4, 0, 0, 4),
+                    placement(5..10, 0, 5, 10),
+                    placement(10..13, 1, 0, 3),
+                ]
+            );
+        }
+    }
+
+    /// Tests for [crate::wrap_bytes].
+    mod wrap_bytes {
+        use super::*;
+
+        use std::borrow::Cow;
+
+        #[test]
+        fn paragraphs() {
+            let input = b"foo \xff bar\n\nbaz \xfe\xfd qux\nquux\n";
+            let output = crate::wrap_bytes::<Salsa>(input, MINI_LINE.clone().width(7))
+                .flat_map(Cow::into_owned)
+                .collect::<Vec<_>>();
+            assert_eq!(output, b"foo \xff\nbar\n\nbaz\n\xfe\xfd qux\nquux\n");
+        }
+
+        #[test]
+        fn borrowed() {
+            let input = b"foo\xff bar baz";
+            let chunks =
+                crate::wrap_bytes::<Salsa>(input, MINI_LINE.clone().width(8)).collect::<Vec<_>>();
+            assert_eq!(chunks.concat(), b"foo\xff bar\nbaz");
+            assert!(matches!(chunks[0], Cow::Borrowed(b"foo\xff")));
+        }
+
+        #[test]
+        fn valid() {
+            let input = "foo bar baz\n\nqux";
+            let output = crate::wrap_bytes::<Salsa>(input.as_bytes(), MINI_LINE.clone())
+                .flat_map(Cow::into_owned)
+                .collect::<Vec<_>>();
+            let expected = crate::wrap::<Salsa>(input, MINI_LINE.clone()).collect::<String>();
+            assert_eq!(output, expected.as_bytes());
+        }
+    }
+
+    /// Tests for [Toppings::enforce_width].
+    mod enforce_width {
+        use super::*;
+        use crate::line;
+
+        static STRICT: LazyLock<Toppings> =
+            LazyLock::new(|| Toppings::default().width(8).enforce_width(true));
+
+        #[test]
+        fn long_word() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "abcdefghijklmnopqrst"),
+                    &STRICT
+                ),
+                vec!["abcdefgh", "\n", "ijklmnop", "\n", "qrst"]
+            );
+        }
+
+        #[test]
+        fn narrower_first_line() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "abcdefghijklmnopqrst"),
+                    &STRICT.clone().first_line_indent(4)
+                ),
+                vec![
+                    " ", " ", " ", " ", "abcd", "\n", "efghijkl", "\n", "mnopqrst"
+                ]
+            );
+        }
+
+        #[test]
+        fn narrower_continuation_lines() {
+            assert_eq!(
+                all(
+                    line!(
+                        Space(0),
+                        Some("#"),
+                        Space(1),
+                        Some("-"),
+                        "foo",
+                        "abcdefghij"
+                    ),
+                    &STRICT
+                ),
+                vec![
+                    "#", " ", "-", " ", "foo", "\n", "#", " ", " ", " ", "abcd", "\n", "#", " ",
+                    " ", " ", "efgh", "\n", "#", " ", " ", " ", "ij"
+                ]
+            );
+        }
+
+        #[test]
+        fn keep_yields() {
+            let keep = |word: &str, _: &str| match word {
+                "foo" => KeepRule::KeepWithNext,
+                _ => KeepRule::Allow,
+            };
+
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "foo", "barbaz"),
+                    &STRICT.clone().keep(keep)
+                ),
+                vec!["foo", "\n", "barbaz"]
+            );
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "foo", "barbaz"),
+                    &MINI_LINE.clone().keep(keep).width(8)
+                ),
+                vec!["foo", " ", "barbaz"]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::intra_word_penalty].
+    mod intra_word_penalty {
+        use super::*;
+        use crate::{Auto, line};
+
+        static BREAKING: LazyLock<Toppings> =
+            LazyLock::new(|| MINI_LINE.clone().break_long_words(true));
+
+        #[test]
+        fn only_if_cheaper() {
+            let line = || line!(Space(0), None, Space(0), None, "aaaa", "bbbbbbbb");
+            assert_eq!(
+                salsa(line(), &BREAKING.clone().intra_word_penalty(100)).concat(),
+                "aaaa\nbbbbbbbb"
+            );
+            assert_eq!(
+                salsa(line(), &BREAKING.clone().intra_word_penalty(10)).concat(),
+                "aaaa bbbbb\nbbb"
+            );
+        }
+
+        #[test]
+        fn long_word() {
+            let line = || line!(Space(0), None, Space(0), None, "aa", "bbbbbbbbbbbbbb");
+            let toppings = BREAKING.clone().intra_word_penalty(100);
+            assert_eq!(salsa(line(), &toppings).concat(), "aa bbbbbbb\nbbbbbbb");
+            // Guacamole doesn't break words apart itself.
+            assert_eq!(
+                guacamole(line(), &toppings).concat(),
+                "aa\nbbbbbbbbbb\nbbbb"
+            );
+        }
+
+        #[test]
+        fn enforce_width() {
+            let line = line!(Space(0), None, Space(0), None, "aaaa", "bbbbbbbb");
+            let toppings = MINI_LINE.clone().enforce_width(true).intra_word_penalty(10);
+            assert_eq!(salsa(line, &toppings).concat(), "aaaa bbbbb\nbbb");
+        }
+
+        #[test]
+        fn auto() {
+            let line = || line!(Space(0), None, Space(0), None, "aaaa", "bbbbbbbb");
+            let toppings = BREAKING.clone().intra_word_penalty(10);
+
+            // Salsa breaks the word apart, as it's cheaper than a ragged line.
+            assert_eq!(
+                super::super::LineWrap::new::<Auto>(line(), &toppings).collect::<String>(),
+                "aaaa bbbbb\nbbb"
+            );
+            // Guacamole only breaks apart words that don't fit on a line.
+            assert_eq!(
+                super::super::LineWrap::new::<Auto<2>>(line(), &toppings).collect::<String>(),
+                "aaaa\nbbbbbbbb"
+            );
+            assert_eq!(
+                super::super::LineWrap::new::<Auto<2>>(
+                    line!(Space(0), None, Space(0), None, "aa", "bbbbbbbbbbbbbb"),
+                    &toppings
+                )
+                .collect::<String>(),
+                "aa\nbbbbbbbbbb\nbbbb"
+            );
+        }
+
+        #[test]
+        fn min_last_line_words() {
+            let line = line!(
+                Space(0),
+                None,
+                Space(0),
+                None,
+                "aaaaaaaaaa",
+                "bbbbbbbbbbb",
+                "ccccc"
+            );
+            let toppings = BREAKING.clone().width(12).min_last_line_words(2);
+            // The rest of a word broken apart doesn't count as a word.
+            assert_eq!(
+                salsa(line, &toppings.intra_word_penalty(100)).concat(),
+                "aaaaaaaaaa\nbbbbbbbbbbb\nccccc"
+            );
+        }
+
+        #[test]
+        fn justify() {
+            let line = line!(
+                Space(0),
+                None,
+                Space(0),
+                None,
+                "aaaaaaaaaaaaaa",
+                "ccc",
+                "dddddddddd"
+            );
+            assert_eq!(
+                salsa(line, &BREAKING.clone().intra_word_penalty(50).justify(true)).concat(),
+                "aaaaaaaaa\naaaaa  ccc\ndddddddddd"
+            );
+        }
+
+        #[test]
+        fn keep_brackets() {
+            // Words kept together are never broken apart by the sauce.
+            let line = line!(Space(0), None, Space(0), None, "aaaa", "bbbbbbbb");
+            let toppings = BREAKING.clone().intra_word_penalty(10).keep_brackets(true);
+            assert_eq!(salsa(line, &toppings).concat(), "aaaa\nbbbbbbbb");
+        }
+
+        #[test]
+        fn protect_urls() {
+            let line = line!(Space(0), None, Space(0), None, "see", "www.example.com");
+            assert_eq!(
+                salsa(
+                    line,
+                    &BREAKING.clone().intra_word_penalty(0).protect_urls(true)
+                )
+                .concat(),
+                "see\nwww.example.com"
+            );
+        }
+    }
+
+    /// Tests for [Toppings::continuation_column].
+    mod continuation_column {
+        use super::*;
+        use crate::line;
+
+        static COLUMN: LazyLock<Toppings> =
+            LazyLock::new(|| Toppings::default().width(16).continuation_column(8));
+
+        #[test]
+        fn plain() {
+            assert_eq!(
+                all(
+                    line!(
+                        Space(0),
+                        None,
+                        Space(0),
+                        None,
+                        "foo",
+                        "bar",
+                        "baz",
+                        "qux",
+                        "quux"
+                    ),
+                    &COLUMN
+                ),
+                vec![
+                    "foo", " ", "bar", " ", "baz", " ", "qux", "\n", " ", " ", " ", " ", " ", " ",
+                    " ", " ", "quux"
+                ]
+            );
+        }
+
+        #[test]
+        fn comment_and_bullet() {
+            assert_eq!(
+                all(
+                    line!(
+                        Space(2),
+                        Some("//"),
+                        Space(1),
+                        Some("-"),
+                        "foo",
+                        "bar",
+                        "baz",
+                        "qux"
+                    ),
+                    &COLUMN
+                ),
+                vec![
+                    " ", " ", "//", " ", "-", " ", "foo", " ", "bar", "\n", " ", " ", "//", " ",
+                    " ", " ", " ", "baz", " ", "qux"
+                ]
+            );
+        }
+
+        #[test]
+        fn tabs() {
+            assert_eq!(
+                all(
+                    line!(Tab(1), Some("#"), Space(1), None, "foo", "bar", "baz"),
+                    &COLUMN
+                ),
+                vec![
+                    "\t", "#", " ", "foo", " ", "bar", "\n", "\t", "#", " ", " ", " ", "baz"
+                ]
+            );
+        }
+
+        #[test]
+        fn prefix_past_column() {
+            assert_eq!(
+                all(
+                    line!(Space(6), Some("//"), Space(1), None, "foo", "bar"),
+                    &Toppings::default().width(12).continuation_column(8)
+                ),
+                vec![
+                    " ", " ", " ", " ", " ", " ", "//", " ", "foo", "\n", " ", " ", " ", " ", " ",
+                    " ", "//", " ", "bar"
+                ]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::hanging].
+    mod hanging {
+        use super::*;
+        use crate::line;
+
+        #[test]
+        fn comment_and_bullet() {
+            assert_eq!(
+                all(
+                    line!(
+                        Space(2),
+                        Some("//"),
+                        Space(1),
+                        Some("-"),
+                        "foo",
+                        "bar",
+                        "baz",
+                        "qux"
+                    ),
+                    &Toppings::default().width(16).hanging(4)
+                ),
+                vec![
+                    " ", " ", "//", " ", "-", " ", "foo", " ", "bar", "\n", " ", " ", "//", " ",
+                    " ", " ", " ", " ", "baz", " ", "qux"
+                ]
+            );
+        }
+
+        #[test]
+        fn zero() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), Some("-"), "foo", "bar", "baz"),
+                    &Toppings::default().width(8).hanging(0)
+                ),
+                vec!["-", " ", "foo", "\n", "bar", " ", "baz"]
+            );
+        }
+
+        #[test]
+        fn no_bullet() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "foo", "bar", "baz"),
+                    &Toppings::default().width(8).hanging(4)
+                ),
+                vec!["foo", " ", "bar", "\n", "baz"]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::line_numbers].
+    mod gutter {
+        use super::*;
+        use crate::{Gutter, line};
+
+        fn numbered(gutter: &'static str) -> Line<'static> {
+            Line {
+                gutter: Some(gutter),
+                ..line!(Space(2), Some("//"), Space(1), None, "foo", "bar", "baz")
+            }
+        }
+
+        #[test]
+        fn repeat() {
+            let toppings = Toppings::default().width(16).line_numbers(Gutter::Repeat);
+
+            assert_eq!(
+                all(numbered(" 12"), &toppings),
+                vec![
+                    " 12", " ", " ", "//", " ", "foo", " ", "bar", "\n", " 12", " ", " ", "//",
+                    " ", "baz"
+                ]
+            );
+        }
+
+        #[test]
+        fn blank() {
+            let toppings = Toppings::default().width(16).line_numbers(Gutter::Blank);
+
+            assert_eq!(
+                all(numbered(" 12"), &toppings),
+                vec![
+                    " 12", " ", " ", "//", " ", "foo", " ", "bar", "\n", " ", " ", " ", " ", " ",
+                    "//", " ", "baz"
+                ]
+            );
+        }
+
+        #[test]
+        fn empty() {
+            let toppings = Toppings::default().line_numbers(Gutter::Blank);
+
+            assert_eq!(
+                all(
+                    Line {
+                        gutter: Some("7"),
+                        ..line!(Space(0), None, Space(0), None ;)
+                    },
+                    &toppings
+                ),
+                vec!["7", "\n"]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::output_indent].
+    mod output_indent {
+        use super::*;
+        use crate::line;
+
+        static BLOCK: LazyLock<Toppings> =
+            LazyLock::new(|| Toppings::default().width(12).output_indent(4));
+
+        #[test]
+        fn every_line() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "foo", "bar", "baz" ;),
+                    &BLOCK
+                ),
+                vec![
+                    " ", " ", " ", " ", "foo", " ", "bar", "\n", " ", " ", " ", " ", "baz", "\n"
+                ]
+            );
+        }
+
+        #[test]
+        fn on_top_of_indent() {
+            assert_eq!(
+                all(
+                    line!(Space(2), Some("#"), Space(1), None, "foo", "bar"),
+                    &BLOCK
+                ),
+                vec![
+                    " ", " ", " ", " ", " ", " ", "#", " ", "foo", "\n", " ", " ", " ", " ", " ",
+                    " ", "#", " ", "bar"
+                ]
+            );
+        }
+
+        #[test]
+        fn empty_line() {
+            assert_eq!(
+                all(line!(Space(0), None, Space(0), None ;), &BLOCK),
+                vec!["\n"]
+            );
+        }
+
+        #[test]
+        fn verbatim() {
+            assert_eq!(
+                all(
+                    Line {
+                        verbatim: Some("foo  bar"),
+                        ..line!(Space(0), None, Space(0), None, "foo", "bar" ;)
+                    },
+                    &BLOCK
+                ),
+                vec![" ", " ", " ", " ", "foo  bar", "\n"]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::max_indent].
+    mod max_indent {
+        use super::*;
+        use crate::line;
+
+        #[test]
+        fn exceeding_width() {
+            let toppings = Toppings::default().width(12).max_indent(4);
+
+            let mut expected = vec![" "; 100];
+            expected.extend(["foo", " ", "bar", "\n"]);
+            expected.extend(vec![" "; 100]);
+            expected.extend(["baz", " ", "qux"]);
+
+            assert_eq!(
+                all(
+                    line!(Space(100), None, Space(0), None, "foo", "bar", "baz", "qux"),
+                    &toppings
+                ),
+                expected
+            );
+        }
+
+        #[test]
+        fn below_maximum() {
+            let toppings = Toppings::default().width(12).max_indent(4);
+
+            assert_eq!(
+                all(
+                    line!(Tab(1), Some("#"), Space(1), None, "foo", "bar", "baz"),
+                    &toppings
+                ),
+                vec![
+                    "\t", "#", " ", "foo", "\n", "\t", "#", " ", "bar", "\n", "\t", "#", " ", "baz"
+                ]
+            );
+        }
+
+        #[test]
+        fn unset() {
+            assert_eq!(
+                all(
+                    line!(Space(8), None, Space(0), None, "foo", "bar"),
+                    &MINI_LINE
+                ),
+                vec![
+                    " ", " ", " ", " ", " ", " ", " ", " ", "foo", "\n", " ", " ", " ", " ", " ",
+                    " ", " ", " ", "bar"
+                ]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::exact_indent].
+    mod exact_indent {
+        use super::*;
+        use crate::{line, wrap};
+
+        #[test]
+        fn round_trip() {
+            let toppings = Toppings::default().width(16).exact_indent(true);
+            let input = "\t  \tfoo bar baz qux\n";
+
+            assert_eq!(
+                wrap::<Salsa>(input, toppings.clone()).collect::<String>(),
+                "\t  \tfoo\n\t  \tbar\n\t  \tbaz\n\t  \tqux\n"
+            );
+
+            let wide = toppings.width(80);
+            assert_eq!(wrap::<Salsa>(input, wide).collect::<String>(), input);
+        }
+
+        #[test]
+        fn first_line_indent() {
+            let toppings = Toppings::default().width(20).first_line_indent(2);
+
+            assert_eq!(
+                all(
+                    Line {
+                        raw_indent: Some(" \t"),
+                        ..line!(Space(5), None, Space(0), None, "foo", "bar", "baz")
+                    },
+                    &toppings
+                ),
+                vec![" \t", " ", " ", "foo", " ", "bar", " ", "baz"]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::tab_width_by_level].
+    mod tab_levels {
+        use super::*;
+        use crate::line;
+
+        #[test]
+        fn two_tabs() {
+            let line = line!(Tab(2), Some("#"), Space(1), None, "foo", "bar", "baz");
+            let toppings = Toppings::default()
+                .width(16)
+                .tab_width_by_level(|level| [2, 4][level]);
+
+            // 2 + 4 + 2 columns of prefix, leaving 8 for words.
+            assert_eq!(super::super::unbreakable_width(&line, &toppings), 8);
+            assert_eq!(
+                all(line.clone(), &toppings),
+                vec![
+                    "\t", "\t", "#", " ", "foo", " ", "bar", "\n", "\t", "\t", "#", " ", "baz"
+                ]
+            );
+
+            // Flat tabs leave only 6 columns for words.
+            assert_eq!(
+                all(line, &Toppings::default().width(16)),
+                vec![
+                    "\t", "\t", "#", " ", "foo", "\n", "\t", "\t", "#", " ", "bar", "\n", "\t",
+                    "\t", "#", " ", "baz"
+                ]
+            );
+        }
+
+        #[test]
+        fn indent_by_width() {
+            let toppings = Toppings::default()
+                .indent_by_width(true)
+                .tab_width_by_level(|level| [2, 4][level]);
+
+            // Two tabs are as wide as six spaces.
+            assert_eq!(
+                crate::wrap::<Salsa>("\t\tfoo\n      bar\n", toppings).collect::<String>(),
+                "\t\tfoo bar\n"
+            );
+            assert_eq!(
+                crate::wrap::<Salsa>(
+                    "\t\tfoo\n      bar\n",
+                    Toppings::default().indent_by_width(true)
+                )
+                .collect::<String>(),
+                "\t\tfoo\n      bar\n"
+            );
+        }
+
+        #[test]
+        fn list_numbers() {
+            let toppings = Toppings::default()
+                .align_list_numbers(true)
+                .tab_width_by_level(|level| [2, 4][level]);
+
+            // The numbers end at the same column, so they are aligned as one
+            // list.
+            assert_eq!(
+                crate::wrap::<Salsa>("\t\t9. foo\n      10. bar\n", toppings).collect::<String>(),
+                "\t\t 9. foo\n      10. bar\n"
+            );
+        }
+
+        #[test]
+        fn parsed_lines() {
+            let toppings = Toppings::default().tab_width_by_level(|level| [2, 4][level]);
+            let lines = crate::parse_lines("\t\tfoo\n", &toppings).collect::<Vec<_>>();
+
+            assert_eq!(lines[0].indent_width(), 6);
+        }
+    }
+
+    /// Tests for [Toppings::break_before_code].
+    mod break_before_code {
+        use super::*;
+        use crate::line;
+
+        #[test]
+        fn wide_span() {
+            let toppings = Toppings::default().width(20).break_before_code(50);
+
+            assert_eq!(
+                all(
+                    line!(
+                        Space(0),
+                        None,
+                        Space(0),
+                        None,
+                        "see",
+                        "`foo(bar,",
+                        "baz)`",
+                        "ok"
+                    ),
+                    &toppings
+                ),
+                vec!["see", "\n", "`foo(bar,", " ", "baz)`", " ", "ok"]
+            );
+        }
+
+        #[test]
+        fn short_span() {
+            let toppings = Toppings::default().width(20).break_before_code(50);
+
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "see", "`foo`", "ok"),
+                    &toppings
+                ),
+                vec!["see", " ", "`foo`", " ", "ok"]
+            );
+        }
+
+        #[test]
+        fn leading_span() {
+            let toppings = Toppings::default().width(20).break_before_code(10);
+
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "`foo`", "ok", "`bar`"),
+                    &toppings
+                ),
+                vec!["`foo`", " ", "ok", "\n", "`bar`"]
+            );
+        }
+
+        #[test]
+        fn unclosed_span() {
+            let toppings = Toppings::default().width(20).break_before_code(10);
+
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "see", "`foo", "ok"),
+                    &toppings
+                ),
+                vec!["see", " ", "`foo", " ", "ok"]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::min_last_line_words].
+    mod min_last_line_words {
+        use super::*;
+        use crate::line;
+
+        static TWO: LazyLock<Toppings> =
+            LazyLock::new(|| Toppings::default().width(12).min_last_line_words(2));
+
+        #[test]
+        fn pulled_down() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "aaaa", "bbbb", "cc", "d"),
+                    &TWO
+                ),
+                vec!["aaaa", " ", "bbbb", "\n", "cc", " ", "d"]
+            );
+        }
+
+        #[test]
+        fn orphan() {
+            let line = || {
+                line!(
+                    Space(0),
+                    None,
+                    Space(0),
+                    None,
+                    "aaa",
+                    "bbb",
+                    "ccc",
+                    "ddd",
+                    "e"
+                )
+            };
+            let toppings = Toppings::default().width(15);
+
+            assert_eq!(salsa(line(), &toppings).concat(), "aaa bbb ccc ddd\ne");
+            assert_eq!(
+                salsa(line(), &toppings.min_last_line_words(2)).concat(),
+                "aaa bbb ccc\nddd e"
+            );
+        }
+
+        #[test]
+        fn balanced_by_salsa() {
+            let line = || {
+                line!(
+                    Space(0),
+                    None,
+                    Space(0),
+                    None,
+                    "aaa",
+                    "b",
+                    "c",
+                    "d",
+                    "eeeeeee",
+                    "fff",
+                    "ggggggg"
+                )
+            };
+
+            assert_eq!(
+                salsa(line(), &TWO).concat(),
+                "aaa b c\nd eeeeeee\nfff ggggggg"
+            );
+            assert_eq!(
+                guacamole(line(), &TWO).concat(),
+                "aaa b c d\neeeeeee\nfff ggggggg"
+            );
+        }
+
+        #[test]
+        fn does_not_fit() {
+            assert_eq!(
+                all(
+                    line!(
+                        Space(0),
+                        None,
+                        Space(0),
+                        None,
+                        "aaaa",
+                        "bbbb",
+                        "cc",
+                        "dddddddddd"
+                    ),
+                    &TWO
+                ),
+                vec!["aaaa", " ", "bbbb", " ", "cc", "\n", "dddddddddd"]
+            );
+        }
+
+        #[test]
+        fn as_many_as_fit() {
+            let line = || {
+                line!(
+                    Space(0),
+                    None,
+                    Space(0),
+                    None,
+                    "a",
+                    "bbbbbb",
+                    "cc",
+                    "dddddddd"
+                )
+            };
+
+            // Three words don't fit on the last line, but two do.
+            assert_eq!(
+                guacamole(line(), &TWO.clone().min_last_line_words(0)).concat(),
+                "a bbbbbb cc\ndddddddd"
+            );
+            assert_eq!(
+                guacamole(line(), &TWO.clone().min_last_line_words(3)).concat(),
+                "a bbbbbb\ncc dddddddd"
+            );
+        }
+
+        #[test]
+        fn too_few_words() {
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "aaaaaaaa", "bbbbbbbb"),
+                    &TWO.clone().min_last_line_words(3)
+                ),
+                vec!["aaaaaaaa", "\n", "bbbbbbbb"]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::cjk].
+    mod cjk {
+        use super::*;
+        use crate::line;
+
+        #[test]
+        fn ambiguous_width() {
+            let line = line!(Space(0), None, Space(0), Some("•"), "§§", "§§", "§§");
+
+            assert_eq!(
+                all(line.clone(), &MINI_LINE),
+                vec![
+                    "•", " ", "§§", "\n", " ", " ", " ", "§§", "\n", " ", " ", " ", "§§"
+                ]
+            );
+            assert_eq!(
+                all(line, &MINI_LINE.clone().cjk(false)),
+                vec!["•", " ", "§§", " ", "§§", " ", "§§"]
+            );
+        }
+
+        #[test]
+        fn zwj_sequence() {
+            // A family emoji is as wide as a single one, either way.
+            let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}";
+            let line = || line!(Space(0), None, Space(0), None, family, family, family);
+
+            for toppings in [Toppings::default(), Toppings::default().cjk(false)] {
+                assert_eq!(
+                    all(line(), &toppings.width(5)),
+                    vec![family, " ", family, "\n", family]
+                );
+            }
+        }
+    }
+
+    /// Tests for width calculations that would overflow.
+    mod overflow {
+        use super::*;
+        use crate::line;
+
+        #[test]
+        fn huge_tab_width() {
+            assert_eq!(
+                all(
+                    line!(Tab(2), Some("#"), Tab(1), Some("-"), "foo", "bar"),
+                    &Toppings::default().tabs(usize::MAX)
+                ),
+                vec![
+                    "\t", "\t", "#", "\t", "-", " ", "foo", "\n", "\t", "\t", "#", "\t", " ", " ",
+                    "bar"
+                ]
+            );
+        }
+
+        #[test]
+        fn huge_tab_count() {
+            let toppings = Toppings::default().tabs(usize::MAX / 2).output_indent(1);
+            let line = line!(Tab(usize::MAX), Some("//"), Space(1), None, "foo", "bar");
+
+            assert_eq!(
+                super::super::unbreakable_width(&line, &toppings),
+                usize::MAX
+            );
+
+            // No room for words at all, but indentation is still emitted.
+            assert_eq!(
+                super::super::LineWrap::new::<Salsa>(line, &toppings)
+                    .take(3)
+                    .collect::<Vec<_>>(),
+                vec![" ", "\t", "\t"]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::expand_tabs].
+    mod expand_tabs {
+        use super::*;
+        use crate::line;
+
+        #[test]
+        fn indent_and_padding() {
+            assert_eq!(
+                all(
+                    line!(Tab(1), Some("#"), Tab(1), Some("-"), "foo", "bar"),
+                    &Toppings::default().width(12).tabs(2).expand_tabs(true)
+                ),
+                vec![
+                    " ", " ", "#", " ", " ", "-", " ", "foo", "\n", " ", " ", "#", " ", " ", " ",
+                    " ", "bar"
+                ]
+            );
+        }
+
+        #[test]
+        fn mixed_indent() {
+            let line = Line {
+                raw_indent: Some("\t  "),
+                ..line!(Space(6), None, Space(0), None, "foo", "bar")
+            };
+
+            assert_eq!(
+                all(line, &Toppings::default().width(9).expand_tabs(true)),
+                vec![
+                    " ", " ", " ", " ", " ", " ", "foo", "\n", " ", " ", " ", " ", " ", " ", "bar"
+                ]
+            );
+        }
+    }
+
+    /// Tests for [Mole].
+    mod mole {
+        use super::*;
+        use crate::line;
+
+        fn mole<'t>(line: Line<'t>, toppings: &Toppings) -> Vec<&'t str> {
+            super::super::LineWrap::new::<Mole>(line, toppings).collect()
+        }
+
+        #[test]
+        fn default_is_salsa() {
+            let lines = [
+                line!(Space(0), None, Space(0), None, "aaa", "bb", "cc", "ddddd"),
+                line!(
+                    Space(2),
+                    Some("#"),
+                    Space(1),
+                    Some("-"),
+                    "a",
+                    "bb",
+                    "ccc",
+                    "dd",
+                    "e"
+                ),
+                line!(Space(0), None, Space(0), None, "aaaaaaaaaaaa", "b", "cc"),
+            ];
+
+            for line in lines {
+                assert_eq!(mole(line.clone(), &MINI_LINE), salsa(line, &MINI_LINE));
+            }
+        }
+
+        #[test]
+        fn overfull() {
+            let line = || line!(Space(0), None, Space(0), None, "aaaa", "bbbb", "c");
+            let toppings = Toppings::default().width(8);
+
+            assert_eq!(
+                mole(line(), &toppings),
+                vec!["aaaa", "\n", "bbbb", " ", "c"]
+            );
+
+            let penalties = Penalties {
+                overfull: Some(1),
+                ..Penalties::default()
+            };
+            assert_eq!(
+                mole(line(), &toppings.penalties(penalties)),
+                vec!["aaaa", " ", "bbbb", "\n", "c"]
+            );
+        }
+
+        #[test]
+        fn underfull() {
+            let line = || line!(Space(0), None, Space(0), None, "aaaa", "bbbb", "c");
+            let toppings = Toppings::default().width(8);
+
+            // Underfull lines cost more than an overfull one.
+            let penalties = Penalties {
+                underfull: 4,
+                overfull: Some(3),
+                last_line: 0,
+            };
+            assert_eq!(
+                mole(line(), &toppings.clone().penalties(penalties)),
+                vec!["aaaa", " ", "bbbb", "\n", "c"]
+            );
+
+            // And the other way around.
+            let penalties = Penalties {
+                underfull: 1,
+                overfull: Some(17),
+                last_line: 0,
+            };
+            assert_eq!(
+                mole(line(), &toppings.penalties(penalties)),
+                vec!["aaaa", "\n", "bbbb", " ", "c"]
+            );
+        }
+    }
+
+    /// Tests for [Toppings::continuation].
+    mod continuation {
+        use super::*;
+        use crate::line;
+
+        #[test]
+        fn each_break() {
+            let line = Line {
+                continuation: Some("\\"),
+                ..line!(Space(2), Some("#"), Space(1), None, "foo", "bar", "baz")
+            };
+            assert_eq!(
+                all(line, &Toppings::default().width(9)).concat(),
+                "  # foo \\\n  # bar \\\n  # baz"
+            );
+        }
+
+        #[test]
+        fn single_line() {
+            let line = Line {
+                continuation: Some("\\"),
+                ..line!(Space(0), None, Space(0), None, "foo", "bar")
+            };
+            assert_eq!(all(line, &Toppings::default().width(9)).concat(), "foo bar");
+        }
+
+        #[test]
+        fn trailing_token() {
+            let line = Line {
+                continuation: Some("\\"),
+                ..line!(Space(0), None, Space(0), None, "foo", "bar", "\\")
+            };
+            assert_eq!(
+                all(line, &Toppings::default().width(9)).concat(),
+                "foo bar \\"
+            );
+
+            let line = Line {
+                continuation: Some("\\"),
+                ..line!(Space(0), None, Space(0), None, "foo", "bar", "baz", "\\")
+            };
+            assert_eq!(
+                all(line, &Toppings::default().width(9)).concat(),
+                "foo bar \\\nbaz \\"
+            );
+        }
+
+        #[test]
+        fn indentation() {
+            let toppings = Toppings::default().width(12).continuation("\\");
+            assert_eq!(
+                crate::wrap::<Salsa>("foo bar \\\n  baz qux quux\n", toppings.clone())
+                    .collect::<String>(),
+                "foo bar \\\n  baz qux \\\n  quux\n"
+            );
+            assert_eq!(
+                crate::wrap::<Salsa>("- foo \\\n    bar baz qux\n", toppings).collect::<String>(),
+                "- foo bar \\\n    baz \\\n    qux\n"
+            );
+        }
+    }
+
+    /// Tests for [Toppings::align].
+    mod align {
+        use super::*;
+        use crate::{Align, line};
+
+        #[test]
+        fn right() {
+            let line = line!(Space(0), None, Space(0), Some("-"), "foo", "bar", "baz");
+            assert_eq!(
+                all(line, &Toppings::default().width(10).align(Align::Right)).concat(),
+                " - foo bar\n       baz"
+            );
+        }
+
+        #[test]
+        fn center() {
+            let line = line!(Space(0), None, Space(0), None, "foo", "bar", "baz");
+            assert_eq!(
+                all(line, &Toppings::default().width(8).align(Align::Center)).concat(),
+                "foo bar\n  baz"
+            );
+        }
+
+        #[test]
+        fn wide() {
+            let line = line!(Space(1), Some("//"), Space(1), None, "日本");
+            assert_eq!(
+                all(line, &Toppings::default().width(10).align(Align::Right)).concat(),
+                "   // 日本"
+            );
+        }
+
+        #[test]
+        fn justified() {
+            let line = line!(
+                Space(0),
+                None,
+                Space(0),
+                None,
+                "foo",
+                "bar",
+                "baz",
+                "qux",
+                "quux"
+            );
+            let toppings = Toppings::default()
+                .width(12)
+                .justify(true)
+                .align(Align::Right);
+            assert_eq!(all(line, &toppings).concat(), "foo  bar baz\n    qux quux");
+        }
+
+        #[test]
+        fn auto() {
+            let input = "## Heading\n\nfoo bar baz\n";
+            let toppings = Toppings::default()
+                .width(14)
+                .markdown(true)
+                .align(Align::Auto);
+            assert_eq!(
+                crate::wrap::<Salsa>(input, toppings).collect::<String>(),
+                "  ## Heading\n\nfoo bar baz\n"
+            );
+        }
+
+        #[test]
+        fn auto_without_blank_line() {
+            let toppings = Toppings::default()
+                .width(14)
+                .markdown(true)
+                .align(Align::Auto);
+            assert_eq!(
+                crate::wrap::<Salsa>("## Heading\nfoo bar\n", toppings.clone()).collect::<String>(),
+                "  ## Heading\nfoo bar\n"
+            );
+            assert_eq!(
+                crate::wrap::<Salsa>("foo bar\n# Heading\n", toppings).collect::<String>(),
+                "foo bar\n  # Heading\n"
+            );
+        }
+
+        #[test]
+        fn auto_verbatim() {
+            let input = "# Heading\n---\n";
+            let toppings = Toppings::default()
+                .width(13)
+                .markdown(true)
+                .horizontal_rules(true)
+                .align(Align::Auto)
+                .auto_align(|_| Align::Right);
+            assert_eq!(
+                crate::wrap::<Salsa>(input, toppings).collect::<String>(),
+                "    # Heading\n---\n"
+            );
+        }
     }
 
-    /// Tests for inputs that yield just a single line.
-    mod single_line {
+    /// Tests for [Toppings::justify].
+    mod justify {
         use super::*;
-        use crate::{Newline, line};
+        use crate::line;
 
         #[test]
-        fn empty() {
+        fn spread() {
+            let line = line!(Space(0), None, Space(0), None, "a", "b", "c", "d", "eeeee");
             assert_eq!(
-                all(line!(Space(0), None, Space(0), None), &HUGE_LINE),
-                Vec::<&str>::new()
+                all(line, &Toppings::default().width(8).justify(true)).concat(),
+                "a  b c d\neeeee"
             );
         }
 
         #[test]
-        fn indent_space() {
+        fn single_word() {
+            let line = line!(Space(0), None, Space(0), None, "foo", "barbaz");
             assert_eq!(
-                all(line!(Space(4), None, Space(0), None), &HUGE_LINE),
-                vec![" ", " ", " ", " "]
+                all(line, &Toppings::default().width(7).justify(true)).concat(),
+                "foo\nbarbaz"
             );
         }
 
         #[test]
-        fn indent_tab() {
+        fn wide() {
+            let line = line!(Space(0), None, Space(0), None, "日本", "語", "foo");
             assert_eq!(
-                all(line!(Tab(2), None, Space(0), None), &HUGE_LINE),
-                vec!["\t", "\t"]
+                all(line, &Toppings::default().width(9).justify(true)).concat(),
+                "日本   語\nfoo"
             );
         }
 
         #[test]
-        fn comment() {
+        fn gap() {
+            let mut line = line!(Space(0), None, Space(0), None, "foo.", "bar", "baz", "qux");
+            line.gaps = vec![(1, Space(2))];
             assert_eq!(
-                all(line!(Space(0), Some("//"), Space(0), None), &HUGE_LINE),
-                vec!["//"]
+                all(line, &Toppings::default().width(14).justify(true)).concat(),
+                "foo.   bar baz\nqux"
             );
         }
 
         #[test]
-        fn padding_space() {
+        fn bullet() {
+            let line = line!(Space(0), None, Space(0), Some("-"), "a", "b", "c", "d");
             assert_eq!(
-                all(line!(Space(0), None, Space(4), None), &HUGE_LINE),
-                vec![" ", " ", " ", " "]
+                all(line, &Toppings::default().width(8).justify(true)).concat(),
+                "- a  b c\n  d"
             );
         }
+    }
+
+    /// Tests for [Toppings::min_width].
+    mod min_width {
+        use super::*;
+        use crate::line;
 
         #[test]
-        fn padding_tab() {
+        fn deep_indent() {
+            let line = || line!(Space(6), Some("//"), Space(1), None, "foo", "bar", "baz");
+
             assert_eq!(
-                all(line!(Space(0), None, Tab(2), None), &HUGE_LINE),
-                vec!["\t", "\t"]
+                all(line(), &MINI_LINE)
+                    .into_iter()
+                    .filter(|chunk| chunk.trim().len() > 2)
+                    .collect::<Vec<_>>(),
+                vec!["foo", "bar", "baz"]
             );
-        }
 
-        #[test]
-        fn bullet() {
             assert_eq!(
-                all(line!(Space(0), None, Space(0), Some("123.")), &HUGE_LINE),
-                vec!["123."]
+                all(line(), &MINI_LINE.clone().min_width(7)),
+                vec![
+                    " ", " ", " ", " ", " ", " ", "//", " ", "foo", " ", "bar", "\n", " ", " ",
+                    " ", " ", " ", " ", "//", " ", "baz"
+                ]
             );
         }
 
         #[test]
-        fn words() {
+        fn below_breakable_width() {
+            let line = || line!(Space(0), None, Space(0), None, "foo", "bar", "baz");
+
             assert_eq!(
-                all(
-                    line!(Space(0), None, Space(0), None, "foo", "bar", "baz"),
-                    &HUGE_LINE
-                ),
-                vec!["foo", " ", "bar", " ", "baz"]
+                all(line(), &MINI_LINE.clone().min_width(4)),
+                all(line(), &MINI_LINE)
             );
         }
+    }
+
+    /// Tests for [Toppings::first_line_indent].
+    mod first_line_indent {
+        use super::*;
+        use crate::line;
 
         #[test]
-        fn final_newline_lf() {
+        fn outdent() {
             assert_eq!(
-                all(line!(Space(0), None, Space(0), None ;), &HUGE_LINE),
-                vec!["\n"]
+                all(
+                    line!(Space(4), None, Space(0), None, "foo", "bar", "baz" ;),
+                    &MINI_LINE.clone().first_line_indent(-2)
+                ),
+                vec![
+                    " ", " ", "foo", " ", "bar", "\n", " ", " ", " ", " ", "baz", "\n"
+                ]
             );
         }
 
         #[test]
-        fn final_newline_crlf() {
+        fn outdent_tabs() {
             assert_eq!(
                 all(
-                    line!(Space(0), None, Space(0), None ;),
-                    &HUGE_LINE.clone().newline(Newline::CRLF)
+                    line!(Tab(1), None, Space(0), None, "foo", "bar"),
+                    &MINI_LINE.clone().first_line_indent(-6)
                 ),
-                vec!["\r\n"]
+                vec!["foo", " ", "bar"]
             );
         }
 
         #[test]
-        fn indent_and_padding() {
+        fn indent() {
             assert_eq!(
-                all(line!(Tab(2), None, Space(2), None), &HUGE_LINE),
-                vec!["\t", "\t", " ", " "]
+                all(
+                    line!(Tab(1), Some("#"), Space(1), None, "foo", "bar"),
+                    &MINI_LINE.clone().first_line_indent(2)
+                ),
+                vec!["\t", " ", " ", "#", " ", "foo", "\n", "\t", "#", " ", "bar"]
             );
         }
 
         #[test]
-        fn all_together() {
+        fn empty_line() {
             assert_eq!(
                 all(
-                    line!(Tab(2), Some("//"), Space(2), Some("-"), "foo", "bar", "baz" ;),
-                    &HUGE_LINE
+                    line!(Space(2), None, Space(0), None ;),
+                    &MINI_LINE.clone().first_line_indent(2)
                 ),
-                vec![
-                    "\t", "\t", "//", " ", " ", "-", " ", "foo", " ", "bar", " ", "baz", "\n"
-                ]
+                vec![" ", " ", "\n"]
             );
         }
     }
 
-    /// Tests for inputs that should yield several lines.
-    mod multi_line {
+    /// Tests for [super::LineWrap::with_widths].
+    mod widths {
         use super::*;
         use crate::line;
 
         #[test]
-        fn words() {
+        fn guacamole_widens() {
             assert_eq!(
-                all(
-                    line!(Space(0), None, Space(0), None, "foo", "bar", "baz"),
-                    &MINI_LINE
-                ),
-                vec!["foo", " ", "bar", "\n", "baz"]
+                super::super::LineWrap::with_widths::<Guacamole>(
+                    line!(Space(0), None, Space(0), None, "a", "b", "c", "d", "e", "f"),
+                    &Toppings::default(),
+                    &mut |line_idx| if line_idx == 0 { 3 } else { 7 },
+                )
+                .collect::<Vec<_>>(),
+                vec!["a", " ", "b", "\n", "c", " ", "d", " ", "e", " ", "f"]
             );
         }
 
         #[test]
-        fn big_word() {
+        fn salsa_widens() {
             assert_eq!(
-                all(
-                    line!(Space(0), None, Space(0), None, "foo", "barbarbarbar", "baz"),
-                    &MINI_LINE
-                ),
-                vec!["foo", "\n", "barbarbarbar", "\n", "baz"]
+                super::super::LineWrap::with_widths::<Salsa>(
+                    line!(Space(0), None, Space(0), None, "a", "b", "c", "d", "e", "f"),
+                    &Toppings::default(),
+                    &mut |line_idx| if line_idx == 0 { 3 } else { 7 },
+                )
+                .collect::<Vec<_>>(),
+                vec!["a", " ", "b", "\n", "c", " ", "d", " ", "e", " ", "f"]
             );
         }
 
         #[test]
-        fn indent_space() {
+        fn glued_units_respect_widths() {
             assert_eq!(
-                all(
-                    line!(Space(2), None, Space(0), None, "foo", "bar", "baz" ;),
-                    &MINI_LINE.clone().tabs(1)
-                ),
-                vec![" ", " ", "foo", " ", "bar", "\n", " ", " ", "baz", "\n"]
+                super::super::LineWrap::with_widths::<Guacamole>(
+                    line!(Space(0), None, Space(0), None, "a", "b", "c", "d", "e", "f"),
+                    &Toppings::default().keep(|_, next| match next {
+                        "d" => KeepRule::KeepWithPrevious,
+                        _ => KeepRule::Allow,
+                    }),
+                    &mut |line_idx| if line_idx == 0 { 5 } else { 7 },
+                )
+                .collect::<Vec<_>>(),
+                vec!["a", " ", "b", "\n", "c", " ", "d", " ", "e", " ", "f"]
             );
         }
+    }
+
+    /// Tests for [Toppings::keep].
+    mod keep {
+        use super::*;
+        use crate::line;
+
+        fn section(word: &str, _: &str) -> KeepRule {
+            match word {
+                "§" => KeepRule::KeepWithNext,
+                _ => KeepRule::Allow,
+            }
+        }
 
         #[test]
-        fn indent_low_tabs() {
+        fn without_rule() {
             assert_eq!(
                 all(
-                    line!(Tab(2), None, Space(0), None, "foo", "bar", "baz" ;),
-                    &MINI_LINE.clone().tabs(1)
+                    line!(
+                        Space(0),
+                        None,
+                        Space(0),
+                        None,
+                        "foo",
+                        "bar",
+                        "§",
+                        "5",
+                        "qux"
+                    ),
+                    &MINI_LINE
                 ),
-                vec!["\t", "\t", "foo", " ", "bar", "\n", "\t", "\t", "baz", "\n"]
+                vec!["foo", " ", "bar", " ", "§", "\n", "5", " ", "qux"]
             );
         }
 
         #[test]
-        fn indent_high_tabs() {
+        fn section_kept_with_number() {
             assert_eq!(
                 all(
-                    line!(Tab(2), None, Space(0), None, "foo", "bar", "baz"),
-                    &MINI_LINE.clone().tabs(8)
+                    line!(
+                        Space(0),
+                        None,
+                        Space(0),
+                        None,
+                        "foo",
+                        "bar",
+                        "§",
+                        "5",
+                        "qux"
+                    ),
+                    &MINI_LINE.clone().keep(section)
                 ),
-                vec![
-                    "\t", "\t", "foo", "\n", "\t", "\t", "bar", "\n", "\t", "\t", "baz"
-                ]
+                vec!["foo", " ", "bar", "\n", "§", " ", "5", " ", "qux"]
             );
         }
 
         #[test]
-        fn comment() {
+        fn glued_chain() {
             assert_eq!(
                 all(
-                    line!(Space(0), Some("//"), Space(1), None, "foo", "bar", "baz"),
-                    &MINI_LINE
+                    line!(Space(0), None, Space(0), None, "a", "§", "§", "5", "b"),
+                    &Toppings::default().width(6).keep(section)
                 ),
-                vec!["//", " ", "foo", " ", "bar", "\n", "//", " ", "baz"]
+                vec!["a", "\n", "§", " ", "§", " ", "5", "\n", "b"]
             );
         }
 
         #[test]
-        fn bullet() {
+        fn brackets() {
+            let toppings = Toppings::default().width(8).keep_brackets(true);
+
             assert_eq!(
                 all(
-                    line!(Space(0), None, Space(0), Some("1."), "foo", "bar", "baz"),
-                    &MINI_LINE
+                    line!(Space(0), None, Space(0), None, "foo", "(", "bar", ")"),
+                    &toppings
                 ),
-                vec!["1.", " ", "foo", " ", "bar", "\n", " ", " ", " ", "baz"]
+                vec!["foo", "\n", "(", " ", "bar", " ", ")"]
+            );
+            assert_eq!(
+                all(
+                    line!(Space(0), None, Space(0), None, "foo", "[bar", "baz]", "qux"),
+                    &toppings
+                ),
+                vec!["foo", " ", "[bar", "\n", "baz]", " ", "qux"]
             );
         }
 
         #[test]
-        fn all_together() {
+        fn brackets_and_custom_rule() {
+            let toppings = Toppings::default()
+                .width(6)
+                .keep(section)
+                .keep_brackets(true);
+
             assert_eq!(
-                all(
-                    line!(Tab(2), Some("#"), Space(1), Some("-"), "foo", "bar", "baz" ;),
-                    &MINI_LINE
+                salsa(
+                    line!(
+                        Space(0),
+                        None,
+                        Space(0),
+                        None,
+                        "a",
+                        "§",
+                        "5",
+                        "b",
+                        "{",
+                        "c",
+                        "}"
+                    ),
+                    &toppings
                 ),
                 vec![
-                    "\t", "\t", "#", " ", "-", " ", "foo", "\n", "\t", "\t", "#", " ", " ", " ",
-                    "bar", "\n", "\t", "\t", "#", " ", " ", " ", "baz", "\n"
+                    "a", "\n", "§", " ", "5", " ", "b", "\n", "{", " ", "c", " ", "}"
                 ]
             );
         }
@@ -526,6 +4396,51 @@ mod tests {
         use super::*;
         use crate::line;
 
+        #[test]
+        fn minimax_is_narrower() {
+            let line = line!(
+                Space(0),
+                None,
+                Space(0),
+                None,
+                "aaaa",
+                "b",
+                "c",
+                "d",
+                "eeee",
+                "f"
+            );
+
+            assert_eq!(
+                salsa(line.clone(), &MINI_LINE),
+                vec!["aaaa", " ", "b", " ", "c", " ", "d", "\n", "eeee", " ", "f"]
+            );
+            assert_eq!(
+                super::super::LineWrap::new::<super::super::Minimax>(line, &MINI_LINE)
+                    .collect::<Vec<_>>(),
+                vec!["aaaa", " ", "b", " ", "c", "\n", "d", " ", "eeee", " ", "f"]
+            );
+        }
+
+        #[test]
+        fn words_per_line() {
+            #[rustfmt::skip]
+            assert_eq!(
+                super::super::LineWrap::new::<super::super::WordsPerLine>(
+                    line!(Space(0), None, Space(0), None,
+                        "a", "bbbbbbbbbbbb", "c", "d", "e", "f", "g", "h", "i", "j"),
+                    &MINI_LINE.clone().words_per_line(3)
+                )
+                .collect::<Vec<_>>(),
+                vec![
+                    "a", " ", "bbbbbbbbbbbb", " ", "c", "\n",
+                    "d", " ", "e", " ", "f", "\n",
+                    "g", " ", "h", " ", "i", "\n",
+                    "j"
+                ],
+            );
+        }
+
         #[test]
         fn guacamole_is_suboptimal() {
             #[rustfmt::skip]
@@ -565,6 +4480,190 @@ mod tests {
                 ],
             );
         }
+
+        #[test]
+        fn salsa_breaks_ties_early() {
+            // "a / b c / ddd" and "a b / c / ddd" are equally ragged.
+            let toppings = Toppings::default().width(4);
+
+            #[rustfmt::skip]
+            assert_eq!(
+                salsa(line!(Space(0), None, Space(0), None, "a", "b", "c", "ddd"), &toppings),
+                vec!["a", "\n", "b", " ", "c", "\n", "ddd"],
+            );
+
+            #[rustfmt::skip]
+            assert_eq!(
+                salsa_min_lines(line!(Space(0), None, Space(0), None, "a", "b", "c", "ddd"), &toppings),
+                vec!["a", "\n", "b", " ", "c", "\n", "ddd"],
+            );
+        }
+
+        #[test]
+        fn salsa_min_lines_is_optimal_if_salsa_is_minimal() {
+            // Guacamole needs five lines as well, see guacamole_is_suboptimal,
+            // which leaves Salsa's breaks as the least ragged ones.
+            #[rustfmt::skip]
+            assert_eq!(
+                salsa_min_lines(
+                    line!(Space(0), None, Space(0), None,
+                        "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k",
+                        "l", "m", "n", "o", "p", "qqqqqqqqq" ;),
+                    &MINI_LINE
+                ),
+                vec![
+                    "a", " ", "b", " ", "c", " ", "d", "\n",
+                    "e", " ", "f", " ", "g", " ", "h", "\n",
+                    "i", " ", "j", " ", "k", " ", "l", "\n",
+                    "m", " ", "n", " ", "o", " ", "p", "\n",
+                    "qqqqqqqqq", "\n"
+                ],
+            );
+        }
+
+        #[test]
+        fn salsa_min_lines_uses_fewer_lines_than_salsa() {
+            let toppings = Toppings::default().width(12);
+
+            #[rustfmt::skip]
+            assert_eq!(
+                salsa(
+                    line!(Space(0), None, Space(0), None,
+                        "dddd", "bb", "ccc", "dddd", "ggggggg", "ccc", "a", "hhhhhhhh", "bb"),
+                    &toppings
+                ),
+                vec![
+                    "dddd", " ", "bb", "\n",
+                    "ccc", " ", "dddd", "\n",
+                    "ggggggg", " ", "ccc", "\n",
+                    "a", " ", "hhhhhhhh", "\n",
+                    "bb"
+                ],
+            );
+
+            #[rustfmt::skip]
+            assert_eq!(
+                salsa_min_lines(
+                    line!(Space(0), None, Space(0), None,
+                        "dddd", "bb", "ccc", "dddd", "ggggggg", "ccc", "a", "hhhhhhhh", "bb"),
+                    &toppings
+                ),
+                vec![
+                    "dddd", " ", "bb", " ", "ccc", "\n",
+                    "dddd", " ", "ggggggg", "\n",
+                    "ccc", " ", "a", "\n",
+                    "hhhhhhhh", " ", "bb"
+                ],
+            );
+        }
+
+        #[test]
+        fn salsa_min_lines_is_less_ragged_than_guacamole() {
+            let toppings = Toppings::default().width(12);
+
+            #[rustfmt::skip]
+            assert_eq!(
+                guacamole(
+                    line!(Space(0), None, Space(0), None,
+                        "ffffff", "dddd", "ffffff", "ffffff", "a", "ffffff", "bb"),
+                    &toppings
+                ),
+                vec![
+                    "ffffff", " ", "dddd", "\n",
+                    "ffffff", "\n",
+                    "ffffff", " ", "a", "\n",
+                    "ffffff", " ", "bb"
+                ],
+            );
+
+            #[rustfmt::skip]
+            assert_eq!(
+                salsa_min_lines(
+                    line!(Space(0), None, Space(0), None,
+                        "ffffff", "dddd", "ffffff", "ffffff", "a", "ffffff", "bb"),
+                    &toppings
+                ),
+                vec![
+                    "ffffff", "\n",
+                    "dddd", " ", "ffffff", "\n",
+                    "ffffff", " ", "a", "\n",
+                    "ffffff", " ", "bb"
+                ],
+            );
+        }
+    }
+
+    /// Tests for [break_indices].
+    mod break_indices {
+        use super::super::{Auto, Guacamole, Salsa, break_indices};
+        use crate::Toppings;
+
+        const WORDS: [&str; 17] = [
+            "a",
+            "b",
+            "c",
+            "d",
+            "e",
+            "f",
+            "g",
+            "h",
+            "i",
+            "j",
+            "k",
+            "l",
+            "m",
+            "n",
+            "o",
+            "p",
+            "qqqqqqqqq",
+        ];
+
+        /// Same breaks as in [super::sauce_probing::guacamole_is_suboptimal].
+        #[test]
+        fn guacamole_is_suboptimal() {
+            assert_eq!(
+                break_indices::<Guacamole>(&WORDS, 10, &Toppings::default()),
+                [5, 10, 15, 16]
+            );
+        }
+
+        /// Same breaks as in [super::sauce_probing::salsa_is_optimal].
+        #[test]
+        fn salsa_is_optimal() {
+            assert_eq!(
+                break_indices::<Salsa>(&WORDS, 10, &Toppings::default()),
+                [4, 8, 12, 16]
+            );
+        }
+
+        #[test]
+        fn auto() {
+            assert_eq!(
+                break_indices::<Auto<18>>(&WORDS, 10, &Toppings::default()),
+                break_indices::<Salsa>(&WORDS, 10, &Toppings::default())
+            );
+            assert_eq!(
+                break_indices::<Auto<17>>(&WORDS, 10, &Toppings::default()),
+                break_indices::<Guacamole>(&WORDS, 10, &Toppings::default())
+            );
+        }
+
+        #[test]
+        fn cjk() {
+            let words = ["§§§", "§§"];
+            let toppings = Toppings::default();
+            assert_eq!(break_indices::<Salsa>(&words, 6, &toppings), [1]);
+            assert_eq!(break_indices::<Salsa>(&words, 6, &toppings.cjk(false)), []);
+        }
+
+        #[test]
+        fn no_words() {
+            assert_eq!(break_indices::<Salsa>(&[], 10, &Toppings::default()), []);
+            assert_eq!(
+                break_indices::<Guacamole>(&[], 10, &Toppings::default()),
+                []
+            );
+        }
     }
 
     // ... we could do more here, but I'm good.